@@ -0,0 +1,39 @@
+use ad_blocker_api::StevenBlackBlocker;
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+/// Domains on no blocklist, which the bloom filter should rule out without
+/// walking the trie
+const ALLOWED: [&str; 6] = [
+    "www.example.org",
+    "news.example.com",
+    "cdn.static.example.net",
+    "api.github.com",
+    "mail.example.co.uk",
+    "images.example.org",
+];
+
+fn hosts_lookup(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let blocker = StevenBlackBlocker::empty();
+    runtime.block_on(async {
+        for i in 0..100_000 {
+            blocker.add_blocked_domain(&format!("ads{}.tracker{}.example", i, i % 100)).await;
+        }
+    });
+    
+    c.bench_function("is_blocked_allowed_domain", |b| {
+        b.iter(|| {
+            for domain in ALLOWED {
+                black_box(runtime.block_on(blocker.is_blocked(black_box(domain))));
+            }
+        })
+    });
+    
+    c.bench_function("is_blocked_blocked_domain", |b| {
+        b.iter(|| black_box(runtime.block_on(blocker.is_blocked(black_box("banner.ads42.tracker42.example")))))
+    });
+}
+
+criterion_group!(benches, hosts_lookup);
+criterion_main!(benches);
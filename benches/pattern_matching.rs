@@ -0,0 +1,34 @@
+use ad_blocker_api::{AdBlockerAPI, AdBlockerConfig};
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+/// URLs that fall through the filter engine to the tracking and social patterns
+const URLS: [&str; 6] = [
+    "https://www.google-analytics.com/collect?v=1&t=pageview",
+    "https://www.facebook.com/plugins/like.php?href=https://news.example/",
+    "https://platform.twitter.com/widgets.js",
+    "https://cdn.example/static/app.js",
+    "https://news.example/article/42?ref=home",
+    "https://images.example/photos/large/1.jpg",
+];
+
+fn pattern_matching(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let config = AdBlockerConfig {
+        block_social: true,
+        regex_filters: vec![r"/promo/\d+".to_string(), r"/creatives?/\d+x\d+\.".to_string()],
+        ..AdBlockerConfig::default()
+    };
+    let blocker = runtime.block_on(AdBlockerAPI::from_rules(Vec::new(), config)).unwrap();
+    
+    c.bench_function("should_block_patterns", |b| {
+        b.iter(|| {
+            for url in URLS {
+                black_box(runtime.block_on(blocker.should_block(black_box(url), None)).unwrap());
+            }
+        })
+    });
+}
+
+criterion_group!(benches, pattern_matching);
+criterion_main!(benches);
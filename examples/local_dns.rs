@@ -1,4 +1,6 @@
+use ad_blocker_api::dns::{DnsForwarder, MAX_UDP_PAYLOAD, blocked_response, fit_response, parse_query_name};
 use ad_blocker_api::prelude::*;
+use ad_blocker_api::{DnsCache, RateLimiter};
 use anyhow::Result;
 use std::net::{SocketAddr, UdpSocket};
 use std::sync::Arc;
@@ -12,8 +14,8 @@ async fn main() -> Result<()> {
     println!("Uses port 5353 (no root required)");
     println!();
     
-    // Create StevenBlack blocker
-    let blocker = Arc::new(StevenBlackBlocker::new().await?);
+    // Create StevenBlack blocker (any DomainBlocker, e.g. SimpleAdBlocker, works too)
+    let blocker: Arc<dyn DomainBlocker> = Arc::new(StevenBlackBlocker::new().await?);
     
     // Get local IP
     let local_ip = get_local_ip().unwrap_or_else(|| "127.0.0.1".to_string());
@@ -43,6 +45,12 @@ async fn main() -> Result<()> {
     
     let stats = Arc::new(RwLock::new(LocalDnsStats::default()));
     
+    // 20 queries/sec per client with bursts of 50, so the server can't be
+    // used to flood a spoofed source address
+    let rate_limiter = RateLimiter::new(20.0, 50);
+    let cache = Arc::new(DnsCache::new());
+    let forwarder = Arc::new(DnsForwarder::new("8.8.8.8:53".parse()?));
+    
     // Spawn stats reporter
     let stats_clone = stats.clone();
     tokio::spawn(async move {
@@ -64,17 +72,23 @@ async fn main() -> Result<()> {
     
     // Main DNS server loop
     loop {
-        let mut buffer = [0; 512];
+        let mut buffer = [0; MAX_UDP_PAYLOAD];
         let (size, client_addr) = socket.recv_from(&mut buffer)?;
         
+        if !rate_limiter.check(client_addr.ip()) {
+            continue;
+        }
+        
         let query_data = buffer[..size].to_vec();
         let blocker_clone = blocker.clone();
         let stats_clone = stats.clone();
+        let cache_clone = cache.clone();
+        let forwarder_clone = forwarder.clone();
         let socket_clone = socket.try_clone()?;
         
         // Handle DNS query in background
         tokio::spawn(async move {
-            match handle_dns_query(&query_data, client_addr, &blocker_clone, &stats_clone).await {
+            match handle_dns_query(&query_data, client_addr, &*blocker_clone, &stats_clone, &cache_clone, &forwarder_clone).await {
                 Ok(response) => {
                     if let Err(e) = socket_clone.send_to(&response, client_addr) {
                         eprintln!("Error sending DNS response: {}", e);
@@ -98,25 +112,27 @@ struct LocalDnsStats {
 async fn handle_dns_query(
     query_data: &[u8], 
     client_addr: SocketAddr, 
-    blocker: &StevenBlackBlocker,
-    stats: &Arc<RwLock<LocalDnsStats>>
+    blocker: &dyn DomainBlocker,
+    stats: &Arc<RwLock<LocalDnsStats>>,
+    cache: &DnsCache,
+    forwarder: &DnsForwarder,
 ) -> Result<Vec<u8>> {
     let mut current_stats = stats.write().await;
     current_stats.total_queries += 1;
     drop(current_stats);
     
-    // Simple DNS parsing (just extract domain name)
-    if let Some(domain) = parse_simple_dns_query(query_data) {
+    // Extract the queried domain name
+    if let Some(domain) = parse_query_name(query_data) {
         println!("📱 DNS Query from {}: {}", client_addr.ip(), domain);
         
         // Check if domain should be blocked
-        if blocker.is_blocked(&domain).await {
+        if blocker.is_domain_blocked(&domain).await {
             let mut current_stats = stats.write().await;
             current_stats.blocked_queries += 1;
             drop(current_stats);
             
             println!("   🚫 BLOCKED: {}", domain);
-            return Ok(create_blocked_dns_response(query_data));
+            return Ok(blocked_response(query_data).unwrap_or_default());
         }
         
         println!("   ✅ ALLOWED: {}", domain);
@@ -127,89 +143,17 @@ async fn handle_dns_query(
     current_stats.forwarded_queries += 1;
     drop(current_stats);
     
-    forward_to_upstream_dns(query_data).await
-}
-
-fn parse_simple_dns_query(data: &[u8]) -> Option<String> {
-    if data.len() < 12 {
-        return None;
-    }
-    
-    // Skip DNS header (12 bytes)
-    let mut pos = 12;
-    let mut domain_parts = Vec::new();
-    
-    while pos < data.len() {
-        let len = data[pos] as usize;
-        if len == 0 {
-            break;
-        }
-        
-        pos += 1;
-        if pos + len > data.len() {
-            break;
-        }
-        
-        let part = String::from_utf8_lossy(&data[pos..pos + len]);
-        domain_parts.push(part.to_string());
-        pos += len;
-    }
-    
-    if domain_parts.is_empty() {
-        None
-    } else {
-        Some(domain_parts.join("."))
-    }
-}
-
-fn create_blocked_dns_response(query: &[u8]) -> Vec<u8> {
-    let mut response = query.to_vec();
-    
-    // Set response flags (QR=1, AA=1, RA=1)
-    if response.len() >= 3 {
-        response[2] = 0x81;
-        response[3] = 0x80;
-    }
-    
-    // Add answer section pointing to 0.0.0.0 (blocked)
-    response.extend_from_slice(&[
-        0xc0, 0x0c, // Name pointer to query
-        0x00, 0x01, // Type A
-        0x00, 0x01, // Class IN
-        0x00, 0x00, 0x00, 0x3c, // TTL (60 seconds)
-        0x00, 0x04, // Data length
-        0x00, 0x00, 0x00, 0x00, // IP address 0.0.0.0 (blocked)
-    ]);
-    
-    // Update answer count
-    if response.len() >= 8 {
-        response[6] = 0x00;
-        response[7] = 0x01;
-    }
-    
-    response
-}
-
-async fn forward_to_upstream_dns(query_data: &[u8]) -> Result<Vec<u8>> {
-    // Forward to Google DNS
-    let upstream_socket = UdpSocket::bind("0.0.0.0:0")?;
-    upstream_socket.connect("8.8.8.8:53")?;
-    
-    upstream_socket.send(query_data)?;
-    
-    let mut buffer = [0; 512];
-    let size = upstream_socket.recv(&mut buffer)?;
-    
-    Ok(buffer[..size].to_vec())
+    let response = cache.resolve(query_data, || forwarder.forward(query_data)).await?;
+    Ok(fit_response(query_data, response))
 }
 
 fn get_local_ip() -> Option<String> {
     use std::net::TcpStream;
     
-    if let Ok(stream) = TcpStream::connect("8.8.8.8:80") {
-        if let Ok(local_addr) = stream.local_addr() {
-            return Some(local_addr.ip().to_string());
-        }
+    if let Ok(stream) = TcpStream::connect("8.8.8.8:80")
+        && let Ok(local_addr) = stream.local_addr()
+    {
+        return Some(local_addr.ip().to_string());
     }
     
     None
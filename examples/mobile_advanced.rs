@@ -110,7 +110,7 @@ impl AdvancedMobileClient {
             ..Default::default()
         };
         
-        let mut client_builder = Client::builder()
+        let client_builder = Client::builder()
             .timeout(Duration::from_secs(15))
             .user_agent("MobileApp/2.0 (iOS; AdBlocker)");
             
@@ -153,7 +153,7 @@ impl AdvancedMobileClient {
             let bytes_saved = self.estimate_blocked_bytes(&block_result.category);
             return Ok(MobileResponse::Blocked {
                 url: url.to_string(),
-                reason: block_result.reason,
+                reason: block_result.reason.to_string(),
                 category: block_result.category,
                 time_saved_ms: 150,
                 bytes_saved,
@@ -1,8 +1,7 @@
 use ad_blocker_api::prelude::*;
 use anyhow::Result;
-use reqwest::{Client, Response, Url};
+use reqwest::{Client, Response};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
@@ -13,7 +12,7 @@ pub struct MobileAdBlockingClient {
     stats: ClientStats,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ClientStats {
     pub total_requests: u64,
     pub blocked_requests: u64,
@@ -21,17 +20,6 @@ pub struct ClientStats {
     pub time_saved_ms: u64,
 }
 
-impl Default for ClientStats {
-    fn default() -> Self {
-        Self {
-            total_requests: 0,
-            blocked_requests: 0,
-            bytes_saved: 0,
-            time_saved_ms: 0,
-        }
-    }
-}
-
 impl MobileAdBlockingClient {
     /// Create a new mobile client with ad blocking
     pub async fn new() -> Result<Self> {
@@ -40,6 +28,8 @@ impl MobileAdBlockingClient {
             enable_easylist: true,
             enable_easyprivacy: false, // Disabled for performance
             enable_malware_protection: false,
+            enable_phishing_protection: false,
+            enable_cryptomining: false,
             block_tracking: true,
             block_social: false,
             aggressive_blocking: false,
@@ -58,6 +48,7 @@ impl MobileAdBlockingClient {
                 "api.twitter.com".to_string(),
                 "graph.facebook.com".to_string(),
             ],
+            ..AdBlockerConfig::default()
         };
         
         let client = Client::builder()
@@ -135,8 +126,7 @@ impl MobileAdBlockingClient {
     
     /// Add custom filter
     pub async fn add_custom_filter(&mut self, filter: String) -> Result<()> {
-        // Note: This would require rebuilding the blocker in a real implementation
-        println!("Custom filter added: {}", filter);
+        self.blocker.add_custom_filter(filter).await?;
         Ok(())
     }
     
@@ -1,3 +1,5 @@
+use ad_blocker_api::block_page_html;
+use ad_blocker_api::proxy::tunnel;
 use ad_blocker_api::prelude::*;
 use anyhow::Result;
 use std::net::SocketAddr;
@@ -38,19 +40,19 @@ async fn main() -> Result<()> {
     let listener = TcpListener::bind(addr).await?;
     println!("✅ Proxy server running! Press Ctrl+C to stop.");
     
-    let mut request_count = 0u64;
-    let mut blocked_count = 0u64;
-    
     let mut request_count = 0u64;
     
     loop {
         let (stream, _) = listener.accept().await?;
         request_count += 1;
         
-        // Handle connection directly (simplified for demo)
-        if let Err(e) = handle_connection(stream, &blocker, request_count).await {
-            eprintln!("Connection #{} error: {}", request_count, e);
-        }
+        // Handle each connection in its own task so HTTPS tunnels don't hold up the rest
+        let blocker = blocker.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &blocker, request_count).await {
+                eprintln!("Connection #{} error: {}", request_count, e);
+            }
+        });
     }
 }
 
@@ -77,12 +79,12 @@ async fn handle_connection(mut stream: TcpStream, blocker: &SimpleAdBlocker, req
     // Handle CONNECT method (HTTPS tunneling)
     if method == "CONNECT" {
         let host_port = url_part;
-        let url = format!("https://{}", host_port.split(':').next().unwrap_or(host_port));
+        let host = host_port.split(':').next().unwrap_or(host_port);
         
-        println!("📱 Request #{}: {} (HTTPS)", req_num, url);
+        println!("📱 Request #{}: {} (HTTPS)", req_num, host);
         
         // Check if should be blocked
-        let block_result = blocker.check_url(&url).await?;
+        let block_result = blocker.check_http_request(host, "/", "https", None).await?;
         
         if block_result.should_block {
             println!("   🚫 BLOCKED: {} ({:?})", block_result.reason, block_result.category);
@@ -95,61 +97,34 @@ async fn handle_connection(mut stream: TcpStream, blocker: &SimpleAdBlocker, req
         
         println!("   ✅ ALLOWED: Tunneling HTTPS connection");
         
-        // Establish tunnel to target server
-        match TcpStream::connect(host_port).await {
-            Ok(target) => {
-                // Send 200 Connection Established
-                let response = "HTTP/1.1 200 Connection Established\r\n\r\n";
-                stream.write_all(response.as_bytes()).await?;
-                
-                // Start bidirectional copying
-                let (mut client_read, mut client_write) = stream.into_split();
-                let (mut target_read, mut target_write) = target.into_split();
-                
-                let client_to_target = tokio::io::copy(&mut client_read, &mut target_write);
-                let target_to_client = tokio::io::copy(&mut target_read, &mut client_write);
-                
-                // Wait for either direction to close
-                tokio::select! {
-                    _ = client_to_target => {},
-                    _ = target_to_client => {},
-                }
-            }
-            Err(e) => {
-                println!("   ❌ Failed to connect to {}: {}", host_port, e);
-                let response = "HTTP/1.1 502 Bad Gateway\r\n\r\n";
-                let _ = stream.write_all(response.as_bytes()).await;
-            }
+        // Tunnel to the target server until either side hangs up
+        if let Err(e) = tunnel(stream, host_port).await {
+            println!("   ❌ Tunnel to {} failed: {}", host_port, e);
         }
     } else {
         // Handle regular HTTP requests
-        let url = if url_part.starts_with("http") {
-            url_part.to_string()
+        let (url, block_result) = if url_part.starts_with("http") {
+            (url_part.to_string(), blocker.check_url(url_part).await?)
         } else {
             // Extract host from headers
-            let mut host = "";
-            for line in &lines[1..] {
-                if line.to_lowercase().starts_with("host:") {
-                    host = line[5..].trim();
-                    break;
-                }
-            }
-            if host.is_empty() {
+            let Some(host) = lines[1..]
+                .iter()
+                .find(|line| line.to_lowercase().starts_with("host:"))
+                .map(|line| line[5..].trim())
+            else {
                 return Ok(());
-            }
-            format!("http://{}{}", host, url_part)
+            };
+            let block_result = blocker.check_http_request(host, url_part, "http", None).await?;
+            (format!("http://{}{}", host, url_part), block_result)
         };
         
         println!("📱 Request #{}: {} (HTTP)", req_num, url);
         
-        // Check if should be blocked
-        let block_result = blocker.check_url(&url).await?;
-        
         if block_result.should_block {
             println!("   🚫 BLOCKED: {} ({:?})", block_result.reason, block_result.category);
             
             // Send blocked response
-            let response = create_blocked_response(&url, &block_result.reason);
+            let response = create_blocked_response(&url, &block_result.reason.to_string(), block_result.category);
             let _ = stream.write_all(response.as_bytes()).await;
             return Ok(());
         }
@@ -164,54 +139,8 @@ async fn handle_connection(mut stream: TcpStream, blocker: &SimpleAdBlocker, req
     Ok(())
 }
 
-fn create_blocked_response(url: &str, reason: &str) -> String {
-    let html = format!(r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <title>🚫 Ad Blocked</title>
-    <meta name="viewport" content="width=device-width, initial-scale=1">
-    <style>
-        body {{ 
-            font-family: -apple-system, BlinkMacSystemFont, sans-serif;
-            text-align: center; 
-            padding: 50px; 
-            background: #f5f5f5;
-            color: #333;
-        }}
-        .container {{ 
-            background: white; 
-            padding: 30px; 
-            border-radius: 10px; 
-            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
-            max-width: 400px;
-            margin: 0 auto;
-        }}
-        .emoji {{ font-size: 48px; margin-bottom: 20px; }}
-        h1 {{ color: #e74c3c; margin: 0 0 10px 0; }}
-        .url {{ 
-            background: #f8f9fa; 
-            padding: 10px; 
-            border-radius: 5px; 
-            word-break: break-all;
-            font-size: 12px;
-            margin: 15px 0;
-        }}
-        .reason {{ color: #666; font-size: 14px; }}
-    </style>
-</head>
-<body>
-    <div class="container">
-        <div class="emoji">🛡️</div>
-        <h1>Ad Blocked!</h1>
-        <p class="reason">{}</p>
-        <div class="url">{}</div>
-        <p><small>Protected by Rust Ad Blocker</small></p>
-    </div>
-</body>
-</html>
-"#, reason, url);
-
+fn create_blocked_response(url: &str, reason: &str, category: BlockCategory) -> String {
+    let html = block_page_html(url, reason, category);
     format!(
         "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n{}",
         html.len(),
@@ -223,10 +152,10 @@ fn get_local_ip() -> Option<String> {
     use std::net::TcpStream;
     
     // Try to connect to a remote address to determine local IP
-    if let Ok(stream) = TcpStream::connect("8.8.8.8:80") {
-        if let Ok(local_addr) = stream.local_addr() {
-            return Some(local_addr.ip().to_string());
-        }
+    if let Ok(stream) = TcpStream::connect("8.8.8.8:80")
+        && let Ok(local_addr) = stream.local_addr()
+    {
+        return Some(local_addr.ip().to_string());
     }
     
     None
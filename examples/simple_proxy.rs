@@ -1,3 +1,5 @@
+use ad_blocker_api::escape_html;
+use ad_blocker_api::proxy::tunnel;
 use ad_blocker_api::prelude::*;
 use anyhow::Result;
 use std::net::SocketAddr;
@@ -65,7 +67,7 @@ async fn main() -> Result<()> {
                                 
                                 // For CONNECT requests (HTTPS), establish tunnel
                                 if method == "CONNECT" {
-                                    handle_connect(&mut stream, &url).await;
+                                    handle_connect(stream, &url);
                                 } else {
                                     // For HTTP requests, send a simple response
                                     let response = create_allowed_response(&url);
@@ -81,7 +83,7 @@ async fn main() -> Result<()> {
                     }
                     
                     // Show stats every 10 requests
-                    if request_count % 10 == 0 {
+                    if request_count.is_multiple_of(10) {
                         let block_rate = (blocked_count as f64 / request_count as f64) * 100.0;
                         println!("📊 Stats: {}/{} requests blocked ({:.1}%)", 
                             blocked_count, request_count, block_rate);
@@ -131,7 +133,9 @@ fn parse_request(request: &str) -> Option<(String, String)> {
     }
 }
 
-async fn handle_connect(stream: &mut TcpStream, url: &str) {
+/// Tunnel an allowed HTTPS connection in the background, so the proxy keeps
+/// accepting requests while it is open
+fn handle_connect(stream: TcpStream, url: &str) {
     // Extract host from URL
     let host = url.replace("https://", "").replace("http://", "");
     let host_port = if host.contains(':') {
@@ -140,22 +144,11 @@ async fn handle_connect(stream: &mut TcpStream, url: &str) {
         format!("{}:443", host)
     };
     
-    // Try to connect to the target server
-    match TcpStream::connect(&host_port).await {
-        Ok(_target) => {
-            // Send 200 Connection Established
-            let response = "HTTP/1.1 200 Connection Established\r\n\r\n";
-            let _ = stream.write_all(response.as_bytes()).await;
-            
-            // Note: In a full implementation, you'd tunnel data between client and target
-            // For this demo, we just acknowledge the connection
+    tokio::spawn(async move {
+        if let Err(e) = tunnel(stream, &host_port).await {
+            println!("   ❌ Tunnel to {} failed: {}", host_port, e);
         }
-        Err(_) => {
-            // Send connection failed
-            let response = "HTTP/1.1 502 Bad Gateway\r\n\r\n";
-            let _ = stream.write_all(response.as_bytes()).await;
-        }
-    }
+    });
 }
 
 fn create_blocked_response(url: &str) -> String {
@@ -203,7 +196,7 @@ fn create_blocked_response(url: &str) -> String {
     </div>
 </body>
 </html>
-"#, url);
+"#, escape_html(url));
 
     format!(
         "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n{}",
@@ -249,7 +242,7 @@ fn create_allowed_response(url: &str) -> String {
     </div>
 </body>
 </html>
-"#, url);
+"#, escape_html(url));
 
     format!(
         "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n{}",
@@ -262,10 +255,10 @@ fn get_local_ip() -> Option<String> {
     use std::net::TcpStream;
     
     // Try to connect to a remote address to determine local IP
-    if let Ok(stream) = TcpStream::connect("8.8.8.8:80") {
-        if let Ok(local_addr) = stream.local_addr() {
-            return Some(local_addr.ip().to_string());
-        }
+    if let Ok(stream) = TcpStream::connect("8.8.8.8:80")
+        && let Ok(local_addr) = stream.local_addr()
+    {
+        return Some(local_addr.ip().to_string());
     }
     
     None
@@ -1,3 +1,5 @@
+use ad_blocker_api::escape_html;
+use ad_blocker_api::proxy::connect_upstream;
 use ad_blocker_api::prelude::*;
 use anyhow::Result;
 // use bytes::Bytes;
@@ -39,8 +41,10 @@ impl ProxyService {
         ];
         
         println!("📥 Loading additional blocklists...");
-        if let Err(e) = blocker.load_additional_hosts(additional_hosts).await {
-            eprintln!("⚠️  Warning: Could not load some additional hosts: {}", e);
+        for result in blocker.load_additional_hosts(additional_hosts).await {
+            if let Some(error) = result.error {
+                eprintln!("⚠️  Warning: Could not load {}: {}", result.url, error);
+            }
         }
         
         let https = HttpsConnector::new();
@@ -94,12 +98,27 @@ impl ProxyService {
     }
     
     async fn forward_request(&self, mut req: Request<Body>) -> Result<Response<Body>> {
-        // Handle CONNECT method for HTTPS
+        // Handle CONNECT method for HTTPS: connect to the target, then tunnel
+        // the upgraded client connection to it once the 200 has been sent
         if req.method() == Method::CONNECT {
-            return Ok(Response::builder()
-                .status(StatusCode::OK)
-                .body(Body::empty())
-                .unwrap());
+            let host_port = req
+                .uri()
+                .authority()
+                .map(|authority| authority.to_string())
+                .ok_or_else(|| anyhow::anyhow!("CONNECT request without a host"))?;
+            let mut target = connect_upstream(&host_port).await?;
+            
+            tokio::spawn(async move {
+                match hyper::upgrade::on(req).await {
+                    Ok(mut upgraded) => {
+                        if let Err(e) = tokio::io::copy_bidirectional(&mut upgraded, &mut target).await {
+                            eprintln!("   ❌ Tunnel to {} failed: {}", host_port, e);
+                        }
+                    }
+                    Err(e) => eprintln!("   ❌ Upgrade for {} failed: {}", host_port, e),
+                }
+            });
+            return Ok(Response::new(Body::empty()));
         }
         
         // Ensure we have a proper URI
@@ -193,7 +212,7 @@ impl ProxyService {
     </div>
 </body>
 </html>
-"#, uri.host().unwrap_or("unknown"));
+"#, escape_html(uri.host().unwrap_or("unknown")));
 
         Response::builder()
             .status(StatusCode::OK)
@@ -285,10 +304,10 @@ async fn main() -> Result<()> {
 fn get_local_ip() -> Option<String> {
     use std::net::{TcpStream};
     
-    if let Ok(stream) = TcpStream::connect("8.8.8.8:80") {
-        if let Ok(local_addr) = stream.local_addr() {
-            return Some(local_addr.ip().to_string());
-        }
+    if let Ok(stream) = TcpStream::connect("8.8.8.8:80")
+        && let Ok(local_addr) = stream.local_addr()
+    {
+        return Some(local_addr.ip().to_string());
     }
     
     None
@@ -1,7 +1,8 @@
+use ad_blocker_api::dns::{MAX_UDP_PAYLOAD, blocked_response, parse_query_name};
 use ad_blocker_api::prelude::*;
 use anyhow::Result;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::net::{SocketAddr, UdpSocket};
@@ -194,7 +195,7 @@ impl DynamicAdBlocker {
         }
         
         // 7. Use advanced ad blocker for final check
-        match self.blocker.check_url(&format!("http://{}", domain_lower)).await {
+        match self.blocker.check_domain(&domain_lower).await {
             Ok(result) => result.should_block,
             Err(_) => false,
         }
@@ -265,12 +266,14 @@ async fn main() -> Result<()> {
     println!("🎯 Total unique blocked domains: {}", blocked_count);
     
     // Create enhanced ad blocker with dynamic content filtering
-    let mut config = AdBlockerConfig::default();
-    config.enable_easylist = true;
-    config.enable_easyprivacy = true;
-    config.block_tracking = true;
-    config.block_social = true;
-    config.enable_malware_protection = true;
+    let mut config = AdBlockerConfig {
+        enable_easylist: true,
+        enable_easyprivacy: true,
+        block_tracking: true,
+        block_social: true,
+        enable_malware_protection: true,
+        ..Default::default()
+    };
     
     // Add dynamic content filters for better website rendering
     config.custom_filters.extend(vec![
@@ -324,13 +327,13 @@ async fn main() -> Result<()> {
     let start_time = Instant::now();
     
     loop {
-        let mut buffer = [0; 512];
+        let mut buffer = [0; MAX_UDP_PAYLOAD];
         
         match socket.recv_from(&mut buffer) {
             Ok((size, client_addr)) => {
                 query_count += 1;
                 
-                if let Some(domain) = extract_domain_from_dns_query(&buffer[..size]) {
+                if let Some(domain) = parse_query_name(&buffer[..size]) {
                     println!("📱 Query #{}: {} from {}", query_count, domain, client_addr);
                     
                     let should_block = blocker.should_block(&domain).await;
@@ -340,7 +343,7 @@ async fn main() -> Result<()> {
                         dynamic_blocks += 1; // All blocks now use enhanced detection
                         println!("   🚫 BLOCKED: Enhanced dynamic ad/tracking detection");
                         
-                        let response = create_blocked_dns_response(&buffer[..size]);
+                        let response = blocked_response(&buffer[..size]).unwrap_or_default();
                         let _ = socket.send_to(&response, client_addr);
                     } else {
                         println!("   ✅ ALLOWED: Forwarding to upstream DNS");
@@ -350,7 +353,7 @@ async fn main() -> Result<()> {
                     }
                     
                     // Show enhanced stats every 25 queries
-                    if query_count % 25 == 0 {
+                    if query_count.is_multiple_of(25) {
                         let block_rate = (blocked_count as f64 / query_count as f64) * 100.0;
                         let dynamic_rate = (dynamic_blocks as f64 / blocked_count.max(1) as f64) * 100.0;
                         let uptime = start_time.elapsed().as_secs();
@@ -410,12 +413,10 @@ async fn load_blocklist(file_path: &str) -> Result<HashSet<String>> {
     match File::open(file_path) {
         Ok(file) => {
             let reader = BufReader::new(file);
-            for line in reader.lines() {
-                if let Ok(domain) = line {
-                    let domain = domain.trim().to_lowercase();
-                    if !domain.is_empty() && !domain.starts_with('#') && domain.contains('.') {
-                        domains.insert(domain);
-                    }
+            for domain in reader.lines().map_while(|line| line.ok()) {
+                let domain = domain.trim().to_lowercase();
+                if !domain.is_empty() && !domain.starts_with('#') && domain.contains('.') {
+                    domains.insert(domain);
                 }
             }
             println!("📂 Loaded {} domains from {}", domains.len(), file_path);
@@ -430,45 +431,6 @@ async fn load_blocklist(file_path: &str) -> Result<HashSet<String>> {
 
 
 
-fn extract_domain_from_dns_query(data: &[u8]) -> Option<String> {
-    if data.len() < 12 {
-        return None;
-    }
-    
-    let mut pos = 12;
-    let mut domain = String::new();
-    
-    while pos < data.len() {
-        let len = data[pos] as usize;
-        if len == 0 {
-            break;
-        }
-        
-        pos += 1;
-        if pos + len > data.len() {
-            return None;
-        }
-        
-        if !domain.is_empty() {
-            domain.push('.');
-        }
-        
-        for i in 0..len {
-            if pos + i < data.len() && data[pos + i].is_ascii() {
-                domain.push(data[pos + i] as char);
-            }
-        }
-        
-        pos += len;
-    }
-    
-    if domain.is_empty() {
-        None
-    } else {
-        Some(domain.to_lowercase())
-    }
-}
-
 fn monitor_connection_for_ads(domain: &str) {
     // Log allowed domains for potential post-connection monitoring
     // This could be extended to integrate with browser extensions or proxy monitoring
@@ -477,30 +439,6 @@ fn monitor_connection_for_ads(domain: &str) {
     }
 }
 
-fn create_blocked_dns_response(query: &[u8]) -> Vec<u8> {
-    if query.len() < 12 {
-        return Vec::new();
-    }
-    
-    let mut response = query.to_vec();
-    
-    response[2] = 0x81;
-    response[3] = 0x80;
-    response[6] = 0x00;
-    response[7] = 0x01;
-    
-    response.extend_from_slice(&[
-        0xc0, 0x0c,
-        0x00, 0x01,
-        0x00, 0x01,
-        0x00, 0x00, 0x00, 0x01,
-        0x00, 0x04,
-        0x00, 0x00, 0x00, 0x00,
-    ]);
-    
-    response
-}
-
 fn forward_dns_query_with_timeout(socket: &UdpSocket, query: &[u8], client_addr: SocketAddr) {
     // Try multiple upstream DNS servers for better reliability
     let upstream_servers = [
@@ -510,17 +448,17 @@ fn forward_dns_query_with_timeout(socket: &UdpSocket, query: &[u8], client_addr:
     ];
     
     for upstream_addr_str in &upstream_servers {
-        if let Ok(upstream_addr) = upstream_addr_str.parse::<SocketAddr>() {
-            if let Ok(upstream_socket) = UdpSocket::bind("0.0.0.0:0") {
-                // Set timeout for faster response
-                let _ = upstream_socket.set_read_timeout(Some(Duration::from_millis(2000)));
-                
-                if upstream_socket.send_to(query, upstream_addr).is_ok() {
-                    let mut buffer = [0; 512];
-                    if let Ok((size, _)) = upstream_socket.recv_from(&mut buffer) {
-                        let _ = socket.send_to(&buffer[..size], client_addr);
-                        return; // Success, exit early
-                    }
+        if let Ok(upstream_addr) = upstream_addr_str.parse::<SocketAddr>()
+            && let Ok(upstream_socket) = UdpSocket::bind("0.0.0.0:0")
+        {
+            // Set timeout for faster response
+            let _ = upstream_socket.set_read_timeout(Some(Duration::from_millis(2000)));
+            
+            if upstream_socket.send_to(query, upstream_addr).is_ok() {
+                let mut buffer = [0; MAX_UDP_PAYLOAD];
+                if let Ok((size, _)) = upstream_socket.recv_from(&mut buffer) {
+                    let _ = socket.send_to(&buffer[..size], client_addr);
+                    return; // Success, exit early
                 }
             }
         }
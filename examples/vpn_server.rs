@@ -1,3 +1,4 @@
+use ad_blocker_api::dns::MAX_UDP_PAYLOAD;
 use ad_blocker_api::prelude::*;
 use anyhow::Result;
 use hickory_client::client::{Client, SyncClient};
@@ -41,8 +42,10 @@ impl VpnAdBlocker {
         ];
         
         println!("📥 Loading comprehensive blocklists...");
-        if let Err(e) = blocker.load_additional_hosts(additional_hosts).await {
-            eprintln!("⚠️  Warning: Could not load some additional hosts: {}", e);
+        for result in blocker.load_additional_hosts(additional_hosts).await {
+            if let Some(error) = result.error {
+                eprintln!("⚠️  Warning: Could not load {}: {}", result.url, error);
+            }
         }
         
         Ok(Self {
@@ -103,7 +106,7 @@ impl VpnAdBlocker {
         socket.send(query_data)?;
         
         // Receive response
-        let mut buffer = [0; 512];
+        let mut buffer = [0; MAX_UDP_PAYLOAD];
         let size = socket.recv(&mut buffer)?;
         
         Ok(buffer[..size].to_vec())
@@ -208,7 +211,7 @@ async fn main() -> Result<()> {
     
     // Main DNS server loop
     loop {
-        let mut buffer = [0; 512];
+        let mut buffer = [0; MAX_UDP_PAYLOAD];
         let (size, client_addr) = socket.recv_from(&mut buffer)?;
         
         let blocker = vpn_blocker.clone();
@@ -272,10 +275,10 @@ fn show_setup_instructions(local_ip: &str, public_ip: &str, port: u16) {
 fn get_local_ip() -> Option<String> {
     use std::net::TcpStream;
     
-    if let Ok(stream) = TcpStream::connect("8.8.8.8:80") {
-        if let Ok(local_addr) = stream.local_addr() {
-            return Some(local_addr.ip().to_string());
-        }
+    if let Ok(stream) = TcpStream::connect("8.8.8.8:80")
+        && let Ok(local_addr) = stream.local_addr()
+    {
+        return Some(local_addr.ip().to_string());
     }
     
     None
@@ -9,8 +9,12 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 async fn main() -> Result<()> {
     println!("🌐 Starting Ad Blocker Web Service on http://localhost:8080");
     
-    // Create a single blocker instance
-    let blocker = SimpleAdBlocker::new().await?;
+    // Create a single blocker instance, keeping the last 100 checks for /queries
+    let config = AdBlockerConfig {
+        log_size: 100,
+        ..AdBlockerConfig::default()
+    };
+    let blocker = SimpleAdBlocker::with_config(config).await?;
     
     // Start server
     let listener = TcpListener::bind("127.0.0.1:8080").await?;
@@ -18,6 +22,7 @@ async fn main() -> Result<()> {
     println!("📡 Server ready! Try these endpoints:");
     println!("  GET  /check?url=https://example.com");
     println!("  GET  /stats");
+    println!("  GET  /queries");
     println!("  GET  / (web interface)");
     
     loop {
@@ -57,7 +62,7 @@ async fn handle_request(request: &str, blocker: &SimpleAdBlocker) -> String {
                         let json = json!({
                             "url": url,
                             "blocked": result.should_block,
-                            "reason": result.reason,
+                            "reason": result.reason.to_string(),
                             "category": result.category
                         });
                         http_json_response(200, &json.to_string())
@@ -79,6 +84,19 @@ async fn handle_request(request: &str, blocker: &SimpleAdBlocker) -> String {
             });
             http_json_response(200, &json.to_string())
         }
+        ("GET", "/queries") => {
+            let queries: Vec<_> = blocker.recent_queries().await
+                .into_iter()
+                .rev()
+                .map(|entry| json!({
+                    "url": entry.url,
+                    "blocked": entry.result.should_block,
+                    "reason": entry.result.reason.to_string(),
+                    "category": entry.result.category
+                }))
+                .collect();
+            http_json_response(200, &json!(queries).to_string())
+        }
         ("GET", "/") => {
             let html = r#"
 <!DOCTYPE html>
@@ -114,10 +132,10 @@ async fn handle_request(request: &str, blocker: &SimpleAdBlocker) -> String {
 fn extract_url_param(path: &str) -> Option<String> {
     if let Some(query) = path.split('?').nth(1) {
         for param in query.split('&') {
-            if let Some((key, value)) = param.split_once('=') {
-                if key == "url" {
-                    return Some(urlencoding::decode(value).ok()?.into_owned());
-                }
+            if let Some((key, value)) = param.split_once('=')
+                && key == "url"
+            {
+                return Some(urlencoding::decode(value).ok()?.into_owned());
             }
         }
     }
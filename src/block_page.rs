@@ -0,0 +1,116 @@
+use crate::types::BlockCategory;
+
+/// Page shown in place of a blocked request by `block_page_html`.
+///
+/// `{domain}`, `{reason}` and `{category}` are replaced with the
+/// HTML-escaped values.
+pub const DEFAULT_BLOCK_PAGE_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>Blocked: {domain}</title>
+    <style>
+        body { font-family: -apple-system, BlinkMacSystemFont, sans-serif; text-align: center; padding: 50px; background: #f5f5f5; color: #333; }
+        .container { background: white; padding: 30px; border-radius: 10px; box-shadow: 0 2px 10px rgba(0,0,0,0.1); max-width: 400px; margin: 0 auto; }
+        h1 { color: #e74c3c; margin: 0 0 10px 0; }
+        .domain { background: #f8f9fa; padding: 10px; border-radius: 5px; word-break: break-all; font-size: 12px; margin: 15px 0; }
+        .reason { color: #666; font-size: 14px; }
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>Request blocked</h1>
+        <div class="domain">{domain}</div>
+        <p class="reason">{reason} ({category})</p>
+    </div>
+</body>
+</html>
+"#;
+
+/// The default block page for a blocked request to `domain`
+pub fn block_page_html(domain: &str, reason: &str, category: BlockCategory) -> String {
+    render_block_page(DEFAULT_BLOCK_PAGE_TEMPLATE, domain, reason, category)
+}
+
+/// Fill in the `{domain}`, `{reason}` and `{category}` placeholders of a
+/// block page template. Values are HTML-escaped, and placeholders appearing
+/// inside a value are left alone. Other text in braces is kept as is.
+pub fn render_block_page(template: &str, domain: &str, reason: &str, category: BlockCategory) -> String {
+    let category = format!("{:?}", category);
+    let mut page = String::with_capacity(template.len() + domain.len() + reason.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        page.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let value = [("{domain}", domain), ("{reason}", reason), ("{category}", category.as_str())]
+            .into_iter()
+            .find(|(name, _)| placeholder.starts_with(name));
+        match value {
+            Some((name, value)) => {
+                page.push_str(&escape_html(value));
+                rest = &placeholder[name.len()..];
+            }
+            None => {
+                page.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    page.push_str(rest);
+    page
+}
+
+/// Escape `&`, `<`, `>` and quotes so untrusted text (e.g. a requested
+/// host or URL) can be put in HTML content or attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn placeholders_are_substituted() {
+        let page = render_block_page("<p>{domain} / {reason} / {category} / {other}</p>", "ads.example", "Matched ad filter", BlockCategory::Advertisement);
+        assert_eq!(page, "<p>ads.example / Matched ad filter / Advertisement / {other}</p>");
+        
+        let page = block_page_html("ads.example", "Matched ad filter", BlockCategory::Tracking);
+        assert!(page.contains("<div class=\"domain\">ads.example</div>"));
+        assert!(page.contains("Matched ad filter (Tracking)"));
+    }
+    
+    #[test]
+    fn values_are_html_escaped() {
+        let domain = "<script>alert('x')</script>.example";
+        let page = render_block_page("<h1>{domain}</h1><p>{reason}</p>", domain, "{domain} & more", BlockCategory::Custom);
+        assert_eq!(
+            page,
+            "<h1>&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;.example</h1><p>{domain} &amp; more</p>"
+        );
+        assert!(!block_page_html(domain, "", BlockCategory::Custom).contains("<script>"));
+    }
+    
+    #[test]
+    fn script_hostnames_are_not_executable() {
+        let host = "ads.example\"><script>document.location='https://evil.example/'</script>";
+        let page = block_page_html(host, "Matched ad filter", BlockCategory::Advertisement);
+        assert!(page.contains("ads.example&quot;&gt;&lt;script&gt;document.location=&#39;https://evil.example/&#39;&lt;/script&gt;"));
+        assert!(!page.contains("<script>"));
+        assert!(!page.contains("\"><"));
+        
+        assert_eq!(escape_html("a & b <c> \"d\" 'e'"), "a &amp; b &lt;c&gt; &quot;d&quot; &#39;e&#39;");
+    }
+}
@@ -1,191 +1,920 @@
-use crate::config::AdBlockerConfig;
-use crate::filters::{FilterManager, FilterSources, SocialPatterns, TrackingPatterns};
-use crate::types::{BlockCategory, BlockResult, BlockStats};
+use crate::block_page::{block_page_html, render_block_page};
+use crate::config::{AdBlockerConfig, Precedence};
+use crate::domain::{normalize_domain, parent_domains};
+use crate::error::{AdBlockerError, Result};
+use crate::schedule::{Clock, LocalClock};
+use crate::snapshot::{BlockerSnapshot, SnapshotList};
+use crate::stevenblack::StevenBlackBlocker;
+use crate::result_cache::ResultCache;
+use crate::filters::{CacheStats, FilterManager, FilterSources, PatternSet, SocialPatterns, TrackingParams, TrackingPatterns, load_filter_dirs, timed};
+use crate::types::{BlockCategory, BlockReason, BlockResult, BlockResultFull, BlockStats, BodyMatch, Check, CheckOutcome, CheckStatus, ClientStats, Explanation, FilterCounts, FilterKind, LoadSummary, LogEntry, MinuteBucket, PageReport, ResourceType, StatusReport, WindowedStats};
 
+use adblock::filters::network::NetworkFilterMaskHelper;
+use adblock::lists::{ParsedFilter, parse_filter};
 use adblock::{Engine, FilterSet, request::Request};
-use anyhow::Result;
+use futures_util::future::join_all;
 use regex::Regex;
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use url::Url;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{Mutex, RwLock, watch};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, instrument, warn};
+use url::{Host, Url};
+
+/// Called by `should_block` with each checked URL and its verdict, see
+/// `AdBlockerAPI::set_on_decision`
+pub type DecisionCallback = dyn Fn(&str, &BlockResult) + Send + Sync;
 
 /// Main ad blocker API
+///
+/// Cloning is cheap: all clones share the same engine and statistics, so
+/// checks made through any clone are counted in `get_stats` on every clone.
+/// Custom filters are shared too.
+///
+/// Each clone has its own configuration and whitelist, though. Changes made
+/// through `&mut self` (e.g. `add_whitelist_domain`) are copy-on-write: they
+/// only affect the handle they were made on, never clones made before the
+/// change. Make such changes before cloning to have every handle see them.
+#[derive(Clone)]
 pub struct AdBlockerAPI {
     engine: Arc<RwLock<Engine>>,
-    config: AdBlockerConfig,
-    whitelist_domains: HashSet<String>,
-    tracking_patterns: Vec<Regex>,
-    social_patterns: Vec<Regex>,
+    filter_counts: Arc<RwLock<FilterCounts>>,
+    /// Whether the filter lists have loaded. Only `new_lazy` blockers start
+    /// out loading; other constructors wait for the lists.
+    load_state: Arc<watch::Sender<LoadState>>,
+    last_reload: Arc<RwLock<SystemTime>>,
+    /// When the lists were last loaded, at construction or by `reload_filters`
+    lists_loaded: Arc<RwLock<SystemTime>>,
+    /// Rules from the downloaded lists only, kept so custom rules can be dropped without re-downloading
+    lists: Arc<RwLock<ListRules>>,
+    /// How long the last engine build took, kept apart from `lists` so
+    /// `rebuild` only needs to read them
+    compile_duration: Arc<RwLock<Duration>>,
+    /// Custom filter rules in the engine, starting with `config.custom_filters`
+    /// and the rules read from `config.custom_filter_dirs`.
+    /// Shared so a rebuild through any clone keeps rules added through the others.
+    custom_filters: Arc<RwLock<Vec<String>>>,
+    /// `config.custom_filters` as constructed, restored by `reset_to_config`
+    configured_filters: Arc<Vec<String>>,
+    config: Arc<AdBlockerConfig>,
+    whitelist_domains: Arc<HashSet<String>>,
+    /// `config.whitelist_paths` as (host, path prefix) pairs
+    whitelist_paths: Arc<Vec<(String, String)>>,
+    /// Where each rule in the engine came from, replaced whenever the engine is
+    /// rebuilt
+    filter_sources: Arc<RwLock<HashMap<String, RuleSource>>>,
+    regex_filters: Arc<PatternSet>,
+    tracking_patterns: Arc<PatternSet>,
+    social_patterns: Arc<PatternSet>,
+    /// `config.body_signatures` as one regex, or `None` when there are none
+    body_signatures: Arc<Option<regex::bytes::Regex>>,
     stats: Arc<RwLock<BlockStats>>,
-    _filter_manager: FilterManager,
+    windowed_stats: Arc<RwLock<WindowedStats>>,
+    query_log: Arc<RwLock<VecDeque<LogEntry>>>,
+    /// Per-client counts, only filled in when `config.track_clients` is set
+    client_stats: Arc<RwLock<HashMap<String, ClientStats>>>,
+    /// `config.watch_keywords`, lowercased, without empty ones
+    watch_keywords: Arc<Vec<String>>,
+    /// Allowed domains containing a `config.watch_keywords` entry
+    watched_allows: Arc<RwLock<WatchedAllows>>,
+    /// Consulted by `config.schedule`
+    clock: Arc<dyn Clock>,
+    /// Set by `set_on_decision`
+    on_decision: Option<Arc<DecisionCallback>>,
+    filter_manager: Arc<Mutex<FilterManager>>,
+    /// Whether `reload_filters` downloads the lists again (not for `from_rules`)
+    downloads_lists: bool,
+    /// Recent `should_block` verdicts, replaced when this handle's whitelist
+    /// or toggles change
+    result_cache: Arc<ResultCache>,
+    /// Bumped whenever the engine is rebuilt, invalidating every result cache
+    filter_generation: Arc<AtomicU64>,
+    /// Domains from `config.enable_hosts_lists`, if any were configured
+    hosts: Option<Arc<StevenBlackBlocker>>,
+    /// `config.enable_hosts_lists` entries that failed to load, reported in
+    /// `load_summary` alongside the filter lists that did
+    failed_hosts_lists: Arc<RwLock<Vec<String>>>,
 }
 
 impl AdBlockerAPI {
     /// Create a new ad blocker instance
     pub async fn new(config: AdBlockerConfig) -> Result<Self> {
-        let mut filter_manager = FilterManager::new();
-        let mut filter_set = FilterSet::new(true);
-        
-        // Load EasyList filters
-        if config.enable_easylist {
-            let easylist_rules = filter_manager
-                .load_filters(FilterSources::EASYLIST, config.cache_filters)
-                .await?;
-            filter_set.add_filters(&easylist_rules, Default::default());
-        }
-        
-        // Load EasyPrivacy filters
-        if config.enable_easyprivacy {
-            let easyprivacy_rules = filter_manager
-                .load_filters(FilterSources::EASYPRIVACY, config.cache_filters)
-                .await?;
-            filter_set.add_filters(&easyprivacy_rules, Default::default());
-        }
+        let filter_manager = FilterManager::from_config(&config)?;
+        Self::from_filter_manager(config, filter_manager).await
+    }
+    
+    /// Create an ad blocker that downloads its filter lists with
+    /// `filter_manager` instead of one made from `config`, e.g. one serving
+    /// the built-in lists from a mock server in tests. The manager is kept
+    /// for `reload_filters`.
+    pub(crate) async fn from_filter_manager(config: AdBlockerConfig, mut filter_manager: FilterManager) -> Result<Self> {
+        let lists = download_lists(&config, &mut filter_manager).await?;
+        let hosts = hosts_blocker(&config, &filter_manager);
+        let blocker = Self::build(config, lists, filter_manager, hosts, true).await?;
+        blocker.load_hosts_lists().await;
+        Ok(blocker)
+    }
+    
+    /// Create an ad blocker that returns right away and loads its filter
+    /// lists and hosts lists in a background task, for apps that shouldn't
+    /// wait on downloads at startup. Until `is_ready` (see `wait_ready`),
+    /// only custom filters and the tracking and social patterns apply.
+    pub async fn new_lazy(config: AdBlockerConfig) -> Result<Self> {
+        let filter_manager = FilterManager::from_config(&config)?;
+        let hosts = hosts_blocker(&config, &filter_manager);
+        let lists = ListRules::new(config.max_rules);
+        let blocker = Self::build(config, lists, filter_manager, hosts, true).await?;
+        blocker.load_state.send_replace(LoadState::Loading);
         
-        // Load malware protection filters (optional, may fail due to network)
-        if config.enable_malware_protection {
-            if let Ok(malware_rules) = filter_manager
-                .load_filters(FilterSources::MALWARE_DOMAINS, config.cache_filters)
-                .await
-            {
-                filter_set.add_filters(&malware_rules, Default::default());
-            } else {
-                eprintln!("Warning: Could not load malware protection filters");
+        let loader = blocker.clone();
+        tokio::spawn(async move {
+            loader.load_hosts_lists().await;
+            if let Err(e) = loader.reload_filters().await {
+                warn!(error = %e, "could not load filter lists in the background");
+                loader.load_state.send_replace(LoadState::Failed);
             }
+        });
+        Ok(blocker)
+    }
+    
+    /// Create an ad blocker from the given rules instead of the built-in and
+    /// `extra_sources` lists, without any network access. Matches report the
+    /// `"Rules"` list and count as ads. Custom filters, regex filters, the
+    /// whitelist and the tracking/social toggles still follow `config`;
+    /// `enable_hosts_lists` is ignored.
+    pub async fn from_rules(rules: Vec<String>, config: AdBlockerConfig) -> Result<Self> {
+        let mut lists = ListRules::new(config.max_rules);
+        lists.add(&rules, "Rules", BlockCategory::Advertisement);
+        Self::build(config, lists, FilterManager::new(), None, false).await
+    }
+    
+    /// Rebuild a blocker from a `snapshot` without any network access. It
+    /// gives the same verdicts as the blocker the snapshot was taken from,
+    /// except for domains from `enable_hosts_lists`, which aren't captured.
+    pub async fn from_snapshot(snapshot: BlockerSnapshot) -> Result<Self> {
+        let mut lists = ListRules::new(snapshot.config.max_rules);
+        for list in &snapshot.lists {
+            lists.add(&list.rules, &list.name, list.category);
         }
+        Self::build(snapshot.config, lists, FilterManager::new(), None, false).await
+    }
+    
+    /// Capture the loaded list rules, custom filters and this handle's
+    /// configuration, e.g. to pin a blocker's state in integration tests
+    pub async fn snapshot(&self) -> BlockerSnapshot {
+        // Read before `lists`, the order `add_custom_filter` and friends lock them in
+        let custom_filters = self.custom_filters.read().await.clone();
         
-        // Load social annoyances filters
-        if config.block_social {
-            let social_rules = filter_manager
-                .load_filters(FilterSources::SOCIAL_ANNOYANCES, config.cache_filters)
-                .await?;
-            filter_set.add_filters(&social_rules, Default::default());
+        // Lists are told apart by category too, since custom sources may share a name
+        let mut by_list: HashMap<(&str, BlockCategory), Vec<String>> = HashMap::new();
+        let list_rules = self.lists.read().await;
+        for (rule, source) in &list_rules.sources {
+            by_list.entry((&*source.list, source.category)).or_default().push(rule.clone());
         }
+        let mut lists: Vec<SnapshotList> = by_list
+            .into_iter()
+            .map(|((name, category), mut rules)| {
+                rules.sort_unstable();
+                SnapshotList { name: name.to_string(), category, rules }
+            })
+            .collect();
+        // Rules belong to a single list, so the first rule breaks ties between names
+        lists.sort_unstable_by(|a, b| (&a.name, &a.rules).cmp(&(&b.name, &b.rules)));
         
-        // Add custom filters
-        if !config.custom_filters.is_empty() {
-            filter_set.add_filters(&config.custom_filters, Default::default());
-        }
+        let config = AdBlockerConfig {
+            custom_filters,
+            custom_filter_dirs: Vec::new(),
+            ..(*self.config).clone()
+        };
+        BlockerSnapshot { config, lists }
+    }
+    
+    /// Finish construction from the loaded list rules, adding custom filters
+    async fn build(config: AdBlockerConfig, lists: ListRules, filter_manager: FilterManager, hosts: Option<StevenBlackBlocker>, downloads_lists: bool) -> Result<Self> {
+        config.validate()?;
         
-        let engine = Engine::from_filter_set(filter_set, true);
+        // Add custom filters, followed by those from the filter directories
+        let mut custom_filters = config.custom_filters.clone();
+        custom_filters.extend(load_filter_dirs(&config.custom_filter_dirs).await?);
+        let compile_started = Instant::now();
+        let (engine, filter_counts, filter_sources) = lists.compose(&custom_filters);
+        let compile_duration = compile_started.elapsed();
         
-        // Compile patterns
-        let tracking_patterns = if config.block_tracking {
-            TrackingPatterns::get_patterns()?
-        } else {
-            vec![]
-        };
+        // Compile patterns up front so tracking/social blocking can be toggled at runtime
+        let mut tracking_patterns = TrackingPatterns::get_patterns_except(&config.disabled_patterns)?;
+        tracking_patterns.extend(compile_patterns(&config.extra_tracking_patterns, "tracking pattern")?);
+        let mut social_patterns = SocialPatterns::get_patterns()?;
+        social_patterns.extend(compile_patterns(&config.extra_social_patterns, "social pattern")?);
         
-        let social_patterns = if config.block_social {
-            SocialPatterns::get_patterns()?
-        } else {
-            vec![]
-        };
+        let regex_filters = compile_patterns(&config.regex_filters, "regex filter")?;
+        let body_signatures = compile_signatures(&config.body_signatures)?;
         
-        let whitelist_domains: HashSet<String> = config.whitelist_domains.iter().cloned().collect();
+        let whitelist_domains: HashSet<String> = config.whitelist_domains
+            .iter()
+            .map(|domain| whitelist_entry(domain))
+            .collect();
+        let whitelist_paths = config.whitelist_paths
+            .iter()
+            .map(|entry| whitelist_path_entry(entry))
+            .collect::<Result<Vec<_>>>()?;
+        let result_cache = ResultCache::new(config.result_cache_size);
+        let watch_keywords = config.watch_keywords
+            .iter()
+            .filter(|keyword| !keyword.is_empty())
+            .map(|keyword| keyword.to_lowercase())
+            .collect();
         
         Ok(Self {
             engine: Arc::new(RwLock::new(engine)),
-            config,
-            whitelist_domains,
-            tracking_patterns,
-            social_patterns,
+            filter_counts: Arc::new(RwLock::new(filter_counts)),
+            load_state: Arc::new(watch::Sender::new(LoadState::Ready)),
+            last_reload: Arc::new(RwLock::new(SystemTime::now())),
+            lists_loaded: Arc::new(RwLock::new(SystemTime::now())),
+            lists: Arc::new(RwLock::new(lists)),
+            compile_duration: Arc::new(RwLock::new(compile_duration)),
+            custom_filters: Arc::new(RwLock::new(custom_filters)),
+            configured_filters: Arc::new(config.custom_filters.clone()),
+            config: Arc::new(config),
+            whitelist_domains: Arc::new(whitelist_domains),
+            whitelist_paths: Arc::new(whitelist_paths),
+            filter_sources: Arc::new(RwLock::new(filter_sources)),
+            regex_filters: Arc::new(PatternSet::new(regex_filters)?),
+            tracking_patterns: Arc::new(PatternSet::new(tracking_patterns)?),
+            social_patterns: Arc::new(PatternSet::new(social_patterns)?),
+            body_signatures: Arc::new(body_signatures),
             stats: Arc::new(RwLock::new(BlockStats::default())),
-            _filter_manager: filter_manager,
+            windowed_stats: Arc::new(RwLock::new(WindowedStats::default())),
+            query_log: Arc::new(RwLock::new(VecDeque::new())),
+            client_stats: Arc::new(RwLock::new(HashMap::new())),
+            watch_keywords: Arc::new(watch_keywords),
+            watched_allows: Arc::new(RwLock::new(WatchedAllows::default())),
+            clock: Arc::new(LocalClock),
+            on_decision: None,
+            result_cache: Arc::new(result_cache),
+            filter_generation: Arc::new(AtomicU64::new(0)),
+            filter_manager: Arc::new(Mutex::new(filter_manager)),
+            downloads_lists,
+            hosts: hosts.map(Arc::new),
+            failed_hosts_lists: Arc::new(RwLock::new(Vec::new())),
         })
     }
     
-    /// Check if a URL should be blocked
-    pub async fn should_block(&self, url: &str, source_url: Option<&str>) -> Result<BlockResult> {
-        // Update stats
-        {
-            let mut stats = self.stats.write().await;
-            stats.total_requests += 1;
-        }
-        
-        // Parse URL
-        let parsed_url = match Url::parse(url) {
-            Ok(url) => url,
-            Err(_) => {
-                return Ok(BlockResult {
-                    should_block: false,
-                    reason: "Invalid URL format".to_string(),
-                    filter_matched: None,
-                    category: BlockCategory::Clean,
-                });
-            }
+    /// Download `config.enable_hosts_lists` into the hosts blocker, recording
+    /// the ones that fail for `load_summary`
+    async fn load_hosts_lists(&self) {
+        let Some(hosts) = &self.hosts else {
+            return;
         };
+        let results = hosts.load_additional_hosts(self.config.enable_hosts_lists.iter().map(String::as_str).collect()).await;
+        *self.failed_hosts_lists.write().await = results
+            .into_iter()
+            .filter(|result| result.error.is_some())
+            .map(|result| result.url)
+            .collect();
+    }
+    
+    /// Download the filter lists again and rebuild the engine with them and
+    /// the current custom filters. On error (e.g. a required list failed to
+    /// download) the old engine keeps serving. Blockers made with
+    /// `from_rules` have nothing to download, so they are only rebuilt.
+    #[instrument(skip(self))]
+    pub async fn reload_filters(&self) -> Result<()> {
+        if self.downloads_lists {
+            let lists = download_lists(&self.config, &mut *self.filter_manager.lock().await).await?;
+            *self.lists.write().await = lists;
+        }
+        let custom_filters = self.custom_filters.read().await;
+        self.rebuild(&custom_filters).await;
+        *self.lists_loaded.write().await = SystemTime::now();
+        self.load_state.send_replace(LoadState::Ready);
         
-        // Check whitelist first
-        if let Some(domain) = parsed_url.domain() {
-            if self.whitelist_domains.contains(domain) {
-                return Ok(BlockResult {
-                    should_block: false,
-                    reason: "Domain is whitelisted".to_string(),
-                    filter_matched: None,
-                    category: BlockCategory::Whitelisted,
-                });
+        let counts = *self.filter_counts.read().await;
+        info!(
+            network_rules = counts.network_rules,
+            exception_rules = counts.exception_rules,
+            custom_rules = custom_filters.len(),
+            "reloaded filters"
+        );
+        Ok(())
+    }
+    
+    /// Call `reload_filters` every `interval` in a background task, logging
+    /// failures. The task stops when the returned handle is dropped.
+    pub fn spawn_auto_update(self: Arc<Self>, interval: Duration) -> AutoUpdateHandle {
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes immediately, and the lists were just loaded
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.reload_filters().await {
+                    warn!(error = %e, "could not reload filters");
+                }
             }
+        });
+        AutoUpdateHandle { task }
+    }
+    
+    /// Check if a URL should be blocked.
+    ///
+    /// `source_url` is the page making the request. Rules scoped with
+    /// `$domain=` (e.g. `||widget.example^$domain=news.example`) and
+    /// `$third-party`/`$first-party` only match when it is given; without
+    /// it such rules never apply. The resource type for rules like `$image`
+    /// is guessed from the path's file extension (see `ResourceType`).
+    #[instrument(level = "debug", skip(self))]
+    pub async fn should_block(&self, url: &str, source_url: Option<&str>) -> Result<BlockResult> {
+        let Ok(parsed_url) = parse_checked_url(url) else {
+            return self.reject_invalid(url).await;
+        };
+        let source_url = source_url.map(Url::parse).transpose()?;
+        
+        self.should_block_parsed(&parsed_url, source_url.as_ref()).await
+    }
+    
+    /// Check a request from its parts, as a proxy sees them: `host` (with
+    /// an optional port), the request target `path` (with any query),
+    /// `scheme` (`http` or `https`) and, when known, the host of the page
+    /// that made it. The verdict and stats are the same as `should_block`
+    /// gives for the equivalent URL.
+    pub async fn check_http_request(&self, host: &str, path: &str, scheme: &str, source_host: Option<&str>) -> Result<BlockResult> {
+        let separator = if path.starts_with('/') { "" } else { "/" };
+        let url = format!("{}://{}{}{}", scheme, host, separator, path);
+        let Ok(parsed_url) = Url::parse(&url) else {
+            return self.reject_invalid(&url).await;
+        };
+        let source_url = source_host
+            .map(|source_host| Url::parse(&format!("{}://{}/", scheme, source_host)))
+            .transpose()?;
+        
+        self.should_block_parsed(&parsed_url, source_url.as_ref()).await
+    }
+    
+    /// Check a URL on behalf of a client (e.g. its IP address). When
+    /// `config.track_clients` is set, the result is also counted towards
+    /// that client's `client_stats`.
+    pub async fn should_block_for(&self, client_id: &str, url: &str) -> Result<BlockResult> {
+        let result = self.should_block(url, None).await?;
+        
+        if self.config.track_clients {
+            let domain = parse_checked_url(url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string));
+            self.client_stats
+                .write()
+                .await
+                .entry(client_id.to_string())
+                .or_default()
+                .record(domain.as_deref(), result.would_block);
+        }
+        Ok(result)
+    }
+    
+    /// Check an already-parsed URL, skipping the parse done by `should_block`,
+    /// which delegates here. The verdict and stats are the same.
+    pub async fn should_block_parsed(&self, url: &Url, source_url: Option<&Url>) -> Result<BlockResult> {
+        let result = self.apply_dry_run(self.evaluate_cached(url, source_url).await?);
+        debug!(
+            url = %url,
+            should_block = result.should_block,
+            category = ?result.category,
+            filter = result.filter_matched.as_deref(),
+            "checked URL"
+        );
+        self.log_query(url.as_str(), &result).await;
+        if let Some(host) = url.host_str() {
+            self.watch_allowed(host, &result).await;
+        }
+        self.notify_decision(url.as_str(), &result);
+        Ok(result)
+    }
+    
+    /// `evaluate`, answered from the result cache when possible. Hits are
+    /// counted in stats just like a fresh evaluation.
+    async fn evaluate_cached(&self, url: &Url, source_url: Option<&Url>) -> Result<BlockResult> {
+        let source = source_url.map(Url::as_str);
+        let generation = self.filter_generation.load(Ordering::Acquire);
+        let result = if self.config.schedule.is_some() {
+            self.evaluate(url, source_url).await?
+        } else if let Some(result) = self.result_cache.get(url.as_str(), source, generation) {
+            result
+        } else {
+            let result = self.evaluate(url, source_url).await?;
+            self.result_cache.insert(url.as_str(), source, &result, generation);
+            result
+        };
+        self.record_check(&result).await;
+        Ok(result)
+    }
+    
+    /// Give this handle an empty result cache of its own, after a change
+    /// that only affects this handle's verdicts
+    fn reset_result_cache(&mut self) {
+        self.result_cache = Arc::new(ResultCache::new(self.config.result_cache_size));
+    }
+    
+    /// In `config.dry_run`, let everything through while keeping the verdict
+    /// in `would_block` (stats and the query log still count it as blocked)
+    fn apply_dry_run(&self, mut result: BlockResult) -> BlockResult {
+        if self.config.dry_run {
+            result.should_block = false;
+        }
+        result
+    }
+    
+    /// Count and log a URL that could not be parsed
+    async fn reject_invalid(&self, url: &str) -> Result<BlockResult> {
+        let result = invalid_url_result();
+        self.record_check(&result).await;
+        self.log_query(url, &result).await;
+        self.notify_decision(url, &result);
+        Ok(result)
+    }
+    
+    /// Hand a verdict to the `set_on_decision` callback, if there is one.
+    /// No locks are held while it runs, so it may call back into the blocker.
+    fn notify_decision(&self, url: &str, result: &BlockResult) {
+        if let Some(on_decision) = &self.on_decision {
+            on_decision(url, result);
+        }
+    }
+    
+    /// Count a finished check, and its block if it would be blocked, in
+    /// `stats` and `windowed_stats`. Each check is sampled once, per
+    /// `config.stats_sample_rate`, so its request and block are counted
+    /// together, and a check that isn't sampled takes no stats lock at all.
+    async fn record_check(&self, result: &BlockResult) {
+        if !self.sample_stats() {
+            return;
+        }
+        
+        let mut windowed_stats = self.windowed_stats.write().await;
+        windowed_stats.record_request();
+        if result.would_block {
+            windowed_stats.record_block();
+        }
+        drop(windowed_stats);
+        
+        let mut stats = self.stats.write().await;
+        stats.total_requests += 1;
+        if !result.would_block {
+            return;
+        }
+        stats.blocked_requests += 1;
+        match result.category {
+            BlockCategory::Advertisement => stats.ads_blocked += 1,
+            BlockCategory::Tracking => stats.trackers_blocked += 1,
+            BlockCategory::Malware => stats.malware_blocked += 1,
+            BlockCategory::Phishing => stats.phishing_blocked += 1,
+            BlockCategory::Cryptomining => stats.cryptomining_blocked += 1,
+            BlockCategory::Social => stats.social_blocked += 1,
+            BlockCategory::Custom => stats.custom_blocked += 1,
+            BlockCategory::Whitelisted | BlockCategory::Clean => {}
         }
+    }
+    
+    /// Whether to count this check in the stats, per `config.stats_sample_rate`
+    fn sample_stats(&self) -> bool {
+        let rate = self.config.stats_sample_rate;
+        rate >= 1.0 || (rate > 0.0 && rand::random::<f64>() < rate)
+    }
+    
+    async fn evaluate(&self, parsed_url: &Url, source_url: Option<&Url>) -> Result<BlockResult> {
+        let url = parsed_url.as_str();
         
-        // Check against adblock engine
-        let engine = self.engine.read().await;
+        // Registrable domains are compared using the public suffix list, so
+        // `a.example.co.uk` and `b.example.co.uk` count as first-party
         let request = Request::new(
             url,
-            source_url.unwrap_or(""),
-            "other"
+            source_url.map_or("", Url::as_str),
+            ResourceType::from_path(parsed_url.path()).as_str()
         )?;
-        let blocker_result = engine.check_network_request(&request);
+        let is_third_party = source_url.is_some() && request.is_third_party;
+        
+        // By default the whitelist wins: it is checked before the engine,
+        // custom regex, tracking and social rules, none of which are consulted
+        // (or counted in stats) for a whitelisted domain or path
+        let whitelisted = parsed_url
+            .domain()
+            .map(normalize_domain)
+            .is_some_and(|domain| self.whitelist_domains.contains(domain.as_ref()) || self.wildcard_whitelisted(&domain))
+            || self.path_whitelisted(parsed_url);
+        if whitelisted && self.config.precedence == Precedence::WhitelistWins {
+            return Ok(whitelisted_result(is_third_party));
+        }
+        
+        // Check IP address hosts against the blocked ranges, then the hosts
+        // lists, then the adblock engine
+        if let Some(result) = self.check_ip_ranges(parsed_url, is_third_party).await {
+            return Ok(result);
+        }
+        if let Some(domain) = parsed_url.domain()
+            && let Some(result) = self.check_hosts(&normalize_domain(domain), is_third_party).await
+        {
+            return Ok(result);
+        }
+        if let Some(result) = self.check_engine(&request, is_third_party).await {
+            return Ok(result);
+        }
         
-        if blocker_result.matched {
-            self.update_block_stats(BlockCategory::Advertisement).await;
+        // Check custom regex filters
+        if self.is_scheduled(BlockCategory::Custom)
+            && let Some(pattern) = self.regex_filters.first_match(url)
+        {
             return Ok(BlockResult {
                 should_block: true,
-                reason: "Matched ad filter".to_string(),
-                filter_matched: blocker_result.filter.map(|f| f.to_string()),
-                category: BlockCategory::Advertisement,
+                would_block: true,
+                reason: BlockReason::CustomFilter,
+                filter_matched: Some(pattern.as_str().to_string()),
+                source_list: None,
+                category: BlockCategory::Custom,
+                is_third_party,
             });
         }
         
+        // Tracking and social patterns see the percent-decoded URL, so
+        // `%2Fads%2F` can't slip past a pattern for `/ads/`
+        let decoded = decode_url(url);
+        
         // Check tracking patterns
-        if self.config.block_tracking {
-            for pattern in &self.tracking_patterns {
-                if pattern.is_match(url) {
-                    self.update_block_stats(BlockCategory::Tracking).await;
-                    return Ok(BlockResult {
-                        should_block: true,
-                        reason: "Matched tracking pattern".to_string(),
-                        filter_matched: Some(pattern.as_str().to_string()),
-                        category: BlockCategory::Tracking,
-                    });
-                }
-            }
+        if self.config.block_tracking && self.is_scheduled(BlockCategory::Tracking)
+            && let Some(pattern) = self.tracking_patterns.first_match(&decoded)
+        {
+            return Ok(BlockResult {
+                should_block: true,
+                would_block: true,
+                reason: BlockReason::TrackingPattern,
+                filter_matched: Some(pattern.as_str().to_string()),
+                source_list: None,
+                category: BlockCategory::Tracking,
+                is_third_party,
+            });
         }
         
         // Check social patterns
-        if self.config.block_social {
-            for pattern in &self.social_patterns {
-                if pattern.is_match(url) {
-                    self.update_block_stats(BlockCategory::Social).await;
-                    return Ok(BlockResult {
-                        should_block: true,
-                        reason: "Matched social media pattern".to_string(),
-                        filter_matched: Some(pattern.as_str().to_string()),
-                        category: BlockCategory::Social,
-                    });
-                }
-            }
+        if self.config.block_social && self.is_scheduled(BlockCategory::Social)
+            && let Some(pattern) = self.social_patterns.first_match(&decoded)
+        {
+            return Ok(BlockResult {
+                should_block: true,
+                would_block: true,
+                reason: BlockReason::SocialPattern,
+                filter_matched: Some(pattern.as_str().to_string()),
+                source_list: None,
+                category: BlockCategory::Social,
+                is_third_party,
+            });
+        }
+        
+        if whitelisted {
+            return Ok(whitelisted_result(is_third_party));
+        }
+        
+        Ok(BlockResult {
+            should_block: false,
+            would_block: false,
+            reason: BlockReason::Clean,
+            filter_matched: None,
+            source_list: None,
+            category: BlockCategory::Clean,
+            is_third_party,
+        })
+    }
+    
+    /// Check a bare hostname, as received by a DNS resolver.
+    ///
+    /// Only the filter engine and whitelist are consulted; the tracking and
+    /// social URL patterns need a path to match against and are skipped.
+    /// Like `StevenBlackBlocker::is_blocked`, a whitelisted or blocked parent
+    /// domain also covers its subdomains. There is no source page, so rules
+    /// scoped with `$domain=` never match here.
+    pub async fn is_domain_blocked(&self, domain: &str) -> Result<BlockResult> {
+        let domain = normalize_domain(domain);
+        
+        let result = self.evaluate_domain(&domain).await?;
+        self.record_check(&result).await;
+        let result = self.apply_dry_run(result);
+        self.log_query(&domain, &result).await;
+        self.watch_allowed(&domain, &result).await;
+        self.notify_decision(&domain, &result);
+        Ok(result)
+    }
+    
+    async fn evaluate_domain(&self, domain: &str) -> Result<BlockResult> {
+        if domain.is_empty() {
+            return Ok(invalid_url_result());
+        }
+        
+        let whitelisted = parent_domains(domain).any(|parent| self.whitelist_domains.contains(parent)) || self.wildcard_whitelisted(domain);
+        if whitelisted && self.config.precedence == Precedence::WhitelistWins {
+            return Ok(whitelisted_result(false));
+        }
+        
+        if let Some(result) = self.check_hosts(domain, false).await {
+            return Ok(result);
+        }
+        // `||domain^` rules also match subdomains, so one request covers the parents
+        let request = Request::new(&format!("http://{}/", domain), "", "other")?;
+        if let Some(result) = self.check_engine(&request, false).await {
+            return Ok(result);
+        }
+        
+        if whitelisted {
+            return Ok(whitelisted_result(false));
         }
         
         Ok(BlockResult {
             should_block: false,
-            reason: "URL is clean".to_string(),
+            would_block: false,
+            reason: BlockReason::Clean,
             filter_matched: None,
+            source_list: None,
             category: BlockCategory::Clean,
+            is_third_party: false,
+        })
+    }
+    
+    /// Run a request through the filter engine, attributing any match to the
+    /// list its rule came from
+    async fn check_engine(&self, request: &Request, is_third_party: bool) -> Option<BlockResult> {
+        let blocker_result = self.engine.read().await.check_network_request(request);
+        if !blocker_result.matched {
+            return None;
+        }
+        
+        let (source_list, category) = self.rule_source(blocker_result.filter.as_deref()).await;
+        if !self.is_scheduled(category) {
+            return None;
+        }
+        
+        Some(BlockResult {
+            should_block: true,
+            would_block: true,
+            reason: BlockReason::AdFilter,
+            filter_matched: blocker_result.filter,
+            source_list,
+            category,
+            is_third_party,
+        })
+    }
+    
+    /// Look a normalized domain up in the `config.enable_hosts_lists`
+    /// domains, which also cover their subdomains. Matches count as ads.
+    async fn check_hosts(&self, domain: &str, is_third_party: bool) -> Option<BlockResult> {
+        let hosts = self.hosts.as_ref()?;
+        if !self.is_scheduled(BlockCategory::Advertisement) {
+            return None;
+        }
+        let source_list = hosts.block_source(domain).await?;
+        
+        Some(BlockResult {
+            should_block: true,
+            would_block: true,
+            reason: BlockReason::HostsList,
+            filter_matched: Some(domain.to_string()),
+            source_list: Some(source_list),
+            category: BlockCategory::Advertisement,
+            is_third_party,
+        })
+    }
+    
+    /// Match a URL whose host is a literal IP address against
+    /// `config.blocked_ip_ranges`. Matches count as tracking.
+    async fn check_ip_ranges(&self, url: &Url, is_third_party: bool) -> Option<BlockResult> {
+        let ip = match url.host()? {
+            Host::Ipv4(ip) => IpAddr::V4(ip),
+            Host::Ipv6(ip) => IpAddr::V6(ip),
+            Host::Domain(_) => return None,
+        };
+        if !self.is_scheduled(BlockCategory::Tracking) {
+            return None;
+        }
+        let range = self.config.blocked_ip_ranges.iter().find(|range| range.contains(ip))?;
+        
+        Some(BlockResult {
+            should_block: true,
+            would_block: true,
+            reason: BlockReason::IpRange,
+            filter_matched: Some(range.to_string()),
+            source_list: None,
+            category: BlockCategory::Tracking,
+            is_third_party,
+        })
+    }
+    
+    /// List and category an engine rule came from. Rules with no known
+    /// source count as ads.
+    async fn rule_source(&self, filter: Option<&str>) -> (Option<String>, BlockCategory) {
+        let filter_sources = self.filter_sources.read().await;
+        let source = filter.and_then(|f| filter_sources.get(f));
+        let source_list = source.map(|source| source.list.to_string());
+        let category = source.map_or(BlockCategory::Advertisement, |source| source.category);
+        (source_list, category)
+    }
+    
+    /// Check a URL like `should_block`, also reporting every category whose
+    /// rules match it. The verdict, stats and log entry are those of
+    /// `should_block`; the extra matching is only done for blocked URLs.
+    pub async fn check_url_full(&self, url: &str) -> Result<BlockResultFull> {
+        let result = self.should_block(url, None).await?;
+        if !result.would_block {
+            return Ok(BlockResultFull { result, categories: Vec::new() });
+        }
+        
+        let Ok(parsed_url) = parse_checked_url(url) else {
+            return Ok(BlockResultFull { result, categories: Vec::new() });
+        };
+        let mut categories = self.matching_categories(&parsed_url).await?;
+        // Keep the verdict's category even if custom filters changed between lookups
+        if !categories.contains(&result.category) {
+            categories.insert(0, result.category);
+        }
+        Ok(BlockResultFull { result, categories })
+    }
+    
+    /// Every enabled, currently scheduled rule family matching a URL,
+    /// without touching stats
+    async fn matching_categories(&self, parsed_url: &Url) -> Result<Vec<BlockCategory>> {
+        let url = parsed_url.as_str();
+        let request = Request::new(url, "", ResourceType::from_path(parsed_url.path()).as_str())?;
+        let blocker_result = self.engine.read().await.check_network_request(&request);
+        let engine_category = if blocker_result.matched {
+            Some(self.rule_source(blocker_result.filter.as_deref()).await.1)
+        } else {
+            None
+        };
+        
+        let mut categories = Vec::new();
+        let mut add = |category: BlockCategory| {
+            if self.is_scheduled(category) && !categories.contains(&category) {
+                categories.push(category);
+            }
+        };
+        if let Some(category) = engine_category {
+            add(category);
+        }
+        if self.regex_filters.is_match(url) {
+            add(BlockCategory::Custom);
+        }
+        let decoded = decode_url(url);
+        if self.config.block_tracking && self.tracking_patterns.is_match(&decoded) {
+            add(BlockCategory::Tracking);
+        }
+        if self.config.block_social && self.social_patterns.is_match(&decoded) {
+            add(BlockCategory::Social);
+        }
+        Ok(categories)
+    }
+    
+    /// Run a URL through every check `should_block` makes and report what
+    /// each one found, for debugging why a URL is or isn't blocked. Unlike
+    /// `should_block`, a match doesn't stop the later checks, and stats, the
+    /// query log and the result cache are left alone. Scheme-less URLs are
+    /// read as http, as in `should_block`.
+    pub async fn explain(&self, url: &str) -> Result<Explanation> {
+        let parsed_url = parse_checked_url(url)?;
+        let url = parsed_url.as_str();
+        let request = Request::new(url, "", ResourceType::from_path(parsed_url.path()).as_str())?;
+        let domain = parsed_url.domain().map(normalize_domain);
+        let not_scheduled = |category: BlockCategory| format!("{:?} is not scheduled", category);
+        let mut checks = Vec::new();
+        
+        let whitelisted_domain = domain
+            .as_deref()
+            .filter(|domain| self.whitelist_domains.contains(*domain) || self.wildcard_whitelisted(domain));
+        checks.push(match whitelisted_domain {
+            Some(domain) => CheckOutcome::matched(Check::Whitelist, domain),
+            None => CheckOutcome::found(Check::Whitelist, self.path_whitelisted(&parsed_url).then_some(parsed_url.path())),
+        });
+        
+        let ip = match parsed_url.host() {
+            Some(Host::Ipv4(ip)) => Some(IpAddr::V4(ip)),
+            Some(Host::Ipv6(ip)) => Some(IpAddr::V6(ip)),
+            _ => None,
+        };
+        checks.push(match ip {
+            None => CheckOutcome::skipped(Check::IpRanges, "host is not an IP address"),
+            Some(_) if !self.is_scheduled(BlockCategory::Tracking) => CheckOutcome::skipped(Check::IpRanges, not_scheduled(BlockCategory::Tracking)),
+            Some(ip) => CheckOutcome::found(Check::IpRanges, self.config.blocked_ip_ranges.iter().find(|range| range.contains(ip)).map(ToString::to_string)),
+        });
+        
+        checks.push(match (&self.hosts, domain.as_deref()) {
+            (None, _) => CheckOutcome::skipped(Check::HostsLists, "no hosts lists configured"),
+            (_, None) => CheckOutcome::skipped(Check::HostsLists, "host is not a domain"),
+            _ if !self.is_scheduled(BlockCategory::Advertisement) => CheckOutcome::skipped(Check::HostsLists, not_scheduled(BlockCategory::Advertisement)),
+            (Some(hosts), Some(domain)) => CheckOutcome::found(Check::HostsLists, hosts.block_source(domain).await),
+        });
+        
+        let blocker_result = self.engine.read().await.check_network_request(&request);
+        checks.push(match blocker_result.filter {
+            Some(filter) if blocker_result.matched => {
+                let category = self.rule_source(Some(filter.as_str())).await.1;
+                if self.is_scheduled(category) {
+                    CheckOutcome::matched(Check::Engine, filter)
+                } else {
+                    CheckOutcome::skipped(Check::Engine, format!("{} matched, but {}", filter, not_scheduled(category)))
+                }
+            }
+            _ => CheckOutcome {
+                detail: blocker_result.exception.map(|exception| format!("excepted by {}", exception)),
+                ..CheckOutcome::no_match(Check::Engine)
+            },
+        });
+        
+        checks.push(if self.is_scheduled(BlockCategory::Custom) {
+            CheckOutcome::found(Check::CustomRegex, self.regex_filters.first_match(url).map(Regex::as_str))
+        } else {
+            CheckOutcome::skipped(Check::CustomRegex, not_scheduled(BlockCategory::Custom))
+        });
+        
+        let decoded = decode_url(url);
+        for (check, enabled, category, patterns) in [
+            (Check::TrackingPatterns, self.config.block_tracking, BlockCategory::Tracking, &self.tracking_patterns),
+            (Check::SocialPatterns, self.config.block_social, BlockCategory::Social, &self.social_patterns),
+        ] {
+            checks.push(if !enabled {
+                CheckOutcome::skipped(check, format!("blocking {:?} is disabled", category))
+            } else if !self.is_scheduled(category) {
+                CheckOutcome::skipped(check, not_scheduled(category))
+            } else {
+                CheckOutcome::found(check, patterns.first_match(&decoded).map(Regex::as_str))
+            });
+        }
+        
+        let matched = |check: &&CheckOutcome| check.status == CheckStatus::Matched;
+        let whitelisted = checks.iter().filter(matched).any(|outcome| outcome.check == Check::Whitelist);
+        let blocked_by = checks.iter().filter(matched).map(|outcome| outcome.check).find(|&check| check != Check::Whitelist);
+        let decided_by = match blocked_by {
+            Some(_) if whitelisted && self.config.precedence == Precedence::WhitelistWins => Some(Check::Whitelist),
+            None if whitelisted => Some(Check::Whitelist),
+            blocked_by => blocked_by,
+        };
+        Ok(Explanation {
+            url: url.to_string(),
+            would_block: decided_by.is_some_and(|check| check != Check::Whitelist),
+            decided_by,
+            checks,
         })
     }
     
+    /// Find `config.body_signatures` (e.g. `adsbygoogle`) in a response body,
+    /// to catch ads served from first-party paths that URL checks miss. A
+    /// proxy can use the returned byte ranges to decide what to strip.
+    ///
+    /// Only HTML, JavaScript and other text bodies are scanned, going by
+    /// `content_type`; other bodies, and every body when no signatures are
+    /// configured, have no matches.
+    pub fn scan_response_body(&self, content_type: &str, body: &[u8]) -> Vec<BodyMatch> {
+        let Some(signatures) = self.body_signatures.as_ref() else {
+            return Vec::new();
+        };
+        if !is_text_content(content_type) {
+            return Vec::new();
+        }
+        
+        signatures
+            .find_iter(body)
+            .map(|found| BodyMatch {
+                signature: String::from_utf8_lossy(found.as_bytes()).into_owned(),
+                range: found.range(),
+            })
+            .collect()
+    }
+    
+    /// Remove tracking query parameters (e.g. `utm_source`, `fbclid`) from a URL.
+    /// Remaining parameters keep their order and the fragment is preserved.
+    /// The URL is returned unchanged if none of its parameters match.
+    pub fn sanitize_url(&self, url: &str) -> Result<String> {
+        let mut parsed_url = Url::parse(url)?;
+        
+        let Some(query) = parsed_url.query() else {
+            return Ok(url.to_string());
+        };
+        
+        let kept: Vec<&str> = query
+            .split('&')
+            .filter(|pair| {
+                let name = pair.split('=').next().unwrap_or("");
+                let name = urlencoding::decode(name).map(|n| n.into_owned()).unwrap_or_else(|_| name.to_string());
+                !TrackingParams::matches(&name, &self.config.tracking_params)
+            })
+            .collect();
+        
+        if kept.len() == query.split('&').count() {
+            return Ok(url.to_string());
+        }
+        
+        let new_query = kept.join("&");
+        parsed_url.set_query(if new_query.is_empty() { None } else { Some(&new_query) });
+        Ok(parsed_url.to_string())
+    }
+    
     /// Batch check multiple URLs
     pub async fn batch_check(&self, urls: Vec<String>, source_url: Option<&str>) -> Result<Vec<(String, BlockResult)>> {
         let mut results = Vec::new();
@@ -198,90 +927,825 @@ impl AdBlockerAPI {
         Ok(results)
     }
     
-    /// Add custom filter rule
-    pub async fn add_custom_filter(&mut self, filter: String) -> Result<()> {
-        let mut engine = self.engine.write().await;
-        let mut filter_set = FilterSet::new(true);
-        filter_set.add_filters(&[filter.clone()], Default::default());
-        *engine = Engine::from_filter_set(filter_set, true);
-        
-        self.config.custom_filters.push(filter);
-        Ok(())
+    /// Check bare hostnames like `is_domain_blocked`, e.g. the names in a
+    /// batch of DNS queries. The checks run concurrently and the results
+    /// come back in the order of `domains`.
+    pub async fn batch_check_domains(&self, domains: Vec<String>) -> Result<Vec<(String, BlockResult)>> {
+        let results = join_all(domains.iter().map(|domain| self.is_domain_blocked(domain))).await;
+        domains
+            .into_iter()
+            .zip(results)
+            .map(|(domain, result)| Ok((domain, result?)))
+            .collect()
     }
     
-    /// Add domain to whitelist
-    pub fn add_whitelist_domain(&mut self, domain: String) {
-        self.whitelist_domains.insert(domain.clone());
-        self.config.whitelist_domains.push(domain);
+    /// Check every resource a page loads, with the page as the source URL so
+    /// first- and third-party resources are told apart
+    pub async fn analyze_page(&self, page_url: &str, resource_urls: Vec<String>) -> Result<PageReport> {
+        let mut report = PageReport {
+            page_url: page_url.to_string(),
+            ..PageReport::default()
+        };
+        
+        for (url, result) in self.batch_check(resource_urls, Some(page_url)).await? {
+            report.total_resources += 1;
+            if result.is_third_party {
+                report.third_party_resources += 1;
+            }
+            *report.category_counts.entry(result.category).or_default() += 1;
+            if result.would_block {
+                report.blocked.push((url, result));
+            }
+        }
+        Ok(report)
     }
     
-    /// Remove domain from whitelist
-    pub fn remove_whitelist_domain(&mut self, domain: &str) {
-        self.whitelist_domains.remove(domain);
-        self.config.whitelist_domains.retain(|d| d != domain);
+    /// Check that a filter rule parses, and what kind of rule it is, without
+    /// adding it anywhere. Rules the engine can't parse are a `FilterParse` error.
+    pub fn validate_filter(rule: &str) -> Result<FilterKind> {
+        let rule = rule.trim();
+        if is_comment_rule(rule) {
+            return Ok(FilterKind::Comment);
+        }
+        match parse_filter(rule, false, Default::default()) {
+            Ok(ParsedFilter::Network(filter)) if filter.is_exception() => Ok(FilterKind::Exception),
+            Ok(ParsedFilter::Network(_)) => Ok(FilterKind::Network),
+            Ok(ParsedFilter::Cosmetic(_)) => Ok(FilterKind::Cosmetic),
+            Err(e) => Err(AdBlockerError::FilterParse(format!("invalid filter rule `{}`: {:?}", rule, e))),
+        }
     }
     
-    /// Get current configuration
-    pub fn get_config(&self) -> &AdBlockerConfig {
-        &self.config
+    /// Add custom filter rule, keeping all previously loaded rules. The rule
+    /// is shared with clones, and kept when any of them rebuilds the engine.
+    /// It is also added to this handle's `config.custom_filters`.
+    pub async fn add_custom_filter(&mut self, filter: String) -> Result<()> {
+        // Held for the whole rebuild so concurrent adds through clones aren't lost
+        let custom_filters = self.custom_filters.clone();
+        let mut custom_filters = custom_filters.write().await;
+        custom_filters.push(filter.clone());
+        // The engine owns the compiled rules, so rebuild from the lists
+        self.rebuild(&custom_filters).await;
+        drop(custom_filters);
+        Arc::make_mut(&mut self.config).custom_filters.push(filter);
+        Ok(())
     }
     
-    /// Get blocking statistics
-    pub async fn get_stats(&self) -> BlockStats {
-        self.stats.read().await.clone()
+    /// Add several custom filter rules with a single engine rebuild. Each
+    /// `add_custom_filter` call rebuilds the engine, so this is much faster
+    /// for more than a handful of rules. Like `add_custom_filter`, the rules
+    /// are also added to `config.custom_filters`.
+    pub async fn add_custom_filters(&mut self, filters: Vec<String>) -> Result<()> {
+        if filters.is_empty() {
+            return Ok(());
+        }
+        let custom_filters = self.custom_filters.clone();
+        let mut custom_filters = custom_filters.write().await;
+        custom_filters.extend(filters.iter().cloned());
+        self.rebuild(&custom_filters).await;
+        drop(custom_filters);
+        Arc::make_mut(&mut self.config).custom_filters.extend(filters);
+        Ok(())
     }
     
-    /// Reset statistics
-    pub async fn reset_stats(&self) {
-        let mut stats = self.stats.write().await;
-        *stats = BlockStats::default();
+    /// Remove all custom filter rules, including those added through clones,
+    /// rebuilding the engine from the already-downloaded filter lists
+    pub async fn clear_custom_filters(&mut self) -> Result<()> {
+        let custom_filters = self.custom_filters.clone();
+        let mut custom_filters = custom_filters.write().await;
+        custom_filters.clear();
+        self.rebuild(&[]).await;
+        drop(custom_filters);
+        Arc::make_mut(&mut self.config).custom_filters.clear();
+        Ok(())
     }
     
-    /// Update configuration
-    pub async fn update_config(&mut self, new_config: AdBlockerConfig) -> Result<()> {
-        *self = Self::new(new_config).await?;
+    /// Rebuild the engine from the downloaded filter lists plus the custom
+    /// filters the blocker was constructed with, without re-downloading.
+    /// Rules added since, through this handle or its clones, are dropped and
+    /// `config.custom_filters` goes back to its original value.
+    pub async fn reset_to_config(&mut self) -> Result<()> {
+        let mut configured = self.configured_filters.to_vec();
+        configured.extend(load_filter_dirs(&self.config.custom_filter_dirs).await?);
+        let custom_filters = self.custom_filters.clone();
+        let mut custom_filters = custom_filters.write().await;
+        *custom_filters = configured;
+        self.rebuild(&custom_filters).await;
+        drop(custom_filters);
+        Arc::make_mut(&mut self.config).custom_filters = self.configured_filters.to_vec();
         Ok(())
     }
     
-    async fn update_block_stats(&self, category: BlockCategory) {
-        let mut stats = self.stats.write().await;
-        stats.blocked_requests += 1;
+    /// Replace the engine with one built from the list rules plus `custom_filters`
+    async fn rebuild(&self, custom_filters: &[String]) {
+        let compile_started = Instant::now();
+        let (engine, filter_counts, filter_sources) = self.lists.read().await.compose(custom_filters);
+        *self.compile_duration.write().await = compile_started.elapsed();
         
-        match category {
-            BlockCategory::Advertisement => stats.ads_blocked += 1,
-            BlockCategory::Tracking => stats.trackers_blocked += 1,
-            BlockCategory::Malware => stats.malware_blocked += 1,
-            _ => {}
-        }
+        *self.engine.write().await = engine;
+        *self.filter_counts.write().await = filter_counts;
+        *self.filter_sources.write().await = filter_sources;
+        *self.last_reload.write().await = SystemTime::now();
+        self.filter_generation.fetch_add(1, Ordering::AcqRel);
     }
-}
-
-/// Simple API wrapper for easy integration
-pub struct SimpleAdBlocker {
-    blocker: AdBlockerAPI,
-}
-
-impl SimpleAdBlocker {
-    /// Create a new simple ad blocker with default settings
-    pub async fn new() -> Result<Self> {
-        let config = AdBlockerConfig::default();
-        let blocker = AdBlockerAPI::new(config).await?;
-        
-        Ok(Self { blocker })
+    
+    /// Add domain to whitelist. A `*.example.com` entry whitelists every
+    /// subdomain of `example.com`, but not `example.com` itself.
+    pub fn add_whitelist_domain(&mut self, domain: String) {
+        Arc::make_mut(&mut self.whitelist_domains).insert(whitelist_entry(&domain));
+        Arc::make_mut(&mut self.config).whitelist_domains.push(domain);
+        self.reset_result_cache();
     }
     
-    /// Create with custom configuration
-    pub async fn with_config(config: AdBlockerConfig) -> Result<Self> {
-        let blocker = AdBlockerAPI::new(config).await?;
-        Ok(Self { blocker })
+    /// Remove domain from whitelist. Entries are compared in their stored
+    /// form, so `*.Example.com` removes `*.example.com`, while `example.com`
+    /// leaves a `*.example.com` entry in place. Removing a domain that isn't
+    /// whitelisted does nothing.
+    pub fn remove_whitelist_domain(&mut self, domain: &str) {
+        let entry = whitelist_entry(domain);
+        Arc::make_mut(&mut self.whitelist_domains).remove(&entry);
+        Arc::make_mut(&mut self.config).whitelist_domains.retain(|d| whitelist_entry(d) != entry);
+        self.reset_result_cache();
     }
     
-    /// Simple check if URL should be blocked
-    pub async fn is_blocked(&self, url: &str) -> bool {
-        match self.blocker.should_block(url, None).await {
-            Ok(result) => result.should_block,
-            Err(_) => false,
-        }
+    /// Remove every domain from the whitelist
+    pub fn clear_whitelist(&mut self) {
+        Arc::make_mut(&mut self.whitelist_domains).clear();
+        Arc::make_mut(&mut self.config).whitelist_domains.clear();
+        self.reset_result_cache();
+    }
+    
+    /// Whether a `config.whitelist_paths` entry is a prefix of the URL's host and path
+    fn path_whitelisted(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        self.whitelist_paths
+            .iter()
+            .any(|(allowed_host, prefix)| host == allowed_host && url.path().starts_with(prefix.as_str()))
+    }
+    
+    /// Whether a `*.parent` whitelist entry covers `domain`
+    fn wildcard_whitelisted(&self, domain: &str) -> bool {
+        parent_domains(domain)
+            .skip(1)
+            .any(|parent| self.whitelist_domains.contains(&format!("*.{}", parent)))
+    }
+    
+    /// Add a regex to the tracking patterns; URLs it matches are blocked as
+    /// `Tracking` while `block_tracking` is on. Like the whitelist, the
+    /// pattern only applies to this handle.
+    pub fn add_tracking_pattern(&mut self, pattern: &str) -> Result<()> {
+        let regex = compile_patterns(&[pattern.to_string()], "tracking pattern")?;
+        Arc::make_mut(&mut self.tracking_patterns).extend(regex)?;
+        Arc::make_mut(&mut self.config).extra_tracking_patterns.push(pattern.to_string());
+        self.reset_result_cache();
+        Ok(())
+    }
+    
+    /// Turn tracking pattern matching on or off without rebuilding the engine
+    pub fn set_block_tracking(&mut self, enabled: bool) {
+        Arc::make_mut(&mut self.config).block_tracking = enabled;
+        self.reset_result_cache();
+    }
+    
+    /// Turn social media pattern matching on or off without rebuilding the engine.
+    /// The social annoyances filter list is only loaded if `block_social` was
+    /// set at construction.
+    pub fn set_block_social(&mut self, enabled: bool) {
+        Arc::make_mut(&mut self.config).block_social = enabled;
+        self.reset_result_cache();
+    }
+    
+    /// How often filter list downloads were answered from the cache
+    pub async fn filter_cache_stats(&self) -> CacheStats {
+        self.filter_manager.lock().await.cache_stats()
+    }
+    
+    /// How much the downloaded lists overlapped when they were last loaded,
+    /// which failed to load (including `config.enable_hosts_lists` entries,
+    /// by URL), and how long loading them and compiling the engine took.
+    /// Custom filters and hosts lists aren't included in the rule counts.
+    pub async fn load_summary(&self) -> LoadSummary {
+        let mut summary = self.lists.read().await.summary.clone();
+        summary.compile_duration = *self.compile_duration.read().await;
+        summary.failed_sources.extend(self.failed_hosts_lists.read().await.iter().cloned());
+        summary
+    }
+    
+    /// Number of network, exception and cosmetic rules loaded into the engine
+    pub async fn filter_stats(&self) -> FilterCounts {
+        *self.filter_counts.read().await
+    }
+    
+    /// Custom filter rules from the configuration, including ones added at
+    /// runtime through this handle or any clone
+    pub async fn custom_filters(&self) -> Vec<String> {
+        self.custom_filters.read().await.clone()
+    }
+    
+    /// Get current configuration
+    pub fn get_config(&self) -> &AdBlockerConfig {
+        &self.config
+    }
+    
+    /// Whether filter lists have finished loading
+    pub fn is_ready(&self) -> bool {
+        *self.load_state.borrow() == LoadState::Ready
+    }
+    
+    /// Wait for a `new_lazy` blocker to finish loading its filter lists.
+    /// Returns whether they loaded; `false` means the background load failed
+    /// (the error is logged) and only custom filters and patterns apply
+    /// until a `reload_filters` succeeds. Returns at once for other blockers.
+    pub async fn wait_ready(&self) -> bool {
+        let mut state = self.load_state.subscribe();
+        // The sender lives as long as `self`, so this can't fail
+        let loaded = state.wait_for(|state| *state != LoadState::Loading).await;
+        loaded.is_ok_and(|state| *state == LoadState::Ready)
+    }
+    
+    /// Readiness and loaded rule counts, e.g. for a `/healthz` endpoint
+    pub async fn status(&self) -> StatusReport {
+        let counts = *self.filter_counts.read().await;
+        StatusReport {
+            ready: self.is_ready(),
+            rules_loaded: counts.network_rules + counts.exception_rules + counts.cosmetic_rules,
+            domains_loaded: counts.domain_rules,
+            last_reload: *self.last_reload.read().await,
+        }
+    }
+    
+    /// Whether more than `max_age` has passed since the lists were last
+    /// loaded by a successful `reload_filters`, or since construction if
+    /// there hasn't been one. Rebuilds for custom filter changes don't count.
+    pub async fn is_stale(&self, max_age: Duration) -> bool {
+        let loaded = *self.lists_loaded.read().await;
+        self.clock
+            .system_now()
+            .duration_since(loaded)
+            .is_ok_and(|age| age > max_age)
+    }
+    
+    /// Page to serve in place of a blocked request, from
+    /// `config.block_page_template` or the built-in template
+    pub fn block_page_html(&self, domain: &str, reason: &str, category: BlockCategory) -> String {
+        match &self.config.block_page_template {
+            Some(template) => render_block_page(template, domain, reason, category),
+            None => block_page_html(domain, reason, category),
+        }
+    }
+    
+    /// Get blocking statistics
+    pub async fn get_stats(&self) -> BlockStats {
+        let mut stats = self.stats.read().await.clone();
+        stats.scale_sampled(self.config.stats_sample_rate);
+        stats
+    }
+    
+    /// Body for a Prometheus `/metrics` endpoint, served with the content
+    /// type `text/plain; version=0.0.4`. Metrics are prefixed with `adblocker`.
+    pub async fn metrics(&self) -> String {
+        self.get_stats().await.to_prometheus("adblocker")
+    }
+    
+    /// Counts for one client recorded by `should_block_for`
+    pub async fn client_stats(&self, client_id: &str) -> Option<ClientStats> {
+        self.client_stats.read().await.get(client_id).cloned()
+    }
+    
+    /// The `n` domains most often blocked for a client, most blocked first
+    pub async fn top_blocked_domains(&self, client_id: &str, n: usize) -> Vec<(String, u64)> {
+        self.client_stats
+            .read()
+            .await
+            .get(client_id)
+            .map(|stats| stats.top_blocked(n))
+            .unwrap_or_default()
+    }
+    
+    /// Per-minute request and block counts for the last hour, oldest first,
+    /// scaled up like `get_stats` when `config.stats_sample_rate` is below 1.0
+    pub async fn windowed_stats(&self) -> Vec<MinuteBucket> {
+        let mut buckets = self.windowed_stats.read().await.buckets();
+        for bucket in &mut buckets {
+            bucket.scale_sampled(self.config.stats_sample_rate);
+        }
+        buckets
+    }
+    
+    /// Most recent checks, oldest first (empty unless `log_size` is set)
+    pub async fn recent_queries(&self) -> Vec<LogEntry> {
+        self.query_log.read().await.iter().cloned().collect()
+    }
+    
+    /// Domains that were checked and allowed because no rule matched, but
+    /// contain one of `config.watch_keywords`, sorted. Candidates for new
+    /// block rules. Past `config.watch_size` domains, the one recorded
+    /// first is dropped for each new one.
+    pub async fn watched_allows(&self) -> Vec<String> {
+        self.watched_allows.read().await.domains.iter().cloned().collect()
+    }
+    
+    /// Reset statistics
+    pub async fn reset_stats(&self) {
+        *self.windowed_stats.write().await = WindowedStats::default();
+        self.client_stats.write().await.clear();
+        let mut stats = self.stats.write().await;
+        *stats = BlockStats::default();
+    }
+    
+    /// Update configuration
+    pub async fn update_config(&mut self, new_config: AdBlockerConfig) -> Result<()> {
+        *self = Self::new(new_config).await?;
+        Ok(())
+    }
+    
+    /// Call `callback` with the URL and verdict of every `should_block` check
+    /// made through this handle (and `check_http_request`, which shares it),
+    /// or with the hostname for `is_domain_blocked` checks, e.g. to push
+    /// decisions to a queue or live view. It runs on the checking task after
+    /// the verdict is final, so it should be quick.
+    pub fn set_on_decision(&mut self, callback: Box<DecisionCallback>) {
+        self.on_decision = Some(Arc::from(callback));
+    }
+    
+    /// Replace the clock used to evaluate `config.schedule` and `is_stale`
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+        self.reset_result_cache();
+    }
+    
+    /// Whether the schedule allows blocking `category` right now
+    fn is_scheduled(&self, category: BlockCategory) -> bool {
+        self.config.schedule
+            .as_ref()
+            .is_none_or(|schedule| schedule.is_active(category, self.clock.now()))
+    }
+    
+    async fn log_query(&self, url: &str, result: &BlockResult) {
+        if self.config.log_size == 0 {
+            return;
+        }
+        
+        let mut query_log = self.query_log.write().await;
+        if query_log.len() >= self.config.log_size {
+            query_log.pop_front();
+        }
+        query_log.push_back(LogEntry {
+            url: url.to_string(),
+            result: result.clone(),
+            timestamp: SystemTime::now(),
+        });
+    }
+    
+    /// Remember `domain` if no rule matched it and it contains one of
+    /// `config.watch_keywords`
+    async fn watch_allowed(&self, domain: &str, result: &BlockResult) {
+        if result.category != BlockCategory::Clean {
+            return;
+        }
+        if self.watch_keywords.iter().any(|keyword| domain.contains(keyword.as_str())) {
+            self.watched_allows.write().await.insert(domain, self.config.watch_size);
+        }
+    }
+    
+    /// Count the size of a blocked response towards `bytes_saved`, for callers
+    /// that know it (e.g. a proxy that saw its `Content-Length`)
+    pub async fn record_block_size(&self, category: BlockCategory, bytes: u64) {
+        let mut stats = self.stats.write().await;
+        stats.bytes_saved += bytes;
+        *stats.bytes_saved_by_category.entry(category).or_default() += bytes;
+    }
+    
+}
+
+/// Parse a URL to check, treating scheme-less input like "example.com/ads" as http
+fn parse_checked_url(url: &str) -> std::result::Result<Url, url::ParseError> {
+    match Url::parse(url) {
+        Err(url::ParseError::RelativeUrlWithoutBase) if !url.is_empty() => Url::parse(&format!("http://{}", url)),
+        parsed => parsed,
+    }
+}
+
+fn invalid_url_result() -> BlockResult {
+    BlockResult {
+        should_block: false,
+        would_block: false,
+        reason: BlockReason::InvalidUrl,
+        filter_matched: None,
+        source_list: None,
+        category: BlockCategory::Clean,
+        is_third_party: false,
+    }
+}
+
+/// A whitelist entry as stored and compared: a normalized domain, keeping a
+/// leading `*.` wildcard
+fn whitelist_entry(domain: &str) -> String {
+    match domain.strip_prefix("*.") {
+        Some(parent) => format!("*.{}", normalize_domain(parent)),
+        None => normalize_domain(domain).into_owned(),
+    }
+}
+
+/// A URL with its percent-encoded bytes decoded, or unchanged if it has none
+/// or they don't decode to UTF-8
+fn decode_url(url: &str) -> Cow<'_, str> {
+    urlencoding::decode(url).unwrap_or(Cow::Borrowed(url))
+}
+
+/// Split a `host/path` whitelist entry into its host and path prefix,
+/// normalized the way URLs being checked are (lowercase punycode host,
+/// dot segments resolved, special characters percent-encoded)
+fn whitelist_path_entry(entry: &str) -> Result<(String, String)> {
+    let url = if entry.contains("://") {
+        Url::parse(entry)?
+    } else {
+        Url::parse(&format!("http://{}", entry))?
+    };
+    let host = url
+        .host_str()
+        .ok_or_else(|| AdBlockerError::InvalidUrl(format!("whitelist path `{}` has no host", entry)))?;
+    Ok((host.to_string(), url.path().to_string()))
+}
+
+fn whitelisted_result(is_third_party: bool) -> BlockResult {
+    BlockResult {
+        should_block: false,
+        would_block: false,
+        reason: BlockReason::Whitelisted,
+        filter_matched: None,
+        source_list: None,
+        category: BlockCategory::Whitelisted,
+        is_third_party,
+    }
+}
+
+/// Background task started by `AdBlockerAPI::spawn_auto_update`, stopped
+/// when this is dropped
+#[derive(Debug)]
+pub struct AutoUpdateHandle {
+    task: JoinHandle<()>,
+}
+
+impl Drop for AutoUpdateHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// One regex finding any of the literal `signatures`, or `None` if there are none
+fn compile_signatures(signatures: &[String]) -> Result<Option<regex::bytes::Regex>> {
+    let alternatives: Vec<String> = signatures
+        .iter()
+        .filter(|signature| !signature.is_empty())
+        .map(|signature| regex::escape(signature))
+        .collect();
+    if alternatives.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(regex::bytes::Regex::new(&alternatives.join("|"))?))
+}
+
+/// Whether a `Content-Type` is HTML, JavaScript or other text worth scanning
+fn is_text_content(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    media_type.starts_with("text/")
+        || matches!(
+            media_type.as_str(),
+            "application/javascript" | "application/x-javascript" | "application/ecmascript" | "application/xhtml+xml"
+        )
+}
+
+/// Compile user-supplied regexes, naming the offending `kind` of pattern on error
+fn compile_patterns(patterns: &[String], kind: &str) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|e| {
+                AdBlockerError::FilterParse(format!("invalid {} `{}`: {}", kind, pattern, e))
+            })
+        })
+        .collect()
+}
+
+/// Download the enabled built-in lists, `config.filter_lists` and `config.extra_sources`
+#[instrument(skip_all)]
+async fn download_lists(config: &AdBlockerConfig, filter_manager: &mut FilterManager) -> Result<ListRules> {
+    let mut lists = ListRules::new(config.max_rules);
+    
+    // Built-in lists as (enabled, url, source name, category, required). Optional
+    // lists may fail due to network issues without aborting startup.
+    let built_in = [
+        (config.enable_easylist, FilterSources::EASYLIST, "EasyList", BlockCategory::Advertisement, true),
+        (config.enable_easyprivacy, FilterSources::EASYPRIVACY, "EasyPrivacy", BlockCategory::Tracking, true),
+        (config.enable_malware_protection, FilterSources::MALWARE_DOMAINS, "Malware", BlockCategory::Malware, false),
+        (config.enable_phishing_protection, FilterSources::PHISHING, "Phishing", BlockCategory::Phishing, false),
+        (config.enable_cryptomining, FilterSources::CRYPTOMINING, "Cryptomining", BlockCategory::Cryptomining, false),
+        (config.block_social, FilterSources::SOCIAL_ANNOYANCES, "Social", BlockCategory::Social, true),
+    ];
+    let enabled: Vec<_> = built_in.iter().filter(|(enabled, ..)| *enabled).collect();
+    
+    // Download all enabled lists, and any user-provided sources, concurrently
+    let urls: Vec<&str> = enabled
+        .iter()
+        .map(|(_, url, ..)| *url)
+        .chain(config.filter_lists.iter().map(|spec| spec.url.as_str()))
+        .collect();
+    let ((results, extra_results), load_duration) = timed(async {
+        tokio::join!(
+            filter_manager.load_many_timed(&urls, config.cache_filters),
+            join_all(config.extra_sources.iter().map(|source| timed(source.fetch()))),
+        )
+    })
+    .await;
+    lists.summary.load_duration = load_duration;
+    let mut results = results.into_iter();
+    
+    for ((_, _, name, category, required), (result, elapsed)) in enabled.into_iter().zip(results.by_ref()) {
+        lists.summary.source_load_durations.insert(name.to_string(), elapsed);
+        match result {
+            Ok(rules) => lists.add(&rules, name, *category),
+            Err(e) if *required && !config.fail_open => return Err(e),
+            Err(e) if *required => lists.fail(name, e),
+            Err(e) => {
+                warn!(list = *name, error = %e, "could not load optional filter list");
+                lists.summary.failed_sources.push(name.to_string());
+            }
+        }
+    }
+    
+    for (spec, (result, elapsed)) in config.filter_lists.iter().zip(results) {
+        lists.summary.source_load_durations.insert(spec.url.clone(), elapsed);
+        match result {
+            Ok(rules) => lists.add(&rules, &spec.url, BlockCategory::Advertisement),
+            Err(e) if config.fail_open => lists.fail(&spec.url, e),
+            Err(e) => return Err(e),
+        }
+    }
+    
+    for (source, (result, elapsed)) in config.extra_sources.iter().zip(extra_results) {
+        lists.summary.source_load_durations.insert(source.name(), elapsed);
+        match result {
+            Ok(rules) => lists.add(&rules, &source.name(), source.category()),
+            Err(e) if config.fail_open => lists.fail(&source.name(), e),
+            Err(e) => return Err(e),
+        }
+    }
+    
+    info!(
+        network_rules = lists.counts.network_rules,
+        exception_rules = lists.counts.exception_rules,
+        cosmetic_rules = lists.counts.cosmetic_rules,
+        "loaded filter lists"
+    );
+    info!(
+        total_rules = lists.summary.total_rules,
+        duplicate_rules = lists.summary.duplicate_rules,
+        "deduplicated filter lists"
+    );
+    Ok(lists)
+}
+
+/// An empty hosts blocker downloading with `filter_manager`'s client, or
+/// `None` if `config.enable_hosts_lists` is empty
+fn hosts_blocker(config: &AdBlockerConfig, filter_manager: &FilterManager) -> Option<StevenBlackBlocker> {
+    (!config.enable_hosts_lists.is_empty()).then(|| StevenBlackBlocker::empty().with_client(filter_manager.client().clone()))
+}
+
+/// Domains recorded by `watch_allowed`, remembering the order they were
+/// first seen so the oldest can be dropped
+#[derive(Default)]
+struct WatchedAllows {
+    domains: BTreeSet<String>,
+    order: VecDeque<String>,
+}
+
+impl WatchedAllows {
+    /// Record `domain`, dropping the oldest domains past `max_size`
+    fn insert(&mut self, domain: &str, max_size: usize) {
+        if max_size == 0 || !self.domains.insert(domain.to_string()) {
+            return;
+        }
+        self.order.push_back(domain.to_string());
+        while self.order.len() > max_size {
+            if let Some(oldest) = self.order.pop_front() {
+                self.domains.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Rules from the downloaded lists, with the counts and sources of their rules
+#[derive(Clone)]
+struct ListRules {
+    filter_set: FilterSet,
+    counts: FilterCounts,
+    sources: HashMap<String, RuleSource>,
+    summary: LoadSummary,
+    /// `config.max_rules`; rules from lists added past it are dropped
+    max_rules: Option<usize>,
+    /// Rules added so far, not counting comments and blank lines
+    added_rules: usize,
+}
+
+impl ListRules {
+    fn new(max_rules: Option<usize>) -> Self {
+        Self {
+            filter_set: FilterSet::new(true),
+            counts: FilterCounts::default(),
+            sources: HashMap::new(),
+            summary: LoadSummary::default(),
+            max_rules,
+            added_rules: 0,
+        }
+    }
+    
+    fn add(&mut self, rules: &[String], list: &str, category: BlockCategory) {
+        let rules = self.within_cap(rules, list);
+        
+        // Every distinct rule gets a source entry, so new entries are the unique rules
+        let known_rules = self.sources.len();
+        record_sources(&mut self.sources, rules, list, category);
+        let read = rules.iter().filter(|rule| is_rule(rule)).count();
+        let unique = self.sources.len() - known_rules;
+        self.summary.total_rules += read;
+        self.summary.unique_rules += unique;
+        self.summary.duplicate_rules += read - unique;
+        
+        let exceptions_before = self.counts.exception_rules;
+        let unsupported_before = self.summary.unsupported_rules.len();
+        count_rules(&mut self.counts, rules, &mut self.summary.unsupported_rules);
+        self.summary.exceptions += self.counts.exception_rules - exceptions_before;
+        let unsupported = self.summary.unsupported_rules.len() - unsupported_before;
+        if unsupported > 0 {
+            warn!(list, unsupported, "skipping rules the engine can't parse (e.g. `$rewrite=` or `$sitekey=`)");
+        }
+        self.filter_set.add_filters(rules, Default::default());
+    }
+    
+    /// The leading part of `rules` that fits under `max_rules`. The rest is
+    /// counted in `dropped_rules` and logged.
+    fn within_cap<'a>(&mut self, rules: &'a [String], list: &str) -> &'a [String] {
+        let is_rule = |rule: &&String| is_rule(rule);
+        let Some(max_rules) = self.max_rules else {
+            self.added_rules += rules.iter().filter(is_rule).count();
+            return rules;
+        };
+        
+        let room = max_rules.saturating_sub(self.added_rules);
+        let end = rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| is_rule(rule))
+            .nth(room)
+            .map_or(rules.len(), |(index, _)| index);
+        let (kept, dropped) = rules.split_at(end);
+        self.added_rules += kept.iter().filter(is_rule).count();
+        
+        let dropped = dropped.iter().filter(is_rule).count();
+        if dropped > 0 {
+            warn!(list, dropped, max_rules, "reached max_rules; dropping the rest of the list");
+            self.summary.dropped_rules += dropped;
+        }
+        kept
+    }
+    
+    /// Record a required list that failed to load but was skipped because of `config.fail_open`
+    fn fail(&mut self, list: &str, error: AdBlockerError) {
+        warn!(list, error = %error, "could not load filter list; continuing without it");
+        self.summary.failed_sources.push(list.to_string());
+    }
+    
+    /// Build an engine from these rules plus `custom_filters`, which are
+    /// never dropped by `max_rules`
+    fn compose(&self, custom_filters: &[String]) -> (Engine, FilterCounts, HashMap<String, RuleSource>) {
+        let mut lists = self.clone();
+        lists.max_rules = None;
+        if !custom_filters.is_empty() {
+            lists.add(custom_filters, "Custom", BlockCategory::Custom);
+        }
+        (Engine::from_filter_set(lists.filter_set, true), lists.counts, lists.sources)
+    }
+}
+
+/// Progress of loading the filter lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoadState {
+    Loading,
+    Ready,
+    /// A `new_lazy` background load failed
+    Failed,
+}
+
+/// The list a rule was loaded from and the category its matches report
+#[derive(Debug, Clone)]
+struct RuleSource {
+    list: Arc<str>,
+    category: BlockCategory,
+}
+
+/// Remember which list each rule came from so matches can be attributed.
+/// When a rule appears in several lists, the first list loaded wins.
+fn record_sources(filter_sources: &mut HashMap<String, RuleSource>, rules: &[String], list: &str, category: BlockCategory) {
+    let source = RuleSource { list: list.into(), category };
+    for rule in rules.iter().filter(|rule| is_rule(rule)) {
+        filter_sources
+            .entry(rule.trim().to_string())
+            .or_insert_with(|| source.clone());
+    }
+}
+
+/// Tally rules by kind, skipping comments. Rules the engine can't parse,
+/// such as ones with unsupported modifiers, are added to `unsupported`.
+fn count_rules(counts: &mut FilterCounts, rules: &[String], unsupported: &mut Vec<String>) {
+    for rule in rules {
+        match parse_filter(rule, false, Default::default()) {
+            Ok(ParsedFilter::Network(filter)) if filter.is_exception() => counts.exception_rules += 1,
+            Ok(ParsedFilter::Network(_)) => {
+                counts.network_rules += 1;
+                if is_domain_rule(rule) {
+                    counts.domain_rules += 1;
+                }
+            }
+            Ok(ParsedFilter::Cosmetic(_)) => counts.cosmetic_rules += 1,
+            Err(_) if rule.trim().is_empty() || is_comment_rule(rule.trim()) => {}
+            Err(_) => unsupported.push(rule.trim().to_string()),
+        }
+    }
+}
+
+/// Whether a line of a list is a rule rather than blank, a comment or a header
+fn is_rule(rule: &str) -> bool {
+    let rule = rule.trim();
+    !rule.is_empty() && !is_comment_rule(rule)
+}
+
+/// Whether a trimmed rule is a comment (`! ...`) or a header like `[Adblock Plus 2.0]`
+fn is_comment_rule(rule: &str) -> bool {
+    rule.starts_with('!') || (rule.starts_with('[') && rule.ends_with(']'))
+}
+
+/// Whether a rule blocks a whole domain and nothing else (`||host^`)
+fn is_domain_rule(rule: &str) -> bool {
+    rule.trim()
+        .strip_prefix("||")
+        .and_then(|rest| rest.strip_suffix('^'))
+        .is_some_and(|host| !host.is_empty() && !host.contains(['/', '*', '^', '$']))
+}
+
+/// Simple API wrapper for easy integration
+///
+/// Clones share the underlying engine and statistics, but each has its own
+/// configuration and whitelist (see [`AdBlockerAPI`]).
+#[derive(Clone)]
+pub struct SimpleAdBlocker {
+    blocker: AdBlockerAPI,
+}
+
+impl SimpleAdBlocker {
+    /// Create a new simple ad blocker with default settings
+    pub async fn new() -> Result<Self> {
+        let config = AdBlockerConfig::default();
+        let blocker = AdBlockerAPI::new(config).await?;
+        
+        Ok(Self { blocker })
+    }
+    
+    /// Create with custom configuration
+    pub async fn with_config(config: AdBlockerConfig) -> Result<Self> {
+        let blocker = AdBlockerAPI::new(config).await?;
+        Ok(Self { blocker })
+    }
+    
+    /// Create with default settings without waiting for the filter lists,
+    /// which load in the background (see `AdBlockerAPI::new_lazy`)
+    pub async fn new_lazy() -> Result<Self> {
+        Self::with_config_lazy(AdBlockerConfig::default()).await
+    }
+    
+    /// Create with custom configuration without waiting for the filter lists
+    pub async fn with_config_lazy(config: AdBlockerConfig) -> Result<Self> {
+        let blocker = AdBlockerAPI::new_lazy(config).await?;
+        Ok(Self { blocker })
+    }
+    
+    /// Whether the filter lists have finished loading
+    pub fn is_ready(&self) -> bool {
+        self.blocker.is_ready()
+    }
+    
+    /// Wait for the filter lists to load; `false` if loading them failed
+    pub async fn wait_ready(&self) -> bool {
+        self.blocker.wait_ready().await
+    }
+    
+    /// Simple check if URL should be blocked
+    pub async fn is_blocked(&self, url: &str) -> bool {
+        match self.blocker.should_block(url, None).await {
+            Ok(result) => result.should_block,
+            Err(_) => false,
+        }
     }
     
     /// Get detailed block information
@@ -289,8 +1753,1745 @@ impl SimpleAdBlocker {
         self.blocker.should_block(url, None).await
     }
     
+    /// Get detailed block information, listing every matching category
+    pub async fn check_url_full(&self, url: &str) -> Result<BlockResultFull> {
+        self.blocker.check_url_full(url).await
+    }
+    
+    /// Report every check made on a URL and what each one found
+    pub async fn explain(&self, url: &str) -> Result<Explanation> {
+        self.blocker.explain(url).await
+    }
+    
+    /// Get detailed block information for a URL requested by `source_url`,
+    /// which `$domain=` and third-party rules need to match
+    pub async fn check_url_from(&self, url: &str, source_url: &str) -> Result<BlockResult> {
+        self.blocker.should_block(url, Some(source_url)).await
+    }
+    
+    /// Get detailed block information for a proxied request given by its
+    /// host, path and scheme (see `AdBlockerAPI::check_http_request`)
+    pub async fn check_http_request(&self, host: &str, path: &str, scheme: &str, source_host: Option<&str>) -> Result<BlockResult> {
+        self.blocker.check_http_request(host, path, scheme, source_host).await
+    }
+    
+    /// Get detailed block information for a bare hostname
+    pub async fn check_domain(&self, domain: &str) -> Result<BlockResult> {
+        self.blocker.is_domain_blocked(domain).await
+    }
+    
+    /// Add a custom filter rule (e.g. `||ads.example.com^`)
+    pub async fn add_custom_filter(&mut self, filter: String) -> Result<()> {
+        self.blocker.add_custom_filter(filter).await
+    }
+    
+    /// Add several custom filter rules at once
+    pub async fn add_custom_filters(&mut self, filters: Vec<String>) -> Result<()> {
+        self.blocker.add_custom_filters(filters).await
+    }
+    
+    /// Add domain to whitelist
+    pub fn add_whitelist_domain(&mut self, domain: String) {
+        self.blocker.add_whitelist_domain(domain);
+    }
+    
+    /// Remove domain from whitelist
+    pub fn remove_whitelist_domain(&mut self, domain: &str) {
+        self.blocker.remove_whitelist_domain(domain);
+    }
+    
+    /// Remove every domain from the whitelist
+    pub fn clear_whitelist(&mut self) {
+        self.blocker.clear_whitelist();
+    }
+    
     /// Get blocking statistics
     pub async fn get_stats(&self) -> BlockStats {
         self.blocker.get_stats().await
     }
-}
\ No newline at end of file
+    
+    /// Most recent checks, oldest first (empty unless `log_size` is set)
+    pub async fn recent_queries(&self) -> Vec<LogEntry> {
+        self.blocker.recent_queries().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{BodySignatures, FilterSource, FilterSourceSpec};
+    use crate::test_util::{FixedClock, FixedSystemClock, MockResponse, MockServer, closed_url, filter_list, september, temp_path};
+    use async_trait::async_trait;
+    use std::sync::atomic::AtomicUsize;
+    use tracing_test::traced_test;
+    
+    /// A config that downloads none of the built-in lists
+    fn offline_config() -> AdBlockerConfig {
+        AdBlockerConfig {
+            enable_easylist: false,
+            enable_easyprivacy: false,
+            ..AdBlockerConfig::default()
+        }
+    }
+    
+    #[tokio::test]
+    async fn reports_the_list_a_blocking_rule_came_from() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||payload.example^".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let result = blocker.should_block("https://payload.example/dropper.js", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.source_list.as_deref(), Some("Custom"));
+        assert_eq!(result.filter_matched.as_deref(), Some("||payload.example^"));
+        
+        let result = blocker.should_block("https://news.example/", None).await.unwrap();
+        assert!(!result.should_block);
+        assert_eq!(result.source_list, None);
+    }
+    
+    /// A blocker with the built-in list `url` (e.g. `FilterSources::MALWARE_DOMAINS`)
+    /// downloaded from a local mirror serving `rules`, plus whatever `config` enables
+    async fn with_mirrored_list(config: AdBlockerConfig, url: &str, rules: &[&str]) -> AdBlockerAPI {
+        let server = MockServer::start(vec![("/mirror.txt", vec![MockResponse::ok(filter_list(rules))])]).await;
+        let filter_manager = FilterManager::new().with_mirror(url, server.url("/mirror.txt"));
+        AdBlockerAPI::from_filter_manager(config, filter_manager).await.unwrap()
+    }
+    
+    #[tokio::test]
+    async fn malware_list_matches_report_the_malware_source() {
+        let config = AdBlockerConfig { enable_malware_protection: true, ..offline_config() };
+        let blocker = with_mirrored_list(config, FilterSources::MALWARE_DOMAINS, &["||payload.example^"]).await;
+        
+        let result = blocker.should_block("https://payload.example/dropper.exe", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.source_list.as_deref(), Some("Malware"));
+        assert_eq!(result.filter_matched.as_deref(), Some("||payload.example^"));
+        assert_eq!(result.category, BlockCategory::Malware);
+        assert_eq!(blocker.get_stats().await.malware_blocked, 1);
+    }
+    
+    #[tokio::test]
+    async fn from_rules_blocks_only_the_given_rules() {
+        // `from_rules` ignores the built-in lists even when they're enabled
+        let blocker = AdBlockerAPI::from_rules(vec!["||test-ad.com^".to_string()], AdBlockerConfig::default()).await.unwrap();
+        
+        let result = blocker.should_block("https://test-ad.com/banner.js", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.source_list.as_deref(), Some("Rules"));
+        assert_eq!(result.category, BlockCategory::Advertisement);
+        assert!(!blocker.should_block("https://news.example/", None).await.unwrap().should_block);
+        assert_eq!(blocker.filter_stats().await.network_rules, 1);
+    }
+    
+    #[tokio::test]
+    async fn dry_run_reports_but_never_blocks() {
+        let config = AdBlockerConfig { dry_run: true, log_size: 10, ..offline_config() };
+        let blocker = AdBlockerAPI::from_rules(vec!["||ads.example^".to_string()], config).await.unwrap();
+        
+        let result = blocker.should_block("https://ads.example/banner.js", None).await.unwrap();
+        assert!(!result.should_block);
+        assert!(result.would_block);
+        assert_eq!(result.category, BlockCategory::Advertisement);
+        
+        let clean = blocker.should_block("https://news.example/", None).await.unwrap();
+        assert!(!clean.should_block && !clean.would_block);
+        
+        assert_eq!(blocker.get_stats().await.blocked_requests, 1);
+        assert!(blocker.recent_queries().await[0].result.would_block);
+    }
+    
+    #[tokio::test]
+    async fn repeated_checks_hit_the_result_cache() {
+        let config = AdBlockerConfig { result_cache_size: 16, ..offline_config() };
+        let mut blocker = AdBlockerAPI::from_rules(vec!["||ads.example^".to_string()], config).await.unwrap();
+        
+        for _ in 0..3 {
+            assert!(blocker.should_block("https://ads.example/beacon", None).await.unwrap().should_block);
+            assert!(!blocker.should_block("https://news.example/", None).await.unwrap().should_block);
+        }
+        assert_eq!(blocker.result_cache.hits(), 4);
+        let stats = blocker.get_stats().await;
+        assert_eq!((stats.total_requests, stats.blocked_requests), (6, 3));
+        
+        blocker.add_custom_filter("||news.example^".to_string()).await.unwrap();
+        assert!(blocker.should_block("https://news.example/", None).await.unwrap().should_block);
+        
+        blocker.add_whitelist_domain("news.example".to_string());
+        let result = blocker.should_block("https://news.example/", None).await.unwrap();
+        assert_eq!(result.category, BlockCategory::Whitelisted);
+    }
+    
+    #[tokio::test]
+    #[traced_test]
+    async fn blocks_emit_a_debug_event_with_the_category() {
+        let blocker = AdBlockerAPI::from_rules(vec!["||ads.example^".to_string()], offline_config()).await.unwrap();
+        blocker.should_block("https://ads.example/banner.js", None).await.unwrap();
+        
+        assert!(logs_contain("checked URL"));
+        assert!(logs_contain("category=Advertisement"));
+        assert!(logs_contain("filter=\"||ads.example^\""));
+    }
+    
+    #[tokio::test]
+    async fn precedence_decides_between_whitelist_and_rules() {
+        let rules = vec!["||ads.example^".to_string()];
+        let config = AdBlockerConfig {
+            whitelist_domains: vec!["ads.example".to_string(), "news.example".to_string()],
+            ..offline_config()
+        };
+        
+        let lenient = AdBlockerAPI::from_rules(rules.clone(), config.clone()).await.unwrap();
+        let result = lenient.should_block("https://ads.example/banner.js", None).await.unwrap();
+        assert!(!result.should_block);
+        assert_eq!(result.category, BlockCategory::Whitelisted);
+        assert!(!lenient.is_domain_blocked("ads.example").await.unwrap().should_block);
+        
+        let strict_config = AdBlockerConfig { precedence: Precedence::BlocklistWins, ..config };
+        let strict = AdBlockerAPI::from_rules(rules, strict_config).await.unwrap();
+        let result = strict.should_block("https://ads.example/banner.js", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.category, BlockCategory::Advertisement);
+        assert!(strict.is_domain_blocked("ads.example").await.unwrap().should_block);
+        
+        // Whitelisted domains no rule matches are still reported as such
+        let result = strict.should_block("https://news.example/", None).await.unwrap();
+        assert_eq!(result.category, BlockCategory::Whitelisted);
+    }
+    
+    #[tokio::test]
+    async fn batch_domain_checks_keep_their_order() {
+        let rules = vec!["||ads.example^".to_string(), "||tracker.test^".to_string()];
+        let blocker = AdBlockerAPI::from_rules(rules, offline_config()).await.unwrap();
+        let domains = ["news.example", "ads.example", "cdn.ads.example", "tracker.test.", "example.org"];
+        
+        let results = blocker.batch_check_domains(domains.iter().map(|d| d.to_string()).collect()).await.unwrap();
+        let verdicts: Vec<(&str, bool)> = results.iter().map(|(domain, result)| (domain.as_str(), result.should_block)).collect();
+        assert_eq!(verdicts, [
+            ("news.example", false),
+            ("ads.example", true),
+            ("cdn.ads.example", true),
+            ("tracker.test.", true),
+            ("example.org", false),
+        ]);
+        assert_eq!(results[1].1.category, BlockCategory::Advertisement);
+        assert_eq!(blocker.get_stats().await.total_requests, 5);
+    }
+    
+    #[tokio::test]
+    async fn extra_patterns_extend_the_built_in_sets() {
+        let config = AdBlockerConfig {
+            block_social: true,
+            extra_tracking_patterns: vec![r"/telemetry/v\d+/".to_string()],
+            extra_social_patterns: vec![r"mastodon\.example/share".to_string()],
+            ..offline_config()
+        };
+        let mut blocker = AdBlockerAPI::from_rules(Vec::new(), config).await.unwrap();
+        
+        let result = blocker.should_block("https://app.example/telemetry/v2/events", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.category, BlockCategory::Tracking);
+        let result = blocker.should_block("https://mastodon.example/share?text=hi", None).await.unwrap();
+        assert_eq!(result.category, BlockCategory::Social);
+        
+        assert!(!blocker.should_block("https://app.example/beacon.gif", None).await.unwrap().should_block);
+        blocker.add_tracking_pattern(r"/beacon\.gif$").unwrap();
+        let result = blocker.should_block("https://app.example/beacon.gif", None).await.unwrap();
+        assert_eq!(result.category, BlockCategory::Tracking);
+        
+        assert!(matches!(blocker.add_tracking_pattern("(unclosed"), Err(AdBlockerError::FilterParse(_))));
+        let invalid = AdBlockerConfig { extra_social_patterns: vec!["[".to_string()], ..offline_config() };
+        assert!(AdBlockerAPI::from_rules(Vec::new(), invalid).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn whitelist_paths_allow_only_their_prefix() {
+        let config = AdBlockerConfig {
+            whitelist_paths: vec!["CDN.example.com/app/".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::from_rules(vec!["||cdn.example.com^".to_string()], config).await.unwrap();
+        
+        let result = blocker.should_block("https://cdn.example.com/app/main.js", None).await.unwrap();
+        assert!(!result.should_block);
+        assert_eq!(result.category, BlockCategory::Whitelisted);
+        assert!(blocker.should_block("https://cdn.example.com/ads/banner.js", None).await.unwrap().should_block);
+        
+        // Paths are normalized before matching
+        assert!(!blocker.should_block("https://cdn.example.com/ads/../app/vendor.js", None).await.unwrap().should_block);
+        assert!(blocker.should_block("https://cdn.example.com/app/../ads/banner.js", None).await.unwrap().should_block);
+        assert!(blocker.should_block("https://cdn.example.com/application.js", None).await.unwrap().should_block);
+        assert!(blocker.should_block("https://img.cdn.example.com/app/logo.png", None).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn engine_matches_report_their_list_category() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string()],
+            block_tracking: false,
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        let result = blocker.should_block("https://ads.example/banner.png", None).await.unwrap();
+        assert!(matches!(result.category, BlockCategory::Custom));
+    }
+    
+    #[tokio::test]
+    async fn easyprivacy_matches_get_the_tracking_category() {
+        let config = AdBlockerConfig { enable_easyprivacy: true, block_tracking: false, ..offline_config() };
+        let blocker = with_mirrored_list(config, FilterSources::EASYPRIVACY, &["||metrics.example^"]).await;
+        let result = blocker.should_block("https://metrics.example/collect", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.category, BlockCategory::Tracking);
+        assert_eq!(result.source_list.as_deref(), Some("EasyPrivacy"));
+        assert_eq!(blocker.get_stats().await.trackers_blocked, 1);
+    }
+    
+    #[tokio::test]
+    async fn cryptomining_list_blocks_known_miner_domains() {
+        let config = AdBlockerConfig { enable_cryptomining: true, ..offline_config() };
+        let blocker = with_mirrored_list(config, FilterSources::CRYPTOMINING, &["||coinhive.com^", "||crypto-loot.com^"]).await;
+        
+        let result = blocker.is_domain_blocked("coinhive.com").await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.category, BlockCategory::Cryptomining);
+        assert_eq!(result.source_list.as_deref(), Some("Cryptomining"));
+        let result = blocker.should_block("https://static.crypto-loot.com/lib/miner.min.js", None).await.unwrap();
+        assert_eq!(result.category, BlockCategory::Cryptomining);
+        assert_eq!(blocker.get_stats().await.cryptomining_blocked, 2);
+    }
+    
+    #[tokio::test]
+    async fn phishing_list_matches_are_counted_as_phishing() {
+        let phishing = ["||login-verify-account.example^", "||secure-bank-update.example^"];
+        let config = AdBlockerConfig { enable_phishing_protection: true, ..offline_config() };
+        let blocker = with_mirrored_list(config, FilterSources::PHISHING, &phishing).await;
+        
+        for url in ["https://login-verify-account.example/signin", "http://www.secure-bank-update.example/"] {
+            let result = blocker.should_block(url, None).await.unwrap();
+            assert!(result.should_block, "{}", url);
+            assert_eq!(result.category, BlockCategory::Phishing);
+            assert_eq!(result.source_list.as_deref(), Some("Phishing"));
+        }
+        assert!(!blocker.should_block("https://bank.example/login", None).await.unwrap().should_block);
+        
+        let stats = blocker.get_stats().await;
+        assert_eq!((stats.phishing_blocked, stats.blocked_requests, stats.total_requests), (2, 2, 3));
+    }
+    
+    #[tokio::test]
+    async fn ip_hosts_in_blocked_ranges_are_blocked() {
+        let config = AdBlockerConfig {
+            blocked_ip_ranges: vec!["203.0.113.0/24".parse().unwrap(), "2001:db8::/32".parse().unwrap()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::from_rules(Vec::new(), config).await.unwrap();
+        
+        let result = blocker.should_block("http://203.0.113.5/ad", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.category, BlockCategory::Tracking);
+        assert_eq!(result.reason, BlockReason::IpRange);
+        assert_eq!(result.filter_matched.as_deref(), Some("203.0.113.0/24"));
+        assert!(blocker.should_block("http://[2001:db8::5]/ad", None).await.unwrap().should_block);
+        // IPv4-mapped IPv6 literals can't sidestep the IPv4 ranges
+        assert!(blocker.should_block("http://[::ffff:203.0.113.5]/ad", None).await.unwrap().should_block);
+        
+        assert!(!blocker.should_block("http://198.51.100.5/ad", None).await.unwrap().should_block);
+        // Domains aren't resolved to check them
+        assert!(!blocker.should_block("http://ads.example/ad", None).await.unwrap().should_block);
+        assert_eq!(blocker.get_stats().await.trackers_blocked, 3);
+    }
+    
+    #[tokio::test]
+    async fn explain_reports_every_check() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string(), "@@||ads.example/allowed/".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        let stages = [
+            Check::Whitelist,
+            Check::IpRanges,
+            Check::HostsLists,
+            Check::Engine,
+            Check::CustomRegex,
+            Check::TrackingPatterns,
+            Check::SocialPatterns,
+        ];
+        
+        let clean = blocker.explain("https://news.example/article").await.unwrap();
+        assert!(!clean.would_block);
+        assert_eq!(clean.decided_by, None);
+        assert_eq!(clean.checks.iter().map(|outcome| outcome.check).collect::<Vec<_>>(), stages);
+        assert!(clean.checks.iter().all(|outcome| outcome.status != CheckStatus::Matched), "{:?}", clean.checks);
+        
+        let ad = blocker.explain("https://ads.example/banner.js").await.unwrap();
+        assert!(ad.would_block);
+        assert_eq!(ad.decided_by, Some(Check::Engine));
+        let engine = ad.checks.iter().find(|outcome| outcome.check == Check::Engine).unwrap();
+        assert_eq!(engine.status, CheckStatus::Matched);
+        assert_eq!(engine.detail.as_deref(), Some("||ads.example^"));
+        
+        let excepted = blocker.explain("https://ads.example/allowed/banner.js").await.unwrap();
+        assert!(!excepted.would_block);
+        assert!(excepted.checks[3].detail.as_deref().is_some_and(|detail| detail.starts_with("excepted by")));
+        
+        // Scheme-less URLs are read as http, like `should_block` does
+        let bare = blocker.explain("ads.example/banner.js").await.unwrap();
+        assert!(bare.would_block);
+        assert_eq!(bare.url, "http://ads.example/banner.js");
+        
+        // Explaining doesn't count as a check
+        assert_eq!(blocker.get_stats().await.total_requests, 0);
+    }
+    
+    #[tokio::test]
+    async fn stats_follow_the_sample_rate() {
+        let check_all = |blocker: AdBlockerAPI| async move {
+            for i in 0..100 {
+                let url = if i % 4 == 0 { "https://ads.example/banner.js" } else { "https://news.example/" };
+                blocker.should_block(url, None).await.unwrap();
+            }
+            (blocker.get_stats().await, blocker.windowed_stats().await)
+        };
+        let sampled = |stats_sample_rate| AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string()],
+            stats_sample_rate,
+            ..offline_config()
+        };
+        
+        let (stats, buckets) = check_all(AdBlockerAPI::new(sampled(0.0)).await.unwrap()).await;
+        assert_eq!((stats.total_requests, stats.blocked_requests, stats.custom_blocked), (0, 0, 0));
+        assert!(buckets.is_empty());
+        
+        let (stats, buckets) = check_all(AdBlockerAPI::new(sampled(1.0)).await.unwrap()).await;
+        assert_eq!((stats.total_requests, stats.blocked_requests, stats.custom_blocked), (100, 25, 25));
+        assert_eq!(buckets.iter().map(|bucket| bucket.total_requests).sum::<u64>(), 100);
+        assert_eq!(buckets.iter().map(|bucket| bucket.blocked_requests).sum::<u64>(), 25);
+        
+        // A check's block is only counted if its request is
+        for _ in 0..10 {
+            let (stats, buckets) = check_all(AdBlockerAPI::new(sampled(0.5)).await.unwrap()).await;
+            assert!(stats.blocked_requests <= stats.total_requests, "{:?}", stats);
+            assert!(buckets.iter().all(|bucket| bucket.blocked_requests <= bucket.total_requests), "{:?}", buckets);
+        }
+        
+        for rate in [f64::NAN, -0.1, 1.5] {
+            let err = AdBlockerAPI::new(sampled(rate)).await.err().unwrap();
+            assert!(matches!(&err, AdBlockerError::Config(message) if message.contains("stats_sample_rate")), "{:?}", err);
+        }
+    }
+    
+    #[tokio::test]
+    async fn response_bodies_are_scanned_for_ad_signatures() {
+        let config = AdBlockerConfig {
+            body_signatures: BodySignatures::default_signatures(),
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::from_rules(Vec::new(), config).await.unwrap();
+        let body = br#"<p>Story</p><ins class="adsbygoogle"></ins><script>(adsbygoogle = window.adsbygoogle || []).push({});</script>"#;
+        
+        let matches = blocker.scan_response_body("text/html; charset=utf-8", body);
+        assert_eq!(matches.len(), 3);
+        assert!(matches.iter().all(|found| found.signature == "adsbygoogle" && &body[found.range.clone()] == b"adsbygoogle"));
+        assert_eq!(matches[0].range, 24..35);
+        
+        assert!(blocker.scan_response_body("image/png", body).is_empty());
+        assert!(!blocker.scan_response_body("application/javascript", b"googletag.pubads().refresh();").is_empty());
+        
+        // Scanning is off unless signatures are configured
+        let blocker = AdBlockerAPI::from_rules(Vec::new(), offline_config()).await.unwrap();
+        assert!(blocker.scan_response_body("text/html", body).is_empty());
+    }
+    
+    #[tokio::test]
+    async fn disabled_built_in_patterns_stop_matching() {
+        let config = AdBlockerConfig {
+            disabled_patterns: vec![r"segment\.com".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::from_rules(Vec::new(), config).await.unwrap();
+        
+        assert!(!blocker.should_block("https://api.segment.com/v1/track", None).await.unwrap().should_block);
+        let result = blocker.should_block("https://static.hotjar.com/c/hotjar.js", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.reason, BlockReason::TrackingPattern);
+        
+        // Patterns can be disabled by index too
+        let all = TrackingPatterns::get_patterns().unwrap();
+        let without_first = TrackingPatterns::get_patterns_except(&["0".to_string()]).unwrap();
+        assert_eq!(without_first.len(), all.len() - 1);
+        assert_eq!(without_first[0].as_str(), all[1].as_str());
+    }
+    
+    #[tokio::test]
+    async fn decision_callback_sees_every_verdict() {
+        let mut blocker = AdBlockerAPI::from_rules(vec!["||ads.example^".to_string()], offline_config()).await.unwrap();
+        let decisions = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = decisions.clone();
+        blocker.set_on_decision(Box::new(move |url: &str, result: &BlockResult| {
+            seen.lock().unwrap().push((url.to_string(), result.should_block, result.reason));
+        }));
+        
+        blocker.should_block("https://ads.example/banner.js", None).await.unwrap();
+        blocker.should_block("https://news.example/", None).await.unwrap();
+        blocker.is_domain_blocked("ADS.example").await.unwrap();
+        assert_eq!(*decisions.lock().unwrap(), [
+            ("https://ads.example/banner.js".to_string(), true, BlockReason::AdFilter),
+            ("https://news.example/".to_string(), false, BlockReason::Clean),
+            ("ads.example".to_string(), true, BlockReason::AdFilter),
+        ]);
+    }
+    
+    #[tokio::test]
+    async fn patterns_match_percent_decoded_paths() {
+        let config = AdBlockerConfig {
+            extra_tracking_patterns: vec!["/ads/".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::from_rules(Vec::new(), config).await.unwrap();
+        
+        let result = blocker.should_block("https://cdn.example/static%2Fads%2Fbanner.js", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.category, BlockCategory::Tracking);
+        assert_eq!(result.filter_matched.as_deref(), Some("/ads/"));
+        assert!(!blocker.should_block("https://cdn.example/static%2Fapp.js", None).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn social_blocks_are_counted_by_category() {
+        let config = AdBlockerConfig {
+            block_social: true,
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::from_rules(Vec::new(), config).await.unwrap();
+        let result = blocker.should_block("https://www.facebook.com/plugins/like.php", None).await.unwrap();
+        assert_eq!(result.category, BlockCategory::Social);
+        
+        let stats = blocker.get_stats().await;
+        assert_eq!((stats.social_blocked, stats.custom_blocked), (1, 0));
+        assert_eq!(stats.by_category()[&BlockCategory::Social], 1);
+        assert_eq!(stats.by_category()[&BlockCategory::Advertisement], 0);
+    }
+    
+    #[tokio::test]
+    async fn domain_scoped_rules_need_a_matching_source() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||widget.example^$domain=trusted.example".to_string()],
+            ..offline_config()
+        };
+        let blocker = SimpleAdBlocker::with_config(config).await.unwrap();
+        let url = "https://widget.example/embed.js";
+        
+        assert!(blocker.check_url_from(url, "https://trusted.example/page").await.unwrap().should_block);
+        assert!(blocker.check_url_from(url, "https://www.trusted.example/").await.unwrap().should_block);
+        assert!(!blocker.check_url_from(url, "https://other.example/page").await.unwrap().should_block);
+        assert!(!blocker.check_url(url).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn analyze_page_summarizes_resources() {
+        let blocker = AdBlockerAPI::from_rules(vec!["||ads.example^".to_string()], offline_config()).await.unwrap();
+        let resources = [
+            "https://news.example/app.js",
+            "https://cdn.news.example/hero.png",
+            "https://ads.example/banner.js",
+            "https://www.google-analytics.com/analytics.js",
+            "https://fonts.example/serif.woff2",
+        ];
+        
+        let report = blocker
+            .analyze_page("https://news.example/article", resources.iter().map(|url| url.to_string()).collect())
+            .await
+            .unwrap();
+        
+        assert_eq!(report.total_resources, 5);
+        assert_eq!(report.third_party_resources, 3);
+        assert_eq!(report.category_counts[&BlockCategory::Clean], 3);
+        assert_eq!(report.category_counts[&BlockCategory::Advertisement], 1);
+        assert_eq!(report.category_counts[&BlockCategory::Tracking], 1);
+        let blocked: Vec<&str> = report.blocked.iter().map(|(url, _)| url.as_str()).collect();
+        assert_eq!(blocked, ["https://ads.example/banner.js", "https://www.google-analytics.com/analytics.js"]);
+        assert!(report.blocked.iter().all(|(_, result)| result.is_third_party));
+    }
+    
+    #[tokio::test]
+    async fn recorded_block_sizes_accumulate_per_category() {
+        let blocker = AdBlockerAPI::new(offline_config()).await.unwrap();
+        blocker.record_block_size(BlockCategory::Advertisement, 48_000).await;
+        blocker.record_block_size(BlockCategory::Tracking, 512).await;
+        blocker.record_block_size(BlockCategory::Advertisement, 2_000).await;
+        
+        let stats = blocker.get_stats().await;
+        assert_eq!(stats.bytes_saved, 50_512);
+        assert_eq!(stats.bytes_saved_by_category[&BlockCategory::Advertisement], 50_000);
+        assert_eq!(stats.bytes_saved_by_category[&BlockCategory::Tracking], 512);
+        assert!(!stats.bytes_saved_by_category.contains_key(&BlockCategory::Malware));
+    }
+    
+    #[tokio::test]
+    async fn sanitize_url_strips_tracking_params() {
+        let blocker = AdBlockerAPI::new(offline_config()).await.unwrap();
+        
+        let url = "https://shop.example/item?id=7&fbclid=IwAR0abc&utm_source=news&color=red#reviews";
+        assert_eq!(blocker.sanitize_url(url).unwrap(), "https://shop.example/item?id=7&color=red#reviews");
+        assert_eq!(blocker.sanitize_url("https://shop.example/?gclid=1&msclkid=2").unwrap(), "https://shop.example/");
+    }
+    
+    #[tokio::test]
+    async fn sanitize_url_leaves_clean_urls_alone() {
+        let blocker = AdBlockerAPI::new(offline_config()).await.unwrap();
+        
+        for url in ["https://shop.example/item?b=2&a=1#top", "https://shop.example/item", "https://shop.example/?utm=keep"] {
+            assert_eq!(blocker.sanitize_url(url).unwrap(), url);
+        }
+    }
+    
+    #[tokio::test]
+    async fn sanitize_url_uses_configured_params() {
+        let config = AdBlockerConfig { tracking_params: vec!["ref".to_string()], ..offline_config() };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let url = "https://shop.example/?ref=home&fbclid=x";
+        assert_eq!(blocker.sanitize_url(url).unwrap(), "https://shop.example/?fbclid=x");
+    }
+    
+    #[tokio::test]
+    async fn third_party_compares_registrable_domains() {
+        let blocker = AdBlockerAPI::new(offline_config()).await.unwrap();
+        let cases = [
+            ("https://example.com/app.js", Some("https://example.com/"), false),
+            ("https://a.example.co.uk/app.js", Some("https://b.example.co.uk/page"), false),
+            ("https://cdn.example.com/app.js", Some("https://www.example.com/"), false),
+            ("https://tracker.example.net/p.gif", Some("https://example.com/"), true),
+            ("https://other.co.uk/p.gif", Some("https://example.co.uk/"), true),
+            ("https://tracker.example.net/p.gif", None, false),
+        ];
+        for (url, source, third_party) in cases {
+            assert_eq!(blocker.should_block(url, source).await.unwrap().is_third_party, third_party, "{}", url);
+        }
+    }
+    
+    #[tokio::test]
+    async fn clones_share_stats() {
+        let blocker = SimpleAdBlocker::with_config(AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string()],
+            ..offline_config()
+        })
+        .await
+        .unwrap();
+        let clone = blocker.clone();
+        
+        let handle = tokio::spawn(async move { clone.is_blocked("https://ads.example/banner.png").await });
+        assert!(handle.await.unwrap());
+        assert!(!blocker.is_blocked("https://news.example/").await);
+        
+        let stats = blocker.get_stats().await;
+        assert_eq!((stats.total_requests, stats.blocked_requests), (2, 1));
+    }
+    
+    #[tokio::test]
+    async fn clones_keep_their_own_whitelist() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        let url = "https://ads.example/banner.png";
+        assert!(blocker.should_block(url, None).await.unwrap().should_block);
+        
+        let mut clone = blocker.clone();
+        clone.add_whitelist_domain("ads.example".to_string());
+        assert!(!clone.should_block(url, None).await.unwrap().should_block);
+        // The original doesn't see the whitelist entry
+        assert!(blocker.should_block(url, None).await.unwrap().should_block);
+        assert!(blocker.get_config().whitelist_domains.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn whitelist_entries_and_checks_are_normalized() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||doubleclick.net^".to_string(), "||ads.example^".to_string()],
+            whitelist_domains: vec!["Ads.Example.".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        assert!(blocker.is_domain_blocked("Doubleclick.NET.").await.unwrap().should_block);
+        assert_eq!(blocker.is_domain_blocked("ADS.example").await.unwrap().reason, BlockReason::Whitelisted);
+        assert!(!blocker.should_block("https://ads.example./banner.png", None).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn simple_blocker_forwards_filters_and_whitelist() {
+        let mut blocker = SimpleAdBlocker::with_config(offline_config()).await.unwrap();
+        assert!(!blocker.is_blocked("http://foo.test/").await);
+        
+        blocker.add_custom_filter("||foo.test^".to_string()).await.unwrap();
+        assert!(blocker.is_blocked("http://foo.test/").await);
+        
+        blocker.add_whitelist_domain("foo.test".to_string());
+        assert!(!blocker.is_blocked("http://foo.test/").await);
+        blocker.remove_whitelist_domain("foo.test");
+        assert!(blocker.is_blocked("http://foo.test/").await);
+    }
+    
+    #[tokio::test]
+    async fn category_toggles_apply_immediately() {
+        let mut blocker = AdBlockerAPI::new(offline_config()).await.unwrap();
+        let tracker = "https://www.google-analytics.com/collect?v=1";
+        let widget = "https://www.facebook.com/plugins/like.php";
+        
+        let result = blocker.should_block(tracker, None).await.unwrap();
+        assert!(matches!(result.category, BlockCategory::Tracking), "{:?}", result);
+        assert!(!blocker.should_block(widget, None).await.unwrap().should_block);
+        
+        blocker.set_block_tracking(false);
+        blocker.set_block_social(true);
+        assert!(!blocker.should_block(tracker, None).await.unwrap().should_block);
+        let result = blocker.should_block(widget, None).await.unwrap();
+        assert!(matches!(result.category, BlockCategory::Social), "{:?}", result);
+        
+        blocker.set_block_tracking(true);
+        assert!(blocker.should_block(tracker, None).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn query_log_keeps_the_latest_checks() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string()],
+            log_size: 3,
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        for i in 0..4 {
+            blocker.should_block(&format!("https://news.example/{}", i), None).await.unwrap();
+        }
+        blocker.should_block("https://ads.example/banner.png", None).await.unwrap();
+        
+        let log = blocker.recent_queries().await;
+        let urls: Vec<&str> = log.iter().map(|entry| entry.url.as_str()).collect();
+        assert_eq!(urls, ["https://news.example/2", "https://news.example/3", "https://ads.example/banner.png"]);
+        assert!(!log[0].result.should_block);
+        assert!(log[2].result.should_block);
+        assert!(log.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    }
+    
+    #[tokio::test]
+    async fn query_log_is_off_by_default() {
+        let blocker = AdBlockerAPI::new(offline_config()).await.unwrap();
+        blocker.should_block("https://news.example/", None).await.unwrap();
+        assert!(blocker.recent_queries().await.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn checks_land_in_the_current_minute() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        blocker.should_block("https://ads.example/banner.png", None).await.unwrap();
+        blocker.should_block("https://news.example/", None).await.unwrap();
+        
+        let buckets = blocker.windowed_stats().await;
+        let last = buckets.last().unwrap();
+        assert!(last.minute + 1 >= WindowedStats::current_minute());
+        assert_eq!(buckets.iter().map(|b| b.total_requests).sum::<u64>(), 2);
+        assert_eq!(buckets.iter().map(|b| b.blocked_requests).sum::<u64>(), 1);
+    }
+    
+    #[test]
+    fn validate_filter_reports_the_rule_kind() {
+        assert_eq!(AdBlockerAPI::validate_filter("||ads.example.com^").unwrap(), FilterKind::Network);
+        assert_eq!(AdBlockerAPI::validate_filter("/banner/*$image,third-party").unwrap(), FilterKind::Network);
+        assert_eq!(AdBlockerAPI::validate_filter("@@||ads.example.com/ok.js").unwrap(), FilterKind::Exception);
+        assert_eq!(AdBlockerAPI::validate_filter("news.example##.banner").unwrap(), FilterKind::Cosmetic);
+        assert_eq!(AdBlockerAPI::validate_filter("! Title: My list").unwrap(), FilterKind::Comment);
+        assert_eq!(AdBlockerAPI::validate_filter("[Adblock Plus 2.0]").unwrap(), FilterKind::Comment);
+        
+        let err = AdBlockerAPI::validate_filter("||ads.example.com^$unknown-option").unwrap_err();
+        assert!(matches!(&err, AdBlockerError::FilterParse(message) if message.contains("unknown-option")), "{:?}", err);
+        assert!(AdBlockerAPI::validate_filter("").is_err());
+    }
+    
+    #[tokio::test]
+    async fn filter_stats_count_custom_rules_by_kind() {
+        let initial = vec!["||ads.example^".to_string(), "@@||ads.example/ok.js".to_string(), "news.example##.banner".to_string()];
+        let config = AdBlockerConfig { custom_filters: initial.clone(), ..offline_config() };
+        let mut blocker = AdBlockerAPI::new(config).await.unwrap();
+        let before = blocker.filter_stats().await;
+        assert_eq!((before.network_rules, before.exception_rules, before.cosmetic_rules), (1, 1, 1));
+        
+        let custom = vec!["||one.example^".to_string(), "||two.example^".to_string(), "/ad-loader.js".to_string()];
+        for filter in &custom {
+            blocker.add_custom_filter(filter.clone()).await.unwrap();
+        }
+        
+        let after = blocker.filter_stats().await;
+        assert_eq!(after.network_rules, before.network_rules + 3);
+        assert_eq!(after.exception_rules, before.exception_rules);
+        assert_eq!(blocker.custom_filters().await, [initial, custom].concat());
+    }
+    
+    #[tokio::test]
+    async fn allowed_domains_with_watch_keywords_are_recorded() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||analytics.ads.example^".to_string()],
+            watch_keywords: vec!["Analytics".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        assert!(!blocker.should_block("https://analytics.example.com/collect", None).await.unwrap().should_block);
+        assert!(!blocker.is_domain_blocked("news-analytics.example").await.unwrap().should_block);
+        blocker.should_block("https://news.example/", None).await.unwrap();
+        // Blocked domains aren't watched
+        assert!(blocker.should_block("https://analytics.ads.example/", None).await.unwrap().should_block);
+        assert_eq!(blocker.watched_allows().await, ["analytics.example.com", "news-analytics.example"]);
+        
+        let unwatched = AdBlockerAPI::new(offline_config()).await.unwrap();
+        unwatched.should_block("https://analytics.example.com/collect", None).await.unwrap();
+        assert!(unwatched.watched_allows().await.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn watched_allows_keep_the_newest_domains() {
+        let config = AdBlockerConfig { watch_keywords: vec!["metrics".to_string()], watch_size: 3, ..offline_config() };
+        let blocker = AdBlockerAPI::from_rules(Vec::new(), config).await.unwrap();
+        
+        for domain in ["d.metrics.example", "a.metrics.example", "d.metrics.example", "c.metrics.example", "b.metrics.example"] {
+            blocker.is_domain_blocked(domain).await.unwrap();
+        }
+        
+        assert_eq!(blocker.watched_allows().await, ["a.metrics.example", "b.metrics.example", "c.metrics.example"]);
+    }
+    
+    #[tokio::test]
+    async fn batch_added_filters_rebuild_once() {
+        let rules = (0..500).map(|i| format!("||batch{}.example^", i)).collect::<Vec<_>>();
+        let config = AdBlockerConfig { custom_filters: vec!["||initial.example^".to_string()], ..offline_config() };
+        
+        let mut batched = AdBlockerAPI::new(config).await.unwrap();
+        let generation = batched.filter_generation.load(Ordering::Acquire);
+        batched.add_custom_filters(rules).await.unwrap();
+        
+        assert_eq!(batched.filter_generation.load(Ordering::Acquire), generation + 1);
+        assert_eq!(batched.filter_stats().await.network_rules, 501);
+        assert_eq!(batched.get_config().custom_filters.len(), 501);
+        assert!(batched.should_block("https://initial.example/", None).await.unwrap().should_block);
+        for i in 0..500 {
+            let url = format!("https://batch{}.example/ad.js", i);
+            assert!(batched.should_block(&url, None).await.unwrap().should_block, "{}", url);
+        }
+    }
+    
+    #[tokio::test]
+    async fn regex_filters_block_as_custom() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string()],
+            regex_filters: vec![r"/creatives?/\d+x\d+\.".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let result = blocker.should_block("https://cdn.example/creative/300x250.png", None).await.unwrap();
+        assert!(result.should_block);
+        assert!(matches!(result.category, BlockCategory::Custom));
+        assert_eq!(result.reason, BlockReason::CustomFilter);
+        assert_eq!(result.filter_matched.as_deref(), Some(r"/creatives?/\d+x\d+\."));
+        
+        assert!(!blocker.should_block("https://cdn.example/creative/logo.png", None).await.unwrap().should_block);
+        // The engine is consulted first
+        let result = blocker.should_block("https://ads.example/creatives/728x90.gif", None).await.unwrap();
+        assert_eq!(result.reason, BlockReason::AdFilter);
+        // and the built-in tracking patterns after
+        let result = blocker.should_block("https://www.google-analytics.com/creative/1x1.gif", None).await.unwrap();
+        assert_eq!(result.reason, BlockReason::CustomFilter);
+    }
+    
+    #[tokio::test]
+    async fn invalid_regex_filters_fail_construction() {
+        let config = AdBlockerConfig { regex_filters: vec!["/ads/(".to_string()], ..offline_config() };
+        let err = AdBlockerAPI::new(config).await.err().unwrap();
+        assert!(matches!(&err, AdBlockerError::FilterParse(message) if message.contains("/ads/(")), "{:?}", err);
+    }
+    
+    #[tokio::test]
+    async fn clear_custom_filters_drops_configured_and_added_rules() {
+        let config = AdBlockerConfig { custom_filters: vec!["||configured.example^".to_string()], ..offline_config() };
+        let mut blocker = AdBlockerAPI::from_rules(vec!["||listed.example^".to_string()], config).await.unwrap();
+        blocker.add_custom_filter("||custom.example^".to_string()).await.unwrap();
+        assert!(blocker.should_block("https://custom.example/pixel.gif", None).await.unwrap().should_block);
+        
+        blocker.clear_custom_filters().await.unwrap();
+        
+        assert!(!blocker.should_block("https://custom.example/pixel.gif", None).await.unwrap().should_block);
+        assert!(!blocker.should_block("https://configured.example/", None).await.unwrap().should_block);
+        assert!(blocker.should_block("https://listed.example/banner.png", None).await.unwrap().should_block);
+        assert!(blocker.custom_filters().await.is_empty());
+        assert!(blocker.get_config().custom_filters.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn added_rules_are_kept_in_the_config_until_reset_to_config() {
+        let config = AdBlockerConfig { custom_filters: vec!["||configured.example^".to_string()], ..offline_config() };
+        let mut blocker = AdBlockerAPI::from_rules(vec!["||listed.example^".to_string()], config).await.unwrap();
+        blocker.add_custom_filter("||custom.example^".to_string()).await.unwrap();
+        assert_eq!(blocker.get_config().custom_filters, ["||configured.example^", "||custom.example^"]);
+        
+        blocker.reset_to_config().await.unwrap();
+        
+        assert!(!blocker.should_block("https://custom.example/pixel.gif", None).await.unwrap().should_block);
+        assert!(blocker.should_block("https://configured.example/", None).await.unwrap().should_block);
+        assert!(blocker.should_block("https://listed.example/banner.png", None).await.unwrap().should_block);
+        assert_eq!(blocker.custom_filters().await, ["||configured.example^"]);
+        assert_eq!(blocker.get_config().custom_filters, ["||configured.example^"]);
+    }
+    
+    #[tokio::test]
+    async fn custom_filters_are_read_from_directories() {
+        let dir = temp_path("filter-dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ads.txt"), "! Ads\n||ads.example^\n\n").unwrap();
+        std::fs::write(dir.join("trackers.txt"), "# Trackers\n||pixel.example^\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "||news.example^\n").unwrap();
+        
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||configured.example^".to_string()],
+            custom_filter_dirs: vec![dir.clone()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        
+        assert_eq!(blocker.custom_filters().await, vec!["||configured.example^", "||ads.example^", "||pixel.example^"]);
+        for url in ["https://ads.example/banner.png", "https://pixel.example/p.gif"] {
+            let result = blocker.should_block(url, None).await.unwrap();
+            assert!(result.should_block, "{}", url);
+            assert_eq!(result.source_list.as_deref(), Some("Custom"));
+        }
+        assert!(!blocker.should_block("https://news.example/", None).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn custom_filters_added_through_any_clone_survive_rebuilds() {
+        let config = AdBlockerConfig { custom_filters: vec!["||configured.example^".to_string()], ..offline_config() };
+        let mut blocker = AdBlockerAPI::new(config).await.unwrap();
+        let mut other = blocker.clone();
+        other.add_custom_filter("||other.example^".to_string()).await.unwrap();
+        blocker.add_custom_filter("||first.example^".to_string()).await.unwrap();
+        blocker.reload_filters().await.unwrap();
+        other.reload_filters().await.unwrap();
+        
+        let urls = ["https://other.example/", "https://first.example/", "https://configured.example/"];
+        for handle in [&blocker, &other] {
+            for url in urls {
+                assert!(handle.should_block(url, None).await.unwrap().should_block, "{}", url);
+            }
+            assert_eq!(handle.custom_filters().await, ["||configured.example^", "||other.example^", "||first.example^"]);
+        }
+        
+        other.clear_custom_filters().await.unwrap();
+        assert!(!blocker.should_block("https://first.example/", None).await.unwrap().should_block);
+        assert!(blocker.custom_filters().await.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn scheme_less_urls_are_checked_as_http() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||doubleclick.net^".to_string(), "||example.com/ads".to_string()],
+            block_tracking: false,
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        for url in ["doubleclick.net", "example.com/ads", "DoubleClick.net"] {
+            let result = blocker.should_block(url, None).await.unwrap();
+            assert!(result.should_block, "{}", url);
+            assert_eq!(result.reason, BlockReason::AdFilter);
+        }
+        let result = blocker.should_block("example.com/news", None).await.unwrap();
+        assert_eq!(result.reason, BlockReason::Clean);
+    }
+    
+    #[tokio::test]
+    async fn malformed_urls_give_the_invalid_result() {
+        let blocker = AdBlockerAPI::new(offline_config()).await.unwrap();
+        for url in ["", "http://", "exa mple.com/ads", "http://[::1"] {
+            let result = blocker.should_block(url, None).await.unwrap();
+            assert_eq!(result.reason, BlockReason::InvalidUrl, "{:?}", url);
+            assert!(!result.should_block);
+        }
+        assert_eq!(blocker.get_stats().await.total_requests, 4);
+    }
+    
+    #[tokio::test]
+    async fn whitelist_matches_either_spelling_of_a_domain() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||ads.xn--bcher-kva.example^".to_string(), "||ads.xn--mnchen-3ya.example^".to_string()],
+            ..offline_config()
+        };
+        let mut blocker = AdBlockerAPI::new(config).await.unwrap();
+        blocker.add_whitelist_domain("ads.bücher.example".to_string());
+        
+        let result = blocker.should_block("https://ads.xn--bcher-kva.example/banner.png", None).await.unwrap();
+        assert_eq!(result.reason, BlockReason::Whitelisted);
+        let result = blocker.should_block("https://ads.BÜCHER.example/banner.png", None).await.unwrap();
+        assert_eq!(result.reason, BlockReason::Whitelisted);
+        assert!(blocker.should_block("https://ads.xn--mnchen-3ya.example/banner.png", None).await.unwrap().should_block);
+    }
+    
+    /// A filter source serving fixed rules, or failing if it has none
+    struct MemorySource {
+        category: BlockCategory,
+        rules: Vec<String>,
+    }
+    
+    #[async_trait]
+    impl FilterSource for MemorySource {
+        fn name(&self) -> String {
+            "Corporate".to_string()
+        }
+        
+        fn category(&self) -> BlockCategory {
+            self.category
+        }
+        
+        async fn fetch(&self) -> Result<Vec<String>> {
+            if self.rules.is_empty() {
+                return Err(AdBlockerError::FilterParse("corporate list is empty".to_string()));
+            }
+            Ok(self.rules.clone())
+        }
+    }
+    
+    #[tokio::test]
+    async fn custom_sources_load_alongside_built_in_lists() {
+        let source = MemorySource { category: BlockCategory::Malware, rules: vec!["||intranet-threat.example^".to_string()] };
+        let config = AdBlockerConfig { extra_sources: vec![Arc::new(source)], ..offline_config() };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let result = blocker.should_block("https://intranet-threat.example/payload", None).await.unwrap();
+        assert!(result.should_block);
+        assert!(matches!(result.category, BlockCategory::Malware));
+        assert_eq!(result.source_list.as_deref(), Some("Corporate"));
+    }
+    
+    #[tokio::test]
+    async fn max_rules_caps_the_list_rules() {
+        let rules: Vec<String> = (0..2000).map(|i| format!("||ads{}.example^", i)).collect();
+        let source = MemorySource { category: BlockCategory::Advertisement, rules };
+        let config = AdBlockerConfig {
+            extra_sources: vec![Arc::new(source)],
+            custom_filters: vec!["||custom.example^".to_string()],
+            max_rules: Some(100),
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let summary = blocker.load_summary().await;
+        assert_eq!(summary.dropped_rules, 1900);
+        assert_eq!(summary.unique_rules, 100);
+        assert_eq!(blocker.filter_stats().await.network_rules, 101);
+        assert!(blocker.should_block("https://ads99.example/", None).await.unwrap().should_block);
+        assert!(!blocker.should_block("https://ads100.example/", None).await.unwrap().should_block);
+        // Custom filters don't count towards the cap
+        assert!(blocker.should_block("https://custom.example/", None).await.unwrap().should_block);
+    }
+    
+    /// A filter source downloading a list from `url`
+    struct UrlSource {
+        name: &'static str,
+        url: String,
+    }
+    
+    #[async_trait]
+    impl FilterSource for UrlSource {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+        
+        fn category(&self) -> BlockCategory {
+            BlockCategory::Advertisement
+        }
+        
+        async fn fetch(&self) -> Result<Vec<String>> {
+            FilterManager::new().load_filters(&self.url, false).await
+        }
+    }
+    
+    #[tokio::test]
+    async fn filter_lists_are_downloaded_with_their_headers() {
+        let server = MockServer::start(vec![("/mirror/easylist.txt", vec![MockResponse::ok(filter_list(&["||ads.example^"]))])]).await;
+        let url = server.url("/mirror/easylist.txt");
+        let config = AdBlockerConfig {
+            filter_lists: vec![FilterSourceSpec::new(&url).with_header("Authorization", "Bearer mirror-token")],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let result = blocker.should_block("https://ads.example/banner.png", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.source_list, Some(url));
+        assert!(server.requests()[0].to_ascii_lowercase().contains("authorization: bearer mirror-token"));
+    }
+    
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn snapshots_run_alongside_added_filters() {
+        let blocker = AdBlockerAPI::from_rules(vec!["||ads.example^".to_string()], offline_config()).await.unwrap();
+        let mut adder = blocker.clone();
+        let adding = tokio::spawn(async move {
+            for i in 0..50 {
+                adder.add_custom_filter(format!("||tracker{}.example^", i)).await.unwrap();
+            }
+        });
+        let snapshots = tokio::spawn(async move {
+            for _ in 0..200 {
+                blocker.snapshot().await;
+            }
+        });
+        
+        let both = async { tokio::try_join!(adding, snapshots) };
+        tokio::time::timeout(Duration::from_secs(30), both).await.expect("snapshot deadlocked with add_custom_filter").unwrap();
+    }
+    
+    #[tokio::test]
+    async fn restored_snapshots_give_the_same_verdicts() {
+        let ads = MemorySource {
+            category: BlockCategory::Advertisement,
+            rules: vec!["||ads.example^".to_string(), "@@||ads.example/ok.js".to_string(), "/banner/*".to_string()],
+        };
+        let malware = MemorySource { category: BlockCategory::Malware, rules: vec!["||payload.example^".to_string()] };
+        let config = AdBlockerConfig {
+            extra_sources: vec![Arc::new(ads), Arc::new(malware)],
+            regex_filters: vec![r"/promo-[0-9]+\.js$".to_string()],
+            ..offline_config()
+        };
+        let mut blocker = AdBlockerAPI::new(config).await.unwrap();
+        blocker.add_custom_filter("||pixel.example^".to_string()).await.unwrap();
+        blocker.add_whitelist_domain("partner.example".to_string());
+        blocker.set_block_social(true);
+        
+        let json = serde_json::to_string_pretty(&blocker.snapshot().await).unwrap();
+        let restored = AdBlockerAPI::from_snapshot(serde_json::from_str(&json).unwrap()).await.unwrap();
+        
+        let urls = [
+            "https://ads.example/script.js",
+            "https://ads.example/ok.js",
+            "https://cdn.example/banner/1.png",
+            "https://payload.example/",
+            "https://pixel.example/1x1.gif",
+            "https://cdn.example/promo-7.js",
+            "https://partner.example/banner/2.png",
+            "https://www.facebook.com/plugins/like.php",
+            "https://www.google-analytics.com/collect",
+            "https://news.example/",
+        ];
+        for url in urls {
+            let original = blocker.should_block(url, None).await.unwrap();
+            let copy = restored.should_block(url, None).await.unwrap();
+            assert_eq!(serde_json::to_value(&original).unwrap(), serde_json::to_value(&copy).unwrap(), "{}", url);
+        }
+        assert_eq!(serde_json::to_string_pretty(&restored.snapshot().await).unwrap(), json);
+    }
+    
+    #[tokio::test]
+    async fn fail_open_keeps_the_lists_that_loaded() {
+        let server = MockServer::start(vec![("/ads.txt", vec![MockResponse::ok(filter_list(&["||ads.example^"]))])]).await;
+        let unreachable = closed_url().await;
+        let sources = || -> Vec<Arc<dyn FilterSource>> {
+            vec![
+                Arc::new(UrlSource { name: "Ads", url: server.url("/ads.txt") }),
+                Arc::new(UrlSource { name: "Unreachable", url: unreachable.clone() }),
+            ]
+        };
+        
+        let config = AdBlockerConfig { extra_sources: sources(), ..offline_config() };
+        assert!(matches!(AdBlockerAPI::new(config).await, Err(AdBlockerError::Network(_))));
+        
+        let config = AdBlockerConfig { extra_sources: sources(), fail_open: true, ..offline_config() };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        assert!(blocker.should_block("https://ads.example/banner.png", None).await.unwrap().should_block);
+        assert_eq!(blocker.load_summary().await.failed_sources, ["Unreachable"]);
+    }
+    
+    #[tokio::test]
+    async fn failing_custom_sources_fail_construction() {
+        let failing = MemorySource { category: BlockCategory::Custom, rules: vec![] };
+        let config = AdBlockerConfig { extra_sources: vec![Arc::new(failing)], ..offline_config() };
+        assert!(matches!(AdBlockerAPI::new(config).await, Err(AdBlockerError::FilterParse(_))));
+    }
+    
+    /// A filter source serving the next of `lists` on each fetch, repeating
+    /// the last; a `None` list fails to download
+    struct SequenceSource {
+        lists: Vec<Option<Vec<String>>>,
+        fetches: Arc<AtomicUsize>,
+    }
+    
+    impl SequenceSource {
+        fn new(lists: &[Option<&str>]) -> (Self, Arc<AtomicUsize>) {
+            let fetches = Arc::new(AtomicUsize::new(0));
+            let lists = lists.iter().map(|rule| rule.map(|rule| vec![rule.to_string()])).collect();
+            (Self { lists, fetches: fetches.clone() }, fetches)
+        }
+    }
+    
+    #[async_trait]
+    impl FilterSource for SequenceSource {
+        fn category(&self) -> BlockCategory {
+            BlockCategory::Advertisement
+        }
+        
+        async fn fetch(&self) -> Result<Vec<String>> {
+            let fetch = self.fetches.fetch_add(1, Ordering::SeqCst);
+            self.lists[fetch.min(self.lists.len() - 1)]
+                .clone()
+                .ok_or_else(|| AdBlockerError::FilterParse("list unavailable".to_string()))
+        }
+    }
+    
+    #[tokio::test]
+    async fn reload_filters_replaces_lists_and_keeps_custom_rules() {
+        let (source, _) = SequenceSource::new(&[Some("||old.example^"), Some("||new.example^"), None]);
+        let config = AdBlockerConfig {
+            extra_sources: vec![Arc::new(source)],
+            custom_filters: vec!["||custom.example^".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        assert!(blocker.should_block("https://old.example/", None).await.unwrap().should_block);
+        
+        blocker.reload_filters().await.unwrap();
+        assert!(!blocker.should_block("https://old.example/", None).await.unwrap().should_block);
+        for url in ["https://new.example/", "https://custom.example/"] {
+            assert!(blocker.should_block(url, None).await.unwrap().should_block, "{}", url);
+        }
+        
+        // A failed reload keeps serving the previous lists
+        assert!(blocker.reload_filters().await.is_err());
+        let result = blocker.should_block("https://new.example/", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.source_list.as_deref(), Some("External"));
+    }
+    
+    #[tokio::test]
+    async fn auto_update_reloads_until_dropped() {
+        let (source, fetches) = SequenceSource::new(&[Some("||ads.example^")]);
+        let config = AdBlockerConfig { extra_sources: vec![Arc::new(source)], ..offline_config() };
+        let blocker = Arc::new(AdBlockerAPI::new(config).await.unwrap());
+        
+        let updater = blocker.clone().spawn_auto_update(Duration::from_millis(50));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(fetches.load(Ordering::SeqCst) >= 2);
+        assert!(blocker.should_block("https://ads.example/", None).await.unwrap().should_block);
+        
+        drop(updater);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let stopped_at = fetches.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(fetches.load(Ordering::SeqCst), stopped_at);
+    }
+    
+    #[tokio::test]
+    async fn parsed_and_string_checks_agree() {
+        let rules = ["||ads.example^", "@@||ads.example/allowed.js", "||widgets.example^$third-party"];
+        let lists = MemorySource { category: BlockCategory::Advertisement, rules: rules.iter().map(|rule| rule.to_string()).collect() };
+        let config = AdBlockerConfig {
+            extra_sources: vec![Arc::new(lists)],
+            regex_filters: vec![r"/promo/".to_string()],
+            ..offline_config()
+        };
+        let mut blocker = AdBlockerAPI::new(config).await.unwrap();
+        // Without downloading the social list
+        blocker.set_block_social(true);
+        blocker.add_whitelist_domain("trusted.example".to_string());
+        let urls = [
+            "https://ads.example/banner.png",
+            "https://ads.example/allowed.js",
+            "https://widgets.example/embed.js",
+            "https://shop.example/promo/summer",
+            "https://www.google-analytics.com/collect",
+            "https://www.facebook.com/plugins/like.php",
+            "https://trusted.example/ads/",
+            "https://news.example/",
+        ];
+        let source = "https://news.example/article";
+        
+        for url in urls {
+            let from_string = blocker.should_block(url, Some(source)).await.unwrap();
+            let parsed = blocker.should_block_parsed(&Url::parse(url).unwrap(), Some(&Url::parse(source).unwrap())).await.unwrap();
+            assert_eq!(serde_json::to_value(&from_string).unwrap(), serde_json::to_value(&parsed).unwrap(), "{}", url);
+        }
+        let stats = blocker.get_stats().await;
+        assert_eq!(stats.total_requests, 2 * urls.len() as u64);
+        assert_eq!(stats.ads_blocked, 2 * 2);
+    }
+    
+    #[tokio::test]
+    async fn bare_domains_are_classified() {
+        let ads = MemorySource { category: BlockCategory::Advertisement, rules: vec!["||doubleclick.net^".to_string()] };
+        let malware = MemorySource { category: BlockCategory::Malware, rules: vec!["||payload.example^".to_string()] };
+        let config = AdBlockerConfig { extra_sources: vec![Arc::new(ads), Arc::new(malware)], ..offline_config() };
+        let mut blocker = AdBlockerAPI::new(config).await.unwrap();
+        blocker.add_whitelist_domain("safe.doubleclick.net".to_string());
+        
+        let result = blocker.is_domain_blocked("stats.g.doubleclick.net").await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.reason, BlockReason::AdFilter);
+        assert!(matches!(result.category, BlockCategory::Advertisement));
+        assert!(matches!(blocker.is_domain_blocked("Payload.Example.").await.unwrap().category, BlockCategory::Malware));
+        assert_eq!(blocker.is_domain_blocked("cdn.safe.doubleclick.net").await.unwrap().reason, BlockReason::Whitelisted);
+        // URL patterns need a path, so they don't apply to domains
+        assert_eq!(blocker.is_domain_blocked("www.google-analytics.com").await.unwrap().reason, BlockReason::Clean);
+        assert_eq!(blocker.is_domain_blocked("").await.unwrap().reason, BlockReason::InvalidUrl);
+    }
+    
+    #[tokio::test]
+    async fn load_summary_counts_rules_shared_between_lists() {
+        let rules = |rules: &[&str]| rules.iter().map(|rule| rule.to_string()).collect();
+        let ads = MemorySource {
+            category: BlockCategory::Advertisement,
+            rules: rules(&["||ads.example^", "||pixel.example^", "@@||ads.example/ok.js", "||ads.example^"]),
+        };
+        let privacy = MemorySource {
+            category: BlockCategory::Tracking,
+            rules: rules(&["||pixel.example^", "||metrics.example^", "@@||metrics.example/consent.js"]),
+        };
+        let config = AdBlockerConfig { extra_sources: vec![Arc::new(ads), Arc::new(privacy)], ..offline_config() };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let summary = blocker.load_summary().await;
+        assert_eq!(summary.total_rules, 7);
+        assert_eq!(summary.unique_rules, 5);
+        assert_eq!(summary.duplicate_rules, 2);
+        assert_eq!(summary.exceptions, 2);
+    }
+    
+    #[tokio::test]
+    async fn list_headers_and_comments_are_not_counted_as_rules() {
+        let server = MockServer::start(vec![
+            ("/ads.txt", vec![MockResponse::ok(filter_list(&["||ads.example^", "||pixel.example^"]))]),
+            ("/privacy.txt", vec![MockResponse::ok(filter_list(&["||pixel.example^", "||metrics.example^"]))]),
+        ])
+        .await;
+        let config = AdBlockerConfig {
+            filter_lists: vec![FilterSourceSpec::new(server.url("/ads.txt")), FilterSourceSpec::new(server.url("/privacy.txt"))],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let summary = blocker.load_summary().await;
+        assert_eq!(summary.total_rules, 4);
+        assert_eq!(summary.unique_rules, 3);
+        assert_eq!(summary.duplicate_rules, 1);
+        let snapshot = blocker.snapshot().await;
+        assert!(snapshot.lists.iter().flat_map(|list| &list.rules).all(|rule| !rule.starts_with(['[', '!'])), "{:?}", snapshot.lists);
+    }
+    
+    #[tokio::test]
+    async fn load_summary_reports_load_and_compile_durations() {
+        let server = MockServer::start(vec![("/ads.txt", vec![MockResponse::ok(filter_list(&["||ads.example^"]))])]).await;
+        let url = server.url("/ads.txt");
+        let corporate = MemorySource { category: BlockCategory::Custom, rules: vec!["||intranet-ads.example^".to_string()] };
+        let config = AdBlockerConfig {
+            filter_lists: vec![FilterSourceSpec::new(&url)],
+            extra_sources: vec![Arc::new(corporate)],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let summary = blocker.load_summary().await;
+        assert!(!summary.load_duration.is_zero());
+        assert!(!summary.compile_duration.is_zero());
+        assert!(!summary.source_load_durations[&url].is_zero());
+        assert!(summary.source_load_durations.contains_key("Corporate"));
+        assert!(summary.source_load_durations[&url] <= summary.load_duration);
+    }
+    
+    #[tokio::test]
+    async fn hosts_list_domains_block_through_the_unified_api() {
+        let hosts = "0.0.0.0 hosts-only.example\n0.0.0.0 shared.example\n";
+        let server = MockServer::start(vec![("/hosts", vec![MockResponse::ok(hosts)])]).await;
+        let hosts_url = server.url("/hosts");
+        let config = AdBlockerConfig {
+            enable_hosts_lists: vec![hosts_url.clone()],
+            custom_filters: vec!["||shared.example^".to_string()],
+            whitelist_domains: vec!["shared.example".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let result = blocker.should_block("https://cdn.hosts-only.example/ad.js", None).await.unwrap();
+        assert!(result.should_block);
+        assert_eq!(result.reason, BlockReason::HostsList);
+        assert_eq!(result.source_list, Some(hosts_url.clone()));
+        assert!(matches!(result.category, BlockCategory::Advertisement));
+        
+        let domain = blocker.is_domain_blocked("Hosts-Only.Example.").await.unwrap();
+        assert!(domain.should_block);
+        assert_eq!(domain.source_list, Some(hosts_url));
+        // The whitelist still wins over the hosts lists
+        assert!(!blocker.is_domain_blocked("shared.example").await.unwrap().should_block);
+        assert_eq!(blocker.get_stats().await.ads_blocked, 2);
+    }
+    
+    #[tokio::test]
+    async fn failed_hosts_lists_are_reported_in_the_load_summary() {
+        let server = MockServer::start(vec![
+            ("/hosts", vec![MockResponse::ok("0.0.0.0 hosts-only.example\n")]),
+            ("/broken", vec![MockResponse::status(500), MockResponse::status(500)]),
+        ])
+        .await;
+        let config = AdBlockerConfig {
+            enable_hosts_lists: vec![server.url("/hosts"), server.url("/broken")],
+            ..offline_config()
+        };
+        
+        let blocker = AdBlockerAPI::new(config.clone()).await.unwrap();
+        assert!(blocker.is_domain_blocked("hosts-only.example").await.unwrap().should_block);
+        assert_eq!(blocker.load_summary().await.failed_sources, [server.url("/broken")]);
+        
+        let lazy = AdBlockerAPI::new_lazy(config).await.unwrap();
+        assert!(lazy.wait_ready().await);
+        assert_eq!(lazy.load_summary().await.failed_sources, [server.url("/broken")]);
+    }
+    
+    #[tokio::test]
+    async fn hosts_lists_are_downloaded_through_the_configured_proxy() {
+        let server = MockServer::start(vec![("/hosts", vec![MockResponse::ok("0.0.0.0 hosts-only.example\n")])]).await;
+        let config = AdBlockerConfig {
+            enable_hosts_lists: vec![server.url("/hosts")],
+            fetch_proxy: Some(closed_url().await),
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        // The proxy is unreachable, so the list never gets to the server
+        assert!(!blocker.is_domain_blocked("hosts-only.example").await.unwrap().should_block);
+        assert!(server.requests().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn lazy_blockers_load_lists_in_the_background() {
+        let list = filter_list(&["||ads.example^", "||pixel.example^", "news.example##.ad"]);
+        let server = MockServer::start(vec![("/ads.txt", vec![MockResponse::ok(list).delay(Duration::from_millis(500))])]).await;
+        let config = AdBlockerConfig { filter_lists: vec![FilterSourceSpec::new(server.url("/ads.txt"))], ..offline_config() };
+        
+        let started = Instant::now();
+        let blocker = SimpleAdBlocker::with_config_lazy(config).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert!(!blocker.is_ready());
+        // Patterns already apply while the lists load
+        assert!(blocker.is_blocked("https://www.google-analytics.com/collect?v=1").await);
+        assert!(!blocker.is_blocked("https://ads.example/banner.png").await);
+        
+        assert!(blocker.wait_ready().await);
+        assert!(blocker.is_ready());
+        let status = blocker.blocker.status().await;
+        assert!(status.ready);
+        assert_eq!((status.rules_loaded, status.domains_loaded), (3, 2));
+        assert!(blocker.is_blocked("https://ads.example/banner.png").await);
+    }
+    
+    #[tokio::test]
+    async fn lazy_load_failures_are_reported_by_wait_ready() {
+        let config = AdBlockerConfig { filter_lists: vec![FilterSourceSpec::new(closed_url().await)], ..offline_config() };
+        let blocker = AdBlockerAPI::new_lazy(config).await.unwrap();
+        assert!(!blocker.wait_ready().await);
+        assert!(!blocker.is_ready());
+        
+        let eager = AdBlockerAPI::new(offline_config()).await.unwrap();
+        assert!(eager.wait_ready().await);
+    }
+    
+    #[tokio::test]
+    async fn block_page_uses_the_configured_template() {
+        let blocker = AdBlockerAPI::new(offline_config()).await.unwrap();
+        assert!(blocker.block_page_html("ads.example", "Matched ad filter", BlockCategory::Advertisement).contains("ads.example"));
+        
+        let config = AdBlockerConfig {
+            block_page_template: Some("<h1>Acme blocked {domain}</h1>".to_string()),
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        let page = blocker.block_page_html("<b>ads.example</b>", "Matched ad filter", BlockCategory::Advertisement);
+        assert_eq!(page, "<h1>Acme blocked &lt;b&gt;ads.example&lt;/b&gt;</h1>");
+    }
+    
+    #[tokio::test]
+    async fn type_scoped_rules_match_the_inferred_resource_type() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||cdn.example/promo/$image".to_string(), "||cdn.example/widgets/$script".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        assert!(blocker.should_block("https://cdn.example/promo/banner.png?w=300", None).await.unwrap().should_block);
+        assert!(!blocker.should_block("https://cdn.example/promo/offer.html", None).await.unwrap().should_block);
+        assert!(blocker.should_block("https://cdn.example/widgets/chat.js", None).await.unwrap().should_block);
+        assert!(!blocker.should_block("https://cdn.example/widgets/chat.css", None).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn http_requests_match_the_equivalent_url() {
+        let config = AdBlockerConfig {
+            custom_filters: vec![
+                "||ads.example^".to_string(),
+                "/banner/*".to_string(),
+                "||widget.example^$third-party".to_string(),
+            ],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let cases = [
+            (("ads.example", "/", "https", None), ("https://ads.example/", None)),
+            (("cdn.example:8080", "/banner/top.png?size=728", "http", None), ("http://cdn.example:8080/banner/top.png?size=728", None)),
+            (("news.example", "index.html", "https", None), ("https://news.example/index.html", None)),
+            (("widget.example", "/embed.js", "https", Some("news.example")), ("https://widget.example/embed.js", Some("https://news.example/"))),
+            (("widget.example", "/embed.js", "https", Some("widget.example")), ("https://widget.example/embed.js", Some("https://widget.example/"))),
+        ];
+        let verdict = |result: BlockResult| (result.should_block, result.reason, result.category, result.filter_matched, result.is_third_party);
+        for ((host, path, scheme, source_host), (url, source_url)) in cases {
+            let from_parts = blocker.check_http_request(host, path, scheme, source_host).await.unwrap();
+            let from_url = blocker.should_block(url, source_url).await.unwrap();
+            assert_eq!(verdict(from_parts), verdict(from_url), "{}", url);
+        }
+        
+        let invalid = blocker.check_http_request("bad host", "/", "https", None).await.unwrap();
+        assert_eq!(invalid.reason, BlockReason::InvalidUrl);
+    }
+    
+    #[tokio::test]
+    async fn rules_with_unsupported_modifiers_are_reported() {
+        let rules = [
+            "[Adblock Plus 2.0]",
+            "! Title: Imported",
+            "||ads.example^",
+            "||cdn.example/player.js$rewrite=abp-resource:blank-js",
+            "||sponsor.example^$sitekey=abcdsitekeydcba",
+        ];
+        let imported = MemorySource { category: BlockCategory::Advertisement, rules: rules.iter().map(|rule| rule.to_string()).collect() };
+        let config = AdBlockerConfig { extra_sources: vec![Arc::new(imported)], ..offline_config() };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        assert_eq!(blocker.load_summary().await.unsupported_rules, [rules[3], rules[4]]);
+        assert!(blocker.should_block("https://ads.example/banner.png", None).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn lists_become_stale_after_max_age() {
+        let mut blocker = AdBlockerAPI::new(offline_config()).await.unwrap();
+        let max_age = Duration::from_secs(3600);
+        assert!(!blocker.is_stale(max_age).await);
+        
+        let later = SystemTime::now() + Duration::from_secs(3601);
+        blocker.set_clock(Arc::new(FixedSystemClock(later)));
+        assert!(blocker.is_stale(max_age).await);
+        
+        // Adding a rule rebuilds the engine but doesn't refresh the lists
+        blocker.add_custom_filter("||ads.example^".to_string()).await.unwrap();
+        assert!(blocker.is_stale(max_age).await);
+        blocker.set_clock(Arc::new(FixedSystemClock(later - Duration::from_secs(2))));
+        assert!(!blocker.is_stale(max_age).await);
+    }
+    
+    #[tokio::test]
+    async fn status_reports_loaded_rules() {
+        let before = SystemTime::now();
+        let rules = ["||ads.example^", "||pixel.example^", "/banner/*", "news.example##.ad"];
+        let lists = MemorySource { category: BlockCategory::Advertisement, rules: rules.iter().map(|rule| rule.to_string()).collect() };
+        let config = AdBlockerConfig { extra_sources: vec![Arc::new(lists)], ..offline_config() };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let status = blocker.status().await;
+        assert!(status.ready);
+        assert!(blocker.is_ready());
+        assert_eq!((status.rules_loaded, status.domains_loaded), (4, 2));
+        assert!(status.last_reload >= before && status.last_reload <= SystemTime::now());
+    }
+    
+    #[tokio::test]
+    async fn scheduled_categories_follow_the_clock() {
+        use crate::schedule::{BlockSchedule, ScheduleEntry};
+        use chrono::{NaiveTime, Weekday};
+        
+        let school_hours = ScheduleEntry {
+            category: BlockCategory::Social,
+            days: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+            start: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+        };
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string()],
+            schedule: Some(BlockSchedule { entries: vec![school_hours] }),
+            ..offline_config()
+        };
+        let mut blocker = AdBlockerAPI::new(config).await.unwrap();
+        blocker.set_block_social(true);
+        let widget = "https://www.facebook.com/plugins/like.php";
+        
+        // Wednesday
+        blocker.set_clock(Arc::new(FixedClock(september(2, 10, 0))));
+        assert_eq!(blocker.should_block(widget, None).await.unwrap().reason, BlockReason::SocialPattern);
+        blocker.set_clock(Arc::new(FixedClock(september(2, 20, 0))));
+        assert!(!blocker.should_block(widget, None).await.unwrap().should_block);
+        // Other categories aren't scheduled
+        assert!(blocker.should_block("https://ads.example/", None).await.unwrap().should_block);
+        // Saturday
+        blocker.set_clock(Arc::new(FixedClock(september(5, 10, 0))));
+        assert!(!blocker.should_block(widget, None).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn clients_accumulate_independent_counts() {
+        let config = AdBlockerConfig { track_clients: true, block_tracking: false, ..offline_config() };
+        let rules = vec!["||ads.example^".to_string(), "||pixel.example^".to_string()];
+        let blocker = AdBlockerAPI::from_rules(rules, config).await.unwrap();
+        
+        for url in ["https://ads.example/1", "https://ads.example/2", "https://pixel.example/p", "https://news.example/"] {
+            blocker.should_block_for("192.0.2.10", url).await.unwrap();
+        }
+        blocker.should_block_for("192.0.2.20", "https://pixel.example/p").await.unwrap();
+        
+        let first = blocker.client_stats("192.0.2.10").await.unwrap();
+        assert_eq!((first.total_requests, first.blocked_requests), (4, 3));
+        let second = blocker.client_stats("192.0.2.20").await.unwrap();
+        assert_eq!((second.total_requests, second.blocked_requests), (1, 1));
+        assert_eq!(
+            blocker.top_blocked_domains("192.0.2.10", 5).await,
+            [("ads.example".to_string(), 2), ("pixel.example".to_string(), 1)]
+        );
+        assert_eq!(blocker.top_blocked_domains("192.0.2.10", 1).await.len(), 1);
+        assert!(blocker.client_stats("192.0.2.30").await.is_none());
+    }
+    
+    #[tokio::test]
+    async fn clients_are_not_tracked_by_default() {
+        let blocker = AdBlockerAPI::from_rules(vec!["||ads.example^".to_string()], offline_config()).await.unwrap();
+        assert!(blocker.should_block_for("192.0.2.10", "https://ads.example/").await.unwrap().should_block);
+        assert!(blocker.client_stats("192.0.2.10").await.is_none());
+    }
+    
+    #[tokio::test]
+    async fn whitelist_overrides_custom_filters() {
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||partner.example^".to_string()],
+            regex_filters: vec![r"partner\.example/banner".to_string()],
+            whitelist_domains: vec!["partner.example".to_string()],
+            ..offline_config()
+        };
+        let blocker = AdBlockerAPI::new(config).await.unwrap();
+        
+        let result = blocker.should_block("https://partner.example/banner.png", None).await.unwrap();
+        assert!(!result.should_block);
+        assert_eq!(result.category, BlockCategory::Whitelisted);
+        assert_eq!(blocker.get_stats().await.blocked_requests, 0);
+        
+        let result = blocker.is_domain_blocked("cdn.partner.example").await.unwrap();
+        assert_eq!(result.category, BlockCategory::Whitelisted);
+    }
+    
+    #[tokio::test]
+    async fn wildcard_whitelist_entries_can_be_removed() {
+        let mut blocker = AdBlockerAPI::from_rules(vec!["||example.com^".to_string()], offline_config()).await.unwrap();
+        blocker.add_whitelist_domain("*.Example.com".to_string());
+        
+        assert_eq!(blocker.should_block("https://ads.example.com/", None).await.unwrap().category, BlockCategory::Whitelisted);
+        assert_eq!(blocker.is_domain_blocked("cdn.ads.example.com").await.unwrap().category, BlockCategory::Whitelisted);
+        // The wildcard covers subdomains only
+        assert!(blocker.should_block("https://example.com/", None).await.unwrap().should_block);
+        
+        // Neither the bare domain nor a missing entry touch the wildcard
+        blocker.remove_whitelist_domain("example.com");
+        blocker.remove_whitelist_domain("*.other.example");
+        assert!(!blocker.should_block("https://ads.example.com/", None).await.unwrap().should_block);
+        
+        blocker.remove_whitelist_domain("*.example.com");
+        assert!(blocker.should_block("https://ads.example.com/", None).await.unwrap().should_block);
+        assert!(blocker.get_config().whitelist_domains.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn clear_whitelist_removes_every_entry() {
+        let config = AdBlockerConfig {
+            whitelist_domains: vec!["ads.example.com".to_string(), "*.example.com".to_string()],
+            ..offline_config()
+        };
+        let mut blocker = AdBlockerAPI::from_rules(vec!["||example.com^".to_string()], config).await.unwrap();
+        assert!(!blocker.should_block("https://ads.example.com/", None).await.unwrap().should_block);
+        
+        blocker.clear_whitelist();
+        assert!(blocker.should_block("https://ads.example.com/", None).await.unwrap().should_block);
+        assert!(blocker.get_config().whitelist_domains.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn whitelist_overrides_tracking_and_social_patterns() {
+        let config = AdBlockerConfig {
+            block_social: true,
+            whitelist_domains: vec!["www.facebook.com".to_string()],
+            ..offline_config()
+        };
+        let mut blocker = AdBlockerAPI::from_rules(Vec::new(), config).await.unwrap();
+        let url = "https://www.facebook.com/plugins/like.php?href=https://doubleclick.net/";
+        
+        let full = blocker.check_url_full(url).await.unwrap();
+        assert_eq!(full.result.category, BlockCategory::Whitelisted);
+        assert!(full.categories.is_empty());
+        
+        blocker.remove_whitelist_domain("www.facebook.com");
+        assert!(blocker.should_block(url, None).await.unwrap().should_block);
+    }
+    
+    #[tokio::test]
+    async fn each_check_path_reports_its_reason() {
+        let server = MockServer::start(vec![("/hosts", vec![MockResponse::ok("0.0.0.0 hosts-only.example\n")])]).await;
+        let config = AdBlockerConfig {
+            custom_filters: vec!["||ads.example^".to_string()],
+            regex_filters: vec![r"/promo-[0-9]+\.js$".to_string()],
+            whitelist_domains: vec!["partner.example".to_string()],
+            enable_hosts_lists: vec![server.url("/hosts")],
+            ..offline_config()
+        };
+        // Turned on after construction so the social list isn't downloaded
+        let mut blocker = AdBlockerAPI::new(config).await.unwrap();
+        blocker.set_block_social(true);
+        
+        let cases = [
+            ("https://ads.example/banner.png", BlockReason::AdFilter),
+            ("https://www.google-analytics.com/collect?v=1", BlockReason::TrackingPattern),
+            ("https://www.facebook.com/plugins/like.php", BlockReason::SocialPattern),
+            ("https://cdn.example/promo-42.js", BlockReason::CustomFilter),
+            ("https://hosts-only.example/", BlockReason::HostsList),
+            ("https://partner.example/ads.js", BlockReason::Whitelisted),
+            ("https://news.example/", BlockReason::Clean),
+            ("not a url", BlockReason::InvalidUrl),
+        ];
+        for (url, reason) in cases {
+            assert_eq!(blocker.should_block(url, None).await.unwrap().reason, reason, "{}", url);
+        }
+        assert_eq!(blocker.is_domain_blocked("news.example").await.unwrap().reason, BlockReason::Clean);
+        assert_eq!(BlockReason::AdFilter.to_string(), "Matched ad filter");
+    }
+    
+    #[tokio::test]
+    async fn check_url_full_reports_every_matching_category() {
+        let config = AdBlockerConfig { block_social: true, ..offline_config() };
+        let blocker = AdBlockerAPI::from_rules(Vec::new(), config).await.unwrap();
+        
+        let full = blocker.check_url_full("https://www.facebook.com/plugins/like.php?href=https://doubleclick.net/").await.unwrap();
+        assert!(full.result.should_block);
+        assert_eq!(full.result.category, BlockCategory::Tracking);
+        assert_eq!(full.categories, vec![BlockCategory::Tracking, BlockCategory::Social]);
+        assert_eq!(blocker.get_stats().await.blocked_requests, 1);
+        
+        let clean = blocker.check_url_full("https://news.example/").await.unwrap();
+        assert!(clean.categories.is_empty());
+    }
+}
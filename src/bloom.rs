@@ -0,0 +1,110 @@
+/// Bloom filter over domain names, used to rule out definite non-members
+/// before walking the exact domain set.
+///
+/// Hashing is ASCII case-insensitive so queries don't need to be lowercased.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    num_hashes: u32,
+    capacity: usize,
+}
+
+/// Bits per expected element; with 7 hashes this gives roughly a 1% false positive rate
+const BITS_PER_ITEM: usize = 10;
+const NUM_HASHES: u32 = 7;
+
+impl BloomFilter {
+    /// Create a filter sized for `capacity` elements
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        let num_bits = (capacity.max(1) * BITS_PER_ITEM).next_power_of_two();
+        Self {
+            bits: vec![0; num_bits / 64 + 1],
+            num_hashes: NUM_HASHES,
+            capacity,
+        }
+    }
+    
+    /// Number of elements the filter was sized for
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    /// Heap memory used by the bit array
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.bits.capacity() * std::mem::size_of::<u64>()
+    }
+    
+    pub(crate) fn insert(&mut self, item: &str) {
+        if self.bits.is_empty() {
+            return;
+        }
+        let num_bits = self.num_bits();
+        let (h1, h2) = hash_pair(item);
+        for i in 0..self.num_hashes as u64 {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % num_bits;
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+    
+    /// Returns `false` only if the item was definitely never inserted.
+    /// An empty (unsized) filter can't rule anything out.
+    pub(crate) fn might_contain(&self, item: &str) -> bool {
+        if self.bits.is_empty() {
+            return true;
+        }
+        let num_bits = self.num_bits();
+        let (h1, h2) = hash_pair(item);
+        (0..self.num_hashes as u64).all(|i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % num_bits;
+            self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+        })
+    }
+    
+    fn num_bits(&self) -> u64 {
+        (self.bits.len() * 64) as u64
+    }
+}
+
+/// FNV-1a over lowercased bytes with a final mix, split into two hashes for double hashing
+fn hash_pair(item: &str) -> (u64, u64) {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in item.bytes() {
+        hash ^= byte.to_ascii_lowercase() as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    (hash, hash.rotate_left(32) | 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn has_no_false_negatives() {
+        let mut bloom = BloomFilter::with_capacity(10_000);
+        let domains: Vec<String> = (0..10_000).map(|i| format!("ads{}.example.com", i)).collect();
+        for domain in &domains {
+            bloom.insert(domain);
+        }
+        assert!(domains.iter().all(|domain| bloom.might_contain(domain)));
+        assert!(bloom.might_contain("ADS42.Example.COM"));
+    }
+    
+    #[test]
+    fn rules_out_most_non_members() {
+        let mut bloom = BloomFilter::with_capacity(10_000);
+        for i in 0..10_000 {
+            bloom.insert(&format!("ads{}.example.com", i));
+        }
+        let false_positives = (0..10_000).filter(|i| bloom.might_contain(&format!("site{}.example.org", i))).count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+    
+    #[test]
+    fn unsized_filter_rules_nothing_out() {
+        assert!(BloomFilter::default().might_contain("example.com"));
+    }
+}
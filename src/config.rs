@@ -1,17 +1,112 @@
+use crate::dns::BLOCKED_TTL;
+use crate::error::{AdBlockerError, Result};
+use crate::filters::{FilterSource, FilterSourceSpec, TrackingParams};
+use crate::ip_net::IpNet;
+use crate::schedule::BlockSchedule;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Configuration for the ad blocker
+///
+/// Fields missing from a config file fall back to their `Default` values;
+/// unknown fields are rejected.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct AdBlockerConfig {
     pub enable_easylist: bool,
     pub enable_easyprivacy: bool,
     pub enable_malware_protection: bool,
+    pub enable_phishing_protection: bool,
+    pub enable_cryptomining: bool,
     pub custom_filters: Vec<String>,
+    /// Directories whose `.txt` files are read into the custom filters at startup
+    pub custom_filter_dirs: Vec<PathBuf>,
+    /// Regular expressions matched against the full URL, reported as `Custom` blocks
+    pub regex_filters: Vec<String>,
+    /// User-provided filter lists loaded alongside the built-in ones (not serialized)
+    #[serde(skip)]
+    pub extra_sources: Vec<Arc<dyn FilterSource>>,
+    /// More filter lists to download, sent with their own headers (e.g. an
+    /// internal mirror requiring authentication). Matches count as ads and
+    /// report the list's URL as its source.
+    pub filter_lists: Vec<FilterSourceSpec>,
     pub whitelist_domains: Vec<String>,
+    /// URL prefixes (host plus path, e.g. `cdn.example.com/app/`) to never
+    /// block, for hosts that serve both ads and assets that are needed
+    pub whitelist_paths: Vec<String>,
+    /// Regexes blocked as `Tracking` in addition to the built-in tracking patterns
+    pub extra_tracking_patterns: Vec<String>,
+    /// Built-in tracking patterns to leave out, by source (e.g. `segment\.com`)
+    /// or index, for ones causing false positives
+    pub disabled_patterns: Vec<String>,
+    /// Regexes blocked as `Social` in addition to the built-in social patterns
+    pub extra_social_patterns: Vec<String>,
+    /// Hosts files (e.g. `STEVENBLACK_HOSTS_URL`) whose domains are blocked
+    /// ahead of the filter engine. Loaded once at construction; a file that
+    /// fails to download is logged and skipped.
+    pub enable_hosts_lists: Vec<String>,
+    /// Networks (e.g. `203.0.113.0/24`) blocked as trackers when a URL's host
+    /// is a literal IP address in one of them. Domain hosts aren't resolved.
+    pub blocked_ip_ranges: Vec<IpNet>,
+    /// Query parameters removed by `sanitize_url`
+    pub tracking_params: Vec<String>,
+    /// Literal text (e.g. `BodySignatures::default_signatures`) that
+    /// `scan_response_body` looks for in HTML and JavaScript responses.
+    /// Empty turns body scanning off.
+    pub body_signatures: Vec<String>,
     pub block_tracking: bool,
     pub block_social: bool,
     pub aggressive_blocking: bool,
+    /// Restrict categories to certain times of day (always blocked when `None`)
+    pub schedule: Option<BlockSchedule>,
     pub cache_filters: bool,
+    /// Keep going with the lists that did load when a required list (or an
+    /// `extra_sources` or `filter_lists` entry) fails to download, instead of failing
+    /// construction or the reload. Failures are listed in `load_summary`.
+    pub fail_open: bool,
+    /// Timeout for each filter list and hosts list download
+    pub fetch_timeout: Duration,
+    /// How many times a failed download is retried, with exponential backoff
+    pub fetch_retries: u32,
+    /// Proxy for filter list and hosts list downloads, e.g. `http://proxy.example:3128`
+    pub fetch_proxy: Option<String>,
+    /// PEM files with extra root certificates to trust for filter list and
+    /// hosts list downloads, e.g. a TLS-inspecting proxy's CA
+    pub fetch_ca_certs: Vec<PathBuf>,
+    /// Number of recent checks kept in the query log (0 disables logging)
+    pub log_size: usize,
+    /// Keep per-client counts for checks made with `should_block_for`
+    pub track_clients: bool,
+    /// Keywords (e.g. `analytics`) whose appearance in an allowed domain
+    /// records it in `watched_allows`. Empty turns watching off.
+    pub watch_keywords: Vec<String>,
+    /// Most domains kept in `watched_allows`; the oldest are dropped past it
+    pub watch_size: usize,
+    /// HTML for `AdBlockerAPI::block_page_html`, with `{domain}`, `{reason}`
+    /// and `{category}` placeholders (the built-in page when `None`)
+    pub block_page_template: Option<String>,
+    /// Report matches in stats, the query log and `BlockResult::would_block`,
+    /// but never set `should_block`
+    pub dry_run: bool,
+    /// Whether whitelisted domains are allowed even when a rule matches them
+    pub precedence: Precedence,
+    /// Remember up to this many `should_block` verdicts, keyed by URL and
+    /// source URL (0 disables the cache). Ignored when `schedule` is set,
+    /// since verdicts then depend on the time of day.
+    pub result_cache_size: usize,
+    /// TTL, in seconds, of the records in DNS responses built by
+    /// `BlockedResponder::from_config`. 0 asks clients not to cache them.
+    pub block_ttl_seconds: u32,
+    /// Most rules to load from the filter lists, for predictable memory use on
+    /// small machines. Rules past it are dropped (and counted in
+    /// `LoadSummary::dropped_rules`); custom filters always load.
+    pub max_rules: Option<usize>,
+    /// Fraction of checks, from 0.0 to 1.0, counted in `get_stats` and
+    /// `windowed_stats`, whose counts are scaled back up when read. Below 1.0 the counts are
+    /// estimates, but fewer checks contend on the stats lock.
+    pub stats_sample_rate: f64,
 }
 
 impl Default for AdBlockerConfig {
@@ -20,59 +115,221 @@ impl Default for AdBlockerConfig {
             enable_easylist: true,
             enable_easyprivacy: true,
             enable_malware_protection: false, // Disabled by default due to potential network issues
+            enable_phishing_protection: false,
+            enable_cryptomining: false,
             custom_filters: vec![],
+            custom_filter_dirs: vec![],
+            regex_filters: vec![],
+            extra_sources: vec![],
+            filter_lists: vec![],
             whitelist_domains: vec![],
+            whitelist_paths: vec![],
+            extra_tracking_patterns: vec![],
+            disabled_patterns: vec![],
+            extra_social_patterns: vec![],
+            enable_hosts_lists: vec![],
+            blocked_ip_ranges: vec![],
+            tracking_params: TrackingParams::default_params(),
+            body_signatures: vec![],
             block_tracking: true,
             block_social: false,
             aggressive_blocking: false,
+            schedule: None,
             cache_filters: true,
+            fail_open: false,
+            fetch_timeout: Duration::from_secs(30),
+            fetch_retries: 2,
+            fetch_proxy: None,
+            fetch_ca_certs: vec![],
+            log_size: 0,
+            track_clients: false,
+            watch_keywords: vec![],
+            watch_size: 1000,
+            block_page_template: None,
+            dry_run: false,
+            precedence: Precedence::default(),
+            result_cache_size: 0,
+            block_ttl_seconds: BLOCKED_TTL,
+            max_rules: None,
+            stats_sample_rate: 1.0,
         }
     }
 }
 
+/// Which wins when a domain is both whitelisted and matched by a block rule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Precedence {
+    /// Whitelisted domains are never blocked, and rules aren't consulted for them
+    #[default]
+    WhitelistWins,
+    /// Rules are consulted for every domain; the whitelist only changes the
+    /// verdict's category from `Clean` to `Whitelisted` when none match
+    BlocklistWins,
+}
+
 impl AdBlockerConfig {
     /// Create a minimal configuration for basic ad blocking
     pub fn minimal() -> Self {
         Self {
-            enable_easylist: true,
             enable_easyprivacy: false,
-            enable_malware_protection: false,
-            custom_filters: vec![],
-            whitelist_domains: vec![],
             block_tracking: false,
-            block_social: false,
-            aggressive_blocking: false,
-            cache_filters: true,
+            ..Self::default()
         }
     }
     
     /// Create a privacy-focused configuration
     pub fn privacy_focused() -> Self {
         Self {
-            enable_easylist: true,
-            enable_easyprivacy: true,
             enable_malware_protection: true,
-            custom_filters: vec![],
-            whitelist_domains: vec![],
-            block_tracking: true,
+            enable_phishing_protection: true,
+            enable_cryptomining: true,
             block_social: true,
             aggressive_blocking: true,
-            cache_filters: true,
+            ..Self::default()
         }
     }
     
     /// Create a performance-focused configuration (less blocking, faster)
     pub fn performance_focused() -> Self {
         Self {
-            enable_easylist: true,
             enable_easyprivacy: false,
-            enable_malware_protection: false,
-            custom_filters: vec![],
-            whitelist_domains: vec![],
             block_tracking: false,
-            block_social: false,
-            aggressive_blocking: false,
-            cache_filters: true,
+            ..Self::default()
+        }
+    }
+    
+    /// Load a configuration file, as TOML or JSON depending on whether its
+    /// extension is `.toml` or `.json`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path)?;
+        let content = std::fs::read_to_string(path)?;
+        let config: Self = match format {
+            ConfigFormat::Toml => toml::from_str(&content)?,
+            ConfigFormat::Json => serde_json::from_str(&content)?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+    
+    /// Check values that can't be rejected by their type alone. Run by
+    /// `from_file` and when a blocker is constructed.
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.stats_sample_rate) {
+            return Err(AdBlockerError::Config(format!(
+                "stats_sample_rate must be between 0.0 and 1.0, got {}",
+                self.stats_sample_rate
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Write the configuration to a file in the format given by its extension
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = match ConfigFormat::from_path(path)? {
+            ConfigFormat::Toml => toml::to_string_pretty(self)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(self)?,
+        };
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// Config file formats, chosen by file extension
+enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Ok(ConfigFormat::Toml),
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(ConfigFormat::Json),
+            Some(ext) => Err(AdBlockerError::Config(format!(
+                "unsupported config file format: .{} (expected .toml or .json)",
+                ext
+            ))),
+            None => Err(AdBlockerError::Config(format!("config file has no extension: {}", path.display()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+    
+    fn customized() -> AdBlockerConfig {
+        AdBlockerConfig {
+            enable_easyprivacy: false,
+            custom_filters: vec!["||ads.example.com^".to_string()],
+            whitelist_domains: vec!["example.org".to_string()],
+            fetch_timeout: Duration::from_millis(2500),
+            fetch_retries: 5,
+            ..AdBlockerConfig::default()
         }
     }
-}
\ No newline at end of file
+    
+    fn round_trip(file_name: &str) {
+        let path = temp_path(file_name);
+        let config = customized();
+        
+        config.to_file(&path).unwrap();
+        let loaded = AdBlockerConfig::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        
+        let loaded = loaded.unwrap();
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&config).unwrap());
+    }
+    
+    #[test]
+    fn toml_files_round_trip() {
+        round_trip("config.toml");
+    }
+    
+    #[test]
+    fn json_files_round_trip() {
+        round_trip("config.json");
+    }
+    
+    #[test]
+    fn missing_fields_take_defaults() {
+        let path = temp_path("partial.toml");
+        std::fs::write(&path, "enable_easylist = false\nfetch_retries = 5\n").unwrap();
+        let config = AdBlockerConfig::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        
+        let config = config.unwrap();
+        assert!(!config.enable_easylist);
+        assert_eq!(config.fetch_retries, 5);
+        assert!(config.enable_easyprivacy);
+        assert_eq!(config.fetch_timeout, Duration::from_secs(30));
+    }
+    
+    #[test]
+    fn unknown_fields_and_extensions_are_config_errors() {
+        let path = temp_path("typo.json");
+        std::fs::write(&path, r#"{"enable_easylsit": false}"#).unwrap();
+        let err = AdBlockerConfig::from_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(&err, AdBlockerError::Config(message) if message.contains("enable_easylsit")), "{:?}", err);
+        
+        let err = AdBlockerConfig::default().to_file(temp_path("config.yaml")).unwrap_err();
+        assert!(matches!(err, AdBlockerError::Config(_)), "{:?}", err);
+    }
+    
+    #[test]
+    fn out_of_range_sample_rates_are_config_errors() {
+        let path = temp_path("sampled.toml");
+        std::fs::write(&path, "stats_sample_rate = 2.0\n").unwrap();
+        let err = AdBlockerConfig::from_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(&err, AdBlockerError::Config(message) if message.contains("stats_sample_rate")), "{:?}", err);
+        
+        let nan = AdBlockerConfig { stats_sample_rate: f64::NAN, ..AdBlockerConfig::default() };
+        assert!(nan.validate().is_err());
+        assert!(AdBlockerConfig::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,452 @@
+use crate::config::AdBlockerConfig;
+use crate::error::Result;
+use hickory_proto::op::{Message, MessageType, ResponseCode};
+use hickory_proto::rr::rdata::{A, AAAA};
+use hickory_proto::rr::{RData, Record, RecordType};
+use std::collections::HashSet;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// Size of the fixed DNS header
+const HEADER_LEN: usize = 12;
+/// Longest name allowed on the wire (RFC 1035 §2.3.4)
+const MAX_NAME_LEN: usize = 255;
+
+/// Largest UDP message accepted from clients without EDNS0 (RFC 1035)
+pub const MIN_UDP_PAYLOAD: usize = 512;
+/// Largest UDP message the helpers send or receive. EDNS0 clients advertise
+/// up to this much in their OPT record.
+pub const MAX_UDP_PAYLOAD: usize = 4096;
+
+/// TTL of the sinkhole records in blocked responses, in seconds
+pub(crate) const BLOCKED_TTL: u32 = 60;
+
+/// Name in the first question of a raw DNS query, e.g. `"ads.example.com"`.
+///
+/// Compressed names are followed through their pointers, so a question
+/// whose name ends in a pointer into another part of the packet is read in
+/// full. Returns `None` for truncated or malformed packets, pointer loops
+/// and queries for the root name.
+pub fn parse_query_name(packet: &[u8]) -> Option<String> {
+    let question_count = u16::from_be_bytes([*packet.get(4)?, *packet.get(5)?]);
+    if question_count == 0 {
+        return None;
+    }
+    let (name, _) = read_name(packet, HEADER_LEN)?;
+    (!name.is_empty()).then_some(name)
+}
+
+/// Largest response the sender of `query` accepts over UDP: the payload
+/// size advertised in its EDNS0 OPT record, or 512 bytes without one.
+/// Clamped to `MIN_UDP_PAYLOAD..=MAX_UDP_PAYLOAD`.
+pub fn client_payload_size(query: &[u8]) -> usize {
+    Message::from_vec(query)
+        .ok()
+        .and_then(|message| message.extensions().as_ref().map(|edns| usize::from(edns.max_payload())))
+        .unwrap_or(MIN_UDP_PAYLOAD)
+        .clamp(MIN_UDP_PAYLOAD, MAX_UDP_PAYLOAD)
+}
+
+/// Make `response` fit in what the sender of `query` accepts over UDP.
+/// Responses that are too large are cut down to their header, question and
+/// OPT record with the TC flag set, telling the client to retry over TCP.
+pub fn fit_response(query: &[u8], response: Vec<u8>) -> Vec<u8> {
+    if response.len() <= client_payload_size(query) {
+        return response;
+    }
+    
+    let Ok(mut message) = Message::from_vec(&response) else {
+        return response;
+    };
+    message.take_answers();
+    message.take_name_servers();
+    message.take_additionals();
+    message.set_truncated(true);
+    message.to_vec().unwrap_or(response)
+}
+
+/// Response to `query` for a blocked name, as built by the default
+/// `BlockedResponder`: `0.0.0.0` for A queries, `::` for AAAA queries and
+/// an empty answer for anything else
+pub fn blocked_response(query: &[u8]) -> Option<Vec<u8>> {
+    BlockedResponder::new().respond(query)
+}
+
+/// How queries for blocked names are answered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlockingMode {
+    /// Answer A queries with `0.0.0.0` and AAAA queries with `::`, and
+    /// other types with NODATA (no error, no records)
+    #[default]
+    NullAddress,
+    /// Answer every query with NXDOMAIN
+    NxDomain,
+}
+
+/// Builds responses to queries for blocked names
+#[derive(Debug, Clone, Copy)]
+pub struct BlockedResponder {
+    mode: BlockingMode,
+    ttl: u32,
+}
+
+impl Default for BlockedResponder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockedResponder {
+    pub fn new() -> Self {
+        Self {
+            mode: BlockingMode::default(),
+            ttl: BLOCKED_TTL,
+        }
+    }
+    
+    /// A responder using `config.block_ttl_seconds` for its records
+    pub fn from_config(config: &AdBlockerConfig) -> Self {
+        Self::new().with_ttl(config.block_ttl_seconds)
+    }
+    
+    pub fn with_mode(mut self, mode: BlockingMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    
+    /// TTL of the records in null-address answers, in seconds. 0 asks
+    /// clients not to cache them.
+    pub fn with_ttl(mut self, ttl: u32) -> Self {
+        self.ttl = ttl;
+        self
+    }
+    
+    /// TTL given to the records in null-address answers, in seconds
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+    
+    /// Response to `query`, marked authoritative so clients don't retry
+    /// elsewhere. The query's OPT record, if any, is kept so EDNS0 clients
+    /// get one back. Returns `None` if the query can't be parsed or has no
+    /// question.
+    pub fn respond(&self, query: &[u8]) -> Option<Vec<u8>> {
+        let mut message = Message::from_vec(query).ok()?;
+        let question = message.queries().first()?.clone();
+        
+        message
+            .set_message_type(MessageType::Response)
+            .set_authoritative(true)
+            .set_recursion_available(true)
+            .set_response_code(ResponseCode::NoError);
+        
+        let name = question.name().clone();
+        match (self.mode, question.query_type()) {
+            (BlockingMode::NxDomain, _) => {
+                message.set_response_code(ResponseCode::NXDomain);
+            }
+            (BlockingMode::NullAddress, RecordType::A) => {
+                message.add_answer(Record::from_rdata(name, self.ttl, RData::A(A(Ipv4Addr::UNSPECIFIED))));
+            }
+            (BlockingMode::NullAddress, RecordType::AAAA) => {
+                message.add_answer(Record::from_rdata(name, self.ttl, RData::AAAA(AAAA(Ipv6Addr::UNSPECIFIED))));
+            }
+            // NODATA: the name exists, but has no records of this type
+            (BlockingMode::NullAddress, _) => {}
+        }
+        message.to_vec().ok()
+    }
+}
+
+/// Forwards raw DNS queries to an upstream resolver over UDP.
+///
+/// Responses of up to `buffer_size` bytes (4096 by default) are relayed
+/// whole, so large EDNS0 and DNSSEC answers aren't cut off at 512 bytes.
+#[derive(Debug, Clone)]
+pub struct DnsForwarder {
+    upstream: SocketAddr,
+    buffer_size: usize,
+    timeout: Duration,
+}
+
+impl DnsForwarder {
+    pub fn new(upstream: SocketAddr) -> Self {
+        Self {
+            upstream,
+            buffer_size: MAX_UDP_PAYLOAD,
+            timeout: Duration::from_secs(5),
+        }
+    }
+    
+    /// Accept upstream responses of up to `buffer_size` bytes, clamped to
+    /// `MIN_UDP_PAYLOAD..=MAX_UDP_PAYLOAD`
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size.clamp(MIN_UDP_PAYLOAD, MAX_UDP_PAYLOAD);
+        self
+    }
+    
+    /// Give up on the upstream after `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    
+    /// Send `query` upstream and return its response
+    pub async fn forward(&self, query: &[u8]) -> Result<Vec<u8>> {
+        let local: SocketAddr = match self.upstream {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(self.upstream).await?;
+        socket.send(query).await?;
+        
+        let mut buffer = vec![0; self.buffer_size];
+        let size = tokio::time::timeout(self.timeout, socket.recv(&mut buffer))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "upstream DNS server did not respond"))??;
+        buffer.truncate(size);
+        Ok(buffer)
+    }
+}
+
+/// Read the name starting at `start`, returning it along with the offset
+/// just past it (past the first pointer, if the name is compressed)
+fn read_name(packet: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut name_len = 0;
+    let mut pos = start;
+    let mut end = None;
+    let mut visited = HashSet::new();
+    
+    loop {
+        let len = *packet.get(pos)? as usize;
+        match len & 0xC0 {
+            0x00 if len == 0 => {
+                end.get_or_insert(pos + 1);
+                break;
+            }
+            0x00 => {
+                let label = packet.get(pos + 1..pos + 1 + len)?;
+                name_len += len + 1;
+                if name_len > MAX_NAME_LEN {
+                    return None;
+                }
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += len + 1;
+            }
+            0xC0 => {
+                let target = ((len & 0x3F) << 8) | *packet.get(pos + 1)? as usize;
+                end.get_or_insert(pos + 2);
+                // A pointer seen before means the name never ends
+                if !visited.insert(target) {
+                    return None;
+                }
+                pos = target;
+            }
+            // 0x40 and 0x80 label types are reserved
+            _ => return None,
+        }
+    }
+    
+    Some((labels.join("."), end?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::op::{Edns, Query};
+    use hickory_proto::rr::rdata::TXT;
+    use hickory_proto::rr::Name;
+    
+    /// Header for a query with one question
+    const QUERY_HEADER: [u8; 12] = [0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+    
+    fn query(name: &[u8]) -> Vec<u8> {
+        let mut packet = QUERY_HEADER.to_vec();
+        packet.extend_from_slice(name);
+        packet.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        packet
+    }
+    
+    /// Query for `name` with an OPT record advertising `payload` bytes
+    fn edns_query(name: &str, payload: u16) -> Vec<u8> {
+        let mut message = Message::new();
+        message.set_id(7).set_recursion_desired(true);
+        message.add_query(Query::query(Name::from_ascii(name).unwrap(), RecordType::A));
+        let mut edns = Edns::new();
+        edns.set_max_payload(payload);
+        message.set_edns(edns);
+        message.to_vec().unwrap()
+    }
+    
+    /// Response to `query` with enough TXT records to be `size` bytes long
+    fn large_response(query: &[u8], size: usize) -> Vec<u8> {
+        let mut message = Message::from_vec(query).unwrap();
+        message.set_message_type(MessageType::Response);
+        let name = message.queries()[0].name().clone();
+        let base = message.to_vec().unwrap().len();
+        // Each record is a 2 byte name pointer, 10 bytes of type, class,
+        // TTL and length, then the string's length byte and contents
+        let mut remaining = size - base;
+        while remaining > 0 {
+            let text = "x".repeat(remaining.min(200) - 13);
+            remaining -= text.len() + 13;
+            message.add_answer(Record::from_rdata(name.clone(), 60, RData::TXT(TXT::new(vec![text]))));
+        }
+        let response = message.to_vec().unwrap();
+        assert_eq!(response.len(), size);
+        response
+    }
+    
+    #[tokio::test]
+    async fn large_upstream_responses_are_relayed_whole() {
+        let upstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let forwarder = DnsForwarder::new(upstream.local_addr().unwrap());
+        let query = edns_query("big.example.", 4096);
+        
+        let server = tokio::spawn(async move {
+            let mut buffer = [0; MAX_UDP_PAYLOAD];
+            let (size, client) = upstream.recv_from(&mut buffer).await.unwrap();
+            let response = large_response(&buffer[..size], 1500);
+            upstream.send_to(&response, client).await.unwrap();
+            response
+        });
+        
+        let relayed = forwarder.forward(&query).await.unwrap();
+        let sent = server.await.unwrap();
+        assert_eq!(relayed.len(), 1500);
+        assert_eq!(relayed, sent);
+        assert_eq!(fit_response(&query, relayed.clone()), relayed);
+    }
+    
+    #[test]
+    fn responses_are_truncated_to_the_advertised_size() {
+        let query = edns_query("big.example.", 1232);
+        assert_eq!(client_payload_size(&query), 1232);
+        assert_eq!(client_payload_size(&edns_query("big.example.", 65000)), MAX_UDP_PAYLOAD);
+        
+        let plain = query_for("big.example.");
+        assert_eq!(client_payload_size(&plain), MIN_UDP_PAYLOAD);
+        
+        let fitted = Message::from_vec(&fit_response(&query, large_response(&query, 1500))).unwrap();
+        assert!(fitted.truncated());
+        assert!(fitted.answers().is_empty());
+        assert_eq!(fitted.queries().len(), 1);
+        
+        let small = large_response(&plain, 500);
+        assert_eq!(fit_response(&plain, small.clone()), small);
+    }
+    
+    #[test]
+    fn blocked_responses_keep_the_opt_record() {
+        let query = edns_query("ads.example.", 1232);
+        let response = Message::from_vec(&blocked_response(&query).unwrap()).unwrap();
+        
+        assert_eq!(response.id(), 7);
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert_eq!(response.answers()[0].data(), Some(&RData::A(A(Ipv4Addr::UNSPECIFIED))));
+        assert_eq!(response.extensions().as_ref().map(Edns::max_payload), Some(1232));
+        
+        let plain = Message::from_vec(&blocked_response(&query_for("ads.example.")).unwrap()).unwrap();
+        assert!(plain.extensions().is_none());
+    }
+    
+    fn query_for(name: &str) -> Vec<u8> {
+        typed_query(name, RecordType::A)
+    }
+    
+    fn typed_query(name: &str, record_type: RecordType) -> Vec<u8> {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::from_ascii(name).unwrap(), record_type));
+        message.to_vec().unwrap()
+    }
+    
+    #[test]
+    fn aaaa_queries_get_the_unspecified_ipv6_address() {
+        let response = Message::from_vec(&blocked_response(&typed_query("ads.example.", RecordType::AAAA)).unwrap()).unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].record_type(), RecordType::AAAA);
+        assert_eq!(response.answers()[0].data(), Some(&RData::AAAA(AAAA(Ipv6Addr::UNSPECIFIED))));
+    }
+    
+    #[test]
+    fn other_query_types_get_nodata() {
+        let response = Message::from_vec(&blocked_response(&typed_query("ads.example.", RecordType::MX)).unwrap()).unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert!(response.authoritative());
+        assert!(response.answers().is_empty());
+    }
+    
+    #[test]
+    fn answers_use_the_configured_ttl() {
+        for ttl in [300, 0] {
+            let config = AdBlockerConfig { block_ttl_seconds: ttl, ..AdBlockerConfig::default() };
+            let responder = BlockedResponder::from_config(&config);
+            assert_eq!(responder.ttl(), ttl);
+            for record_type in [RecordType::A, RecordType::AAAA] {
+                let response = Message::from_vec(&responder.respond(&typed_query("ads.example.", record_type)).unwrap()).unwrap();
+                assert_eq!(response.answers()[0].ttl(), ttl);
+            }
+        }
+        assert_eq!(BlockedResponder::new().ttl(), BLOCKED_TTL);
+    }
+    
+    #[test]
+    fn nxdomain_mode_answers_every_type_with_nxdomain() {
+        let responder = BlockedResponder::new().with_mode(BlockingMode::NxDomain);
+        for record_type in [RecordType::A, RecordType::AAAA, RecordType::TXT] {
+            let response = Message::from_vec(&responder.respond(&typed_query("ads.example.", record_type)).unwrap()).unwrap();
+            assert_eq!(response.response_code(), ResponseCode::NXDomain);
+            assert!(response.answers().is_empty());
+        }
+    }
+    
+    #[test]
+    fn reads_uncompressed_names() {
+        let packet = query(b"\x03ads\x07example\x03com\x00");
+        assert_eq!(parse_query_name(&packet).as_deref(), Some("ads.example.com"));
+        assert_eq!(read_name(&packet, HEADER_LEN).unwrap().1, packet.len() - 4);
+    }
+    
+    #[test]
+    fn follows_compression_pointers() {
+        // "ads" followed by a pointer to "example.com" after the question
+        let mut packet = query(b"\x03ads\xc0\x16");
+        assert_eq!(packet.len(), 0x16);
+        packet.extend_from_slice(b"\x07example\x03com\x00");
+        
+        assert_eq!(parse_query_name(&packet).as_deref(), Some("ads.example.com"));
+        // The question itself ends right after the pointer
+        assert_eq!(read_name(&packet, HEADER_LEN).unwrap().1, 18);
+    }
+    
+    #[test]
+    fn rejects_pointer_loops() {
+        // "ads" then a pointer back to its own label
+        let packet = query(b"\x03ads\xc0\x0c");
+        assert_eq!(parse_query_name(&packet), None);
+        
+        let packet = query(b"\xc0\x0c");
+        assert_eq!(parse_query_name(&packet), None);
+    }
+    
+    #[test]
+    fn rejects_malformed_packets() {
+        assert_eq!(parse_query_name(&QUERY_HEADER[..8]), None);
+        // Label running past the end of the packet
+        assert_eq!(parse_query_name(&[&QUERY_HEADER[..], &b"\x09ads"[..]].concat()), None);
+        // Pointer past the end of the packet
+        assert_eq!(parse_query_name(&query(b"\x03ads\xc0\xff")), None);
+        // Reserved label type
+        assert_eq!(parse_query_name(&query(b"\x43ads\x00")), None);
+        // No questions
+        let mut packet = query(b"\x03ads\x00");
+        packet[5] = 0;
+        assert_eq!(parse_query_name(&packet), None);
+    }
+}
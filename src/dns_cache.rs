@@ -0,0 +1,316 @@
+use hickory_proto::op::{Message, ResponseCode};
+use hickory_proto::rr::{RData, Record, RecordType};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Cache of upstream DNS responses for a forwarding server.
+///
+/// Responses are keyed by the question's (name, type) and kept for the
+/// smallest TTL among their records. Cached answers are served with their
+/// TTLs reduced by the time spent in the cache and the ID of the new query.
+///
+/// NXDOMAIN and empty NOERROR answers are cached too, for the SOA's negative
+/// TTL (or the cap when there is no SOA), but never longer than the cap.
+///
+/// The cache holds at most `max_entries` responses. When it is full, expired
+/// entries are swept first and then the one closest to expiry is evicted.
+#[derive(Debug)]
+pub struct DnsCache {
+    negative_ttl_cap: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<(String, RecordType), CachedResponse>>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    message: Message,
+    stored_at: Instant,
+    ttl: Duration,
+}
+
+/// Negative answers are cached for at most this long unless overridden
+const DEFAULT_NEGATIVE_TTL_CAP: Duration = Duration::from_secs(300);
+
+/// Responses kept before the cache starts evicting, unless overridden
+const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+impl Default for DnsCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DnsCache {
+    pub fn new() -> Self {
+        Self {
+            negative_ttl_cap: DEFAULT_NEGATIVE_TTL_CAP,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    /// Cache NXDOMAIN and empty answers for no longer than `cap`
+    pub fn with_negative_ttl_cap(mut self, cap: Duration) -> Self {
+        self.negative_ttl_cap = cap;
+        self
+    }
+    
+    /// Keep at most `max` responses (at least one)
+    pub fn with_max_entries(mut self, max: usize) -> Self {
+        self.max_entries = max.max(1);
+        self
+    }
+    
+    /// Cached response to a raw DNS query, if there is an unexpired one
+    pub fn lookup(&self, query: &[u8]) -> Option<Vec<u8>> {
+        self.lookup_at(query, Instant::now())
+    }
+    
+    /// Cache a raw upstream response under its question. Responses that
+    /// can't be parsed, have no question, failed (e.g. SERVFAIL) or have a
+    /// zero TTL are ignored.
+    pub fn store(&self, response: &[u8]) {
+        self.store_at(response, Instant::now())
+    }
+    
+    /// Answer `query` from the cache, or call `upstream` and cache what it returns
+    pub async fn resolve<F, Fut, E>(&self, query: &[u8], upstream: F) -> Result<Vec<u8>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, E>>,
+    {
+        if let Some(cached) = self.lookup(query) {
+            return Ok(cached);
+        }
+        let response = upstream().await?;
+        self.store(&response);
+        Ok(response)
+    }
+    
+    /// Number of cached responses, including any that have expired but not
+    /// yet been looked up again
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    fn lookup_at(&self, query: &[u8], now: Instant) -> Option<Vec<u8>> {
+        let query = Message::from_vec(query).ok()?;
+        let key = cache_key(&query)?;
+        
+        let mut entries = self.entries.lock().unwrap();
+        let cached = entries.get(&key)?;
+        let elapsed = now.saturating_duration_since(cached.stored_at);
+        if elapsed >= cached.ttl {
+            entries.remove(&key);
+            return None;
+        }
+        
+        let mut message = cached.message.clone();
+        drop(entries);
+        
+        let elapsed = elapsed.as_secs() as u32;
+        message.set_id(query.id());
+        age_records(message.answers_mut(), elapsed);
+        age_records(message.name_servers_mut(), elapsed);
+        age_records(message.additionals_mut(), elapsed);
+        message.to_vec().ok()
+    }
+    
+    fn store_at(&self, response: &[u8], now: Instant) {
+        let Ok(message) = Message::from_vec(response) else {
+            return;
+        };
+        let Some(key) = cache_key(&message) else {
+            return;
+        };
+        let Some(ttl) = self.cache_ttl(&message) else {
+            return;
+        };
+        
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(&key) && entries.len() >= self.max_entries {
+            entries.retain(|_, cached| now.saturating_duration_since(cached.stored_at) < cached.ttl);
+            if entries.len() >= self.max_entries {
+                let soonest = entries
+                    .iter()
+                    .min_by_key(|(_, cached)| cached.stored_at + cached.ttl)
+                    .map(|(key, _)| key.clone());
+                if let Some(soonest) = soonest {
+                    entries.remove(&soonest);
+                }
+            }
+        }
+        entries.insert(key, CachedResponse {
+            message,
+            stored_at: now,
+            ttl,
+        });
+    }
+    
+    /// How long a response may be cached, or `None` if it shouldn't be
+    fn cache_ttl(&self, message: &Message) -> Option<Duration> {
+        let negative = match message.response_code() {
+            ResponseCode::NoError => message.answers().is_empty(),
+            ResponseCode::NXDomain => true,
+            _ => return None,
+        };
+        
+        let ttl = if negative {
+            // RFC 2308: the negative TTL is the lesser of the SOA record's
+            // own TTL and its MINIMUM field
+            let soa_ttl = message.name_servers().iter().find_map(|record| match record.data() {
+                Some(RData::SOA(soa)) => Some(Duration::from_secs(record.ttl().min(soa.minimum()).into())),
+                _ => None,
+            });
+            soa_ttl.unwrap_or(self.negative_ttl_cap).min(self.negative_ttl_cap)
+        } else {
+            let min_ttl = message
+                .answers()
+                .iter()
+                .chain(message.name_servers())
+                .chain(message.additionals())
+                .map(|record| record.ttl())
+                .min()?;
+            Duration::from_secs(min_ttl.into())
+        };
+        
+        (!ttl.is_zero()).then_some(ttl)
+    }
+}
+
+fn age_records(records: &mut [Record], elapsed: u32) {
+    for record in records {
+        let ttl = record.ttl().saturating_sub(elapsed);
+        record.set_ttl(ttl);
+    }
+}
+
+fn cache_key(message: &Message) -> Option<(String, RecordType)> {
+    let question = message.queries().first()?;
+    Some((question.name().to_lowercase().to_string(), question.query_type()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::op::{MessageType, Query};
+    use hickory_proto::rr::rdata::{A, SOA};
+    use hickory_proto::rr::Name;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    
+    fn query(id: u16, name: &str) -> Vec<u8> {
+        let mut message = Message::new();
+        message.set_id(id);
+        message.add_query(Query::query(Name::from_ascii(name).unwrap(), RecordType::A));
+        message.to_vec().unwrap()
+    }
+    
+    fn response_to(query: &[u8], code: ResponseCode, records: Vec<Record>, authority: Vec<Record>) -> Vec<u8> {
+        let mut message = Message::from_vec(query).unwrap();
+        message.set_message_type(MessageType::Response);
+        message.set_response_code(code);
+        message.insert_answers(records);
+        message.insert_name_servers(authority);
+        message.to_vec().unwrap()
+    }
+    
+    fn a_record(name: &str, ttl: u32) -> Record {
+        Record::from_rdata(Name::from_ascii(name).unwrap(), ttl, RData::A(A::new(192, 0, 2, 1)))
+    }
+    
+    fn soa_record(ttl: u32, minimum: u32) -> Record {
+        let soa = SOA::new(
+            Name::from_ascii("ns.example.").unwrap(),
+            Name::from_ascii("hostmaster.example.").unwrap(),
+            1, 3600, 600, 86400, minimum,
+        );
+        Record::from_rdata(Name::from_ascii("example.").unwrap(), ttl, RData::SOA(soa))
+    }
+    
+    #[tokio::test]
+    async fn repeated_query_is_served_from_cache() {
+        let cache = DnsCache::new();
+        let upstream_calls = AtomicUsize::new(0);
+        let upstream = |query: Vec<u8>| {
+            upstream_calls.fetch_add(1, Ordering::SeqCst);
+            async move { Ok::<_, ()>(response_to(&query, ResponseCode::NoError, vec![a_record("news.example.", 300)], vec![])) }
+        };
+        
+        let first = query(1, "news.example.");
+        cache.resolve(&first, || upstream(first.clone())).await.unwrap();
+        
+        let second = query(2, "NEWS.example.");
+        let answer = cache.resolve(&second, || upstream(second.clone())).await.unwrap();
+        
+        assert_eq!(upstream_calls.load(Ordering::SeqCst), 1);
+        let answer = Message::from_vec(&answer).unwrap();
+        assert_eq!(answer.id(), 2);
+        assert_eq!(answer.answers().len(), 1);
+    }
+    
+    #[test]
+    fn ttls_count_down_until_expiry() {
+        let cache = DnsCache::new();
+        let start = Instant::now();
+        let request = query(7, "news.example.");
+        let records = vec![a_record("news.example.", 300), a_record("news.example.", 60)];
+        cache.store_at(&response_to(&request, ResponseCode::NoError, records, vec![]), start);
+        
+        let answer = cache.lookup_at(&request, start + Duration::from_secs(20)).unwrap();
+        let ttls: Vec<u32> = Message::from_vec(&answer).unwrap().answers().iter().map(|r| r.ttl()).collect();
+        assert_eq!(ttls, vec![280, 40]);
+        
+        assert!(cache.lookup_at(&request, start + Duration::from_secs(60)).is_none());
+        assert!(cache.is_empty());
+    }
+    
+    #[test]
+    fn nxdomain_is_cached_with_capped_ttl() {
+        let cache = DnsCache::new().with_negative_ttl_cap(Duration::from_secs(30));
+        let start = Instant::now();
+        let request = query(3, "missing.example.");
+        let response = response_to(&request, ResponseCode::NXDomain, vec![], vec![soa_record(3600, 900)]);
+        cache.store_at(&response, start);
+        
+        let answer = cache.lookup_at(&request, start + Duration::from_secs(29)).unwrap();
+        assert_eq!(Message::from_vec(&answer).unwrap().response_code(), ResponseCode::NXDomain);
+        assert!(cache.lookup_at(&request, start + Duration::from_secs(30)).is_none());
+    }
+    
+    #[test]
+    fn full_cache_sweeps_expired_entries_then_evicts_the_soonest_to_expire() {
+        let cache = DnsCache::new().with_max_entries(2);
+        let start = Instant::now();
+        let stale = query(1, "stale.example.");
+        let short = query(2, "short.example.");
+        let long = query(3, "long.example.");
+        let fresh = query(4, "fresh.example.");
+        cache.store_at(&response_to(&stale, ResponseCode::NoError, vec![a_record("stale.example.", 10)], vec![]), start);
+        cache.store_at(&response_to(&long, ResponseCode::NoError, vec![a_record("long.example.", 600)], vec![]), start);
+        
+        let later = start + Duration::from_secs(20);
+        cache.store_at(&response_to(&short, ResponseCode::NoError, vec![a_record("short.example.", 60)], vec![]), later);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.lookup_at(&long, later).is_some());
+        
+        cache.store_at(&response_to(&fresh, ResponseCode::NoError, vec![a_record("fresh.example.", 300)], vec![]), later);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.lookup_at(&short, later).is_none());
+        assert!(cache.lookup_at(&long, later).is_some());
+        assert!(cache.lookup_at(&fresh, later).is_some());
+    }
+    
+    #[test]
+    fn failures_are_not_cached() {
+        let cache = DnsCache::new();
+        let request = query(4, "news.example.");
+        cache.store(&response_to(&request, ResponseCode::ServFail, vec![], vec![]));
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+use std::borrow::Cow;
+
+/// Convert a domain to its ASCII (punycode) form so that Unicode and `xn--`
+/// spellings of the same name compare equal. ASCII input is returned as-is
+/// without allocating; names that fail IDNA processing are left unchanged.
+pub(crate) fn to_ascii_domain(domain: &str) -> Cow<'_, str> {
+    if domain.is_ascii() {
+        return Cow::Borrowed(domain);
+    }
+    
+    match idna::domain_to_ascii(domain) {
+        Ok(ascii) => Cow::Owned(ascii),
+        Err(_) => Cow::Borrowed(domain),
+    }
+}
+
+/// Normalize a domain the way the blocklists and whitelists store it:
+/// lowercase ASCII (punycode), without the trailing dot of a fully
+/// qualified DNS name, so `Doubleclick.NET.` compares equal to
+/// `doubleclick.net`. Already-normalized input is returned without allocating.
+pub(crate) fn normalize_domain(domain: &str) -> Cow<'_, str> {
+    match to_ascii_domain(domain.trim_end_matches('.')) {
+        Cow::Borrowed(domain) if domain.bytes().any(|b| b.is_ascii_uppercase()) => Cow::Owned(domain.to_ascii_lowercase()),
+        Cow::Owned(mut domain) => {
+            domain.make_ascii_lowercase();
+            Cow::Owned(domain)
+        }
+        borrowed => borrowed,
+    }
+}
+
+/// Iterate over a domain and each of its parents, stopping before the bare
+/// top-level label (`a.b.example.com`, `b.example.com`, `example.com`).
+pub(crate) fn parent_domains(domain: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(domain), |d| d.split_once('.').map(|(_, rest)| rest))
+        .filter(move |d| d.contains('.') || *d == domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn unicode_domains_convert_to_punycode() {
+        assert_eq!(to_ascii_domain("bücher.example"), "xn--bcher-kva.example");
+        assert_eq!(to_ascii_domain("xn--bcher-kva.example"), "xn--bcher-kva.example");
+        assert!(matches!(to_ascii_domain("ads.example.com"), Cow::Borrowed(_)));
+    }
+    
+    #[test]
+    fn normalize_strips_the_trailing_dot() {
+        assert_eq!(normalize_domain("doubleclick.net."), "doubleclick.net");
+        assert!(matches!(normalize_domain("doubleclick.net"), Cow::Borrowed(_)));
+    }
+    
+    #[test]
+    fn normalize_lowercases() {
+        assert_eq!(normalize_domain("DoubleClick.NET"), "doubleclick.net");
+        assert_eq!(normalize_domain("BÜCHER.example"), "xn--bcher-kva.example");
+    }
+    
+    #[test]
+    fn normalize_handles_case_and_trailing_dot_together() {
+        assert_eq!(normalize_domain("Doubleclick.NET."), "doubleclick.net");
+        assert_eq!(normalize_domain("Bücher.Example."), "xn--bcher-kva.example");
+    }
+}
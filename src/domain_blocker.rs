@@ -0,0 +1,29 @@
+use crate::blocker::SimpleAdBlocker;
+use crate::stevenblack::StevenBlackBlocker;
+use async_trait::async_trait;
+
+/// Anything that can decide whether a domain is blocked, so DNS servers and
+/// proxies can switch between blocker backends.
+///
+/// Implemented by [`SimpleAdBlocker`] (filter lists) and
+/// [`StevenBlackBlocker`] (hosts files); both also block subdomains of a
+/// blocked domain.
+#[async_trait]
+pub trait DomainBlocker: Send + Sync {
+    /// Whether lookups of `domain` should be blocked. Errors count as not blocked.
+    async fn is_domain_blocked(&self, domain: &str) -> bool;
+}
+
+#[async_trait]
+impl DomainBlocker for SimpleAdBlocker {
+    async fn is_domain_blocked(&self, domain: &str) -> bool {
+        self.check_domain(domain).await.is_ok_and(|result| result.should_block)
+    }
+}
+
+#[async_trait]
+impl DomainBlocker for StevenBlackBlocker {
+    async fn is_domain_blocked(&self, domain: &str) -> bool {
+        self.is_blocked(domain).await
+    }
+}
@@ -0,0 +1,455 @@
+use crate::bloom::BloomFilter;
+use std::cmp::Ordering;
+
+/// Set of domains stored as a trie of reversed labels (`com` -> `example` -> `ads`).
+///
+/// Shared suffixes are stored once, and lookups walk the query's labels
+/// right-to-left without allocating, answering "is this domain or any of its
+/// parents in the set" in a single pass. A bloom filter built alongside the
+/// trie rules out most non-members before the trie is walked at all.
+///
+/// Wildcard entries like `*.example.com` match subdomains only, not
+/// `example.com` itself (unless it is in the set too).
+#[derive(Debug, Clone)]
+pub(crate) struct DomainTrie {
+    nodes: Vec<Node>,
+    /// Interned source names referenced by `Node::source` and `Node::wildcard_source`
+    sources: Vec<Box<str>>,
+    bloom: BloomFilter,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    /// Child labels sorted for binary search
+    children: Vec<(Box<str>, u32)>,
+    /// Index into `sources` plus one, or zero when the source is unknown
+    source: u32,
+    /// Like `source`, for the wildcard entry, which may come from another list
+    wildcard_source: u32,
+    terminal: bool,
+    /// Whether `*.` plus this domain is in the set
+    wildcard: bool,
+}
+
+impl Node {
+    fn is_entry(&self) -> bool {
+        self.terminal || self.wildcard
+    }
+    
+    /// Whether the exact (`wildcard == false`) or wildcard entry is present, and its source
+    fn entry(&self, wildcard: bool) -> (bool, u32) {
+        if wildcard {
+            (self.wildcard, self.wildcard_source)
+        } else {
+            (self.terminal, self.source)
+        }
+    }
+    
+    /// Mutable `entry`
+    fn entry_mut(&mut self, wildcard: bool) -> (&mut bool, &mut u32) {
+        if wildcard {
+            (&mut self.wildcard, &mut self.wildcard_source)
+        } else {
+            (&mut self.terminal, &mut self.source)
+        }
+    }
+}
+
+/// A domain without any `*.` wildcard prefix, and whether it had one
+fn split_wildcard(domain: &str) -> (&str, bool) {
+    match domain.strip_prefix("*.") {
+        Some(domain) => (domain, true),
+        None => (domain, false),
+    }
+}
+
+impl Default for DomainTrie {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+            sources: Vec::new(),
+            bloom: BloomFilter::default(),
+            len: 0,
+        }
+    }
+}
+
+impl DomainTrie {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Insert a domain, returning `true` if it was not already present
+    pub(crate) fn insert(&mut self, domain: &str) -> bool {
+        self.insert_from(domain, None)
+    }
+    
+    /// Insert a domain tagged with the list it came from. If the domain is
+    /// already present, its original source is kept.
+    pub(crate) fn insert_from(&mut self, domain: &str, source: Option<&str>) -> bool {
+        let (domain, wildcard) = split_wildcard(domain);
+        let domain = domain.to_ascii_lowercase();
+        let mut node = 0;
+        
+        for label in domain.rsplit('.') {
+            node = match self.find_child(node, label) {
+                Ok(pos) => self.nodes[node].children[pos].1 as usize,
+                Err(pos) => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::default());
+                    self.nodes[node].children.insert(pos, (label.into(), child as u32));
+                    child
+                }
+            };
+        }
+        
+        let inserted = !self.nodes[node].entry(wildcard).0;
+        if inserted {
+            let source = source.map_or(0, |source| self.intern_source(source));
+            let (flag, entry_source) = self.nodes[node].entry_mut(wildcard);
+            *flag = true;
+            *entry_source = source;
+            self.len += 1;
+            // Resize by doubling so the false positive rate stays low as lists grow
+            if self.len > self.bloom.capacity() {
+                self.rebuild_bloom(self.len * 2);
+            } else {
+                self.bloom.insert(&domain);
+            }
+        }
+        inserted
+    }
+    
+    /// Retag an exact entry inserted from `from` as coming from `to`,
+    /// returning `true` if it was. Entries from other sources are left alone.
+    /// `domain` and `*.domain` are separate entries, each with its own source.
+    pub(crate) fn claim(&mut self, domain: &str, from: &str, to: Option<&str>) -> bool {
+        let (domain, wildcard) = split_wildcard(domain);
+        let Some(node) = self.find_node(domain) else {
+            return false;
+        };
+        let (present, source) = self.nodes[node].entry(wildcard);
+        if !present || source == 0 || &*self.sources[source as usize - 1] != from {
+            return false;
+        }
+        let to = to.map_or(0, |to| self.intern_source(to));
+        *self.nodes[node].entry_mut(wildcard).1 = to;
+        true
+    }
+    
+    /// Remove an exact domain entry, returning `true` if it was present.
+    /// Parent or child entries are left untouched.
+    pub(crate) fn remove(&mut self, domain: &str) -> bool {
+        let (domain, wildcard) = split_wildcard(domain);
+        let Some(node) = self.find_node(domain) else {
+            return false;
+        };
+        let (flag, source) = self.nodes[node].entry_mut(wildcard);
+        if !*flag {
+            return false;
+        }
+        
+        // Removed entries stay in the bloom filter; they are only false positives
+        *flag = false;
+        *source = 0;
+        self.len -= 1;
+        true
+    }
+    
+    /// Check whether the domain or any of its parent domains is in the set
+    pub(crate) fn matches(&self, domain: &str) -> bool {
+        self.find_match(domain).is_some()
+    }
+    
+    /// Source of the entry matching the domain or one of its parents
+    pub(crate) fn matched_source(&self, domain: &str) -> Option<&str> {
+        let (node, wildcard) = self.find_match(domain)?;
+        match self.nodes[node].entry(wildcard).1 {
+            0 => None,
+            index => Some(&self.sources[index as usize - 1]),
+        }
+    }
+    
+    /// Number of domains in the set
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+    
+    /// Rough heap memory used by the trie: its nodes, their labels and
+    /// child lists, the interned sources and the bloom filter
+    pub(crate) fn approx_bytes(&self) -> usize {
+        let nodes: usize = self.nodes
+            .iter()
+            .map(|node| {
+                let labels: usize = node.children.iter().map(|(label, _)| label.len()).sum();
+                labels + node.children.capacity() * std::mem::size_of::<(Box<str>, u32)>()
+            })
+            .sum();
+        let sources: usize = self.sources.iter().map(|source| source.len()).sum();
+        
+        nodes
+            + self.nodes.capacity() * std::mem::size_of::<Node>()
+            + sources
+            + self.sources.capacity() * std::mem::size_of::<Box<str>>()
+            + self.bloom.heap_bytes()
+    }
+    
+    /// Every domain in the set, in no particular order
+    pub(crate) fn domains(&self) -> Vec<String> {
+        let mut domains = Vec::with_capacity(self.len);
+        let mut labels = Vec::new();
+        self.visit(0, &mut labels, &mut |domain, _| domains.push(domain.to_string()));
+        domains
+    }
+    
+    /// Every domain in the set with the source it was inserted from, in no
+    /// particular order
+    pub(crate) fn entries(&self) -> Vec<(String, Option<&str>)> {
+        let mut entries = Vec::with_capacity(self.len);
+        let mut labels = Vec::new();
+        self.visit(0, &mut labels, &mut |domain, source| {
+            let source = match source {
+                0 => None,
+                index => Some(&*self.sources[index as usize - 1]),
+            };
+            entries.push((domain.to_string(), source));
+        });
+        entries
+    }
+    
+    /// The node of the entry matching the domain or one of its parents, and
+    /// whether it matched as a wildcard
+    fn find_match(&self, domain: &str) -> Option<(usize, bool)> {
+        if !self.might_match(domain) {
+            return None;
+        }
+        
+        let mut node = 0;
+        let mut labels = domain.rsplit('.').peekable();
+        
+        while let Some(label) = labels.next() {
+            let pos = self.find_child(node, label).ok()?;
+            node = self.nodes[node].children[pos].1 as usize;
+            let entry = &self.nodes[node];
+            if entry.terminal {
+                return Some((node, false));
+            }
+            // Wildcards only match when there are labels left, i.e. a subdomain
+            if entry.wildcard && labels.peek().is_some() {
+                return Some((node, true));
+            }
+        }
+        
+        None
+    }
+    
+    fn intern_source(&mut self, source: &str) -> u32 {
+        let index = match self.sources.iter().position(|s| &**s == source) {
+            Some(index) => index,
+            None => {
+                self.sources.push(source.into());
+                self.sources.len() - 1
+            }
+        };
+        index as u32 + 1
+    }
+    
+    /// Bloom check of the domain and each parent; `false` means definitely not blocked
+    fn might_match(&self, domain: &str) -> bool {
+        let mut suffix = domain;
+        loop {
+            if self.bloom.might_contain(suffix) {
+                return true;
+            }
+            match suffix.find('.') {
+                Some(pos) => suffix = &suffix[pos + 1..],
+                None => return false,
+            }
+        }
+    }
+    
+    fn rebuild_bloom(&mut self, capacity: usize) {
+        let mut bloom = BloomFilter::with_capacity(capacity);
+        let mut labels = Vec::new();
+        self.visit(0, &mut labels, &mut |domain, _| bloom.insert(split_wildcard(domain).0));
+        self.bloom = bloom;
+    }
+    
+    /// Depth-first walk calling `f` with every domain in the set (wildcards
+    /// as `*.domain`) and its source index
+    fn visit<'a>(&'a self, node: usize, labels: &mut Vec<&'a str>, f: &mut impl FnMut(&str, u32)) {
+        let entry = &self.nodes[node];
+        if entry.is_entry() {
+            let domain = labels.iter().rev().copied().collect::<Vec<&str>>().join(".");
+            if entry.terminal {
+                f(&domain, entry.source);
+            }
+            if entry.wildcard {
+                f(&format!("*.{}", domain), entry.wildcard_source);
+            }
+        }
+        for (label, child) in &self.nodes[node].children {
+            labels.push(label);
+            self.visit(*child as usize, labels, f);
+            labels.pop();
+        }
+    }
+    
+    fn find_node(&self, domain: &str) -> Option<usize> {
+        let mut node = 0;
+        for label in domain.rsplit('.') {
+            let pos = self.find_child(node, label).ok()?;
+            node = self.nodes[node].children[pos].1 as usize;
+        }
+        Some(node)
+    }
+    
+    /// Binary search a node's children, comparing the query label case-insensitively
+    fn find_child(&self, node: usize, label: &str) -> Result<usize, usize> {
+        self.nodes[node]
+            .children
+            .binary_search_by(|(child, _)| compare_label(child, label))
+    }
+}
+
+fn compare_label(stored: &str, query: &str) -> Ordering {
+    stored.bytes().cmp(query.bytes().map(|b| b.to_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn trie(domains: &[&str]) -> DomainTrie {
+        let mut trie = DomainTrie::new();
+        for domain in domains {
+            trie.insert(domain);
+        }
+        trie
+    }
+    
+    #[test]
+    fn matches_entries_and_their_subdomains() {
+        let trie = trie(&["doubleclick.net", "ads.example.com"]);
+        
+        assert!(trie.matches("doubleclick.net"));
+        assert!(trie.matches("stats.g.doubleclick.net"));
+        assert!(trie.matches("ads.example.com"));
+        assert!(trie.matches("cdn.ads.example.com"));
+        assert!(!trie.matches("example.com"));
+        assert!(!trie.matches("notdoubleclick.net"));
+        assert!(!trie.matches("doubleclick.net.evil.com"));
+        assert!(!trie.matches("net"));
+    }
+    
+    #[test]
+    fn lookups_ignore_case() {
+        let trie = trie(&["Tracker.Example.COM"]);
+        assert!(trie.matches("tracker.example.com"));
+        assert!(trie.matches("PIXEL.TRACKER.example.com"));
+    }
+    
+    #[test]
+    fn entries_keep_their_own_source() {
+        let mut trie = DomainTrie::new();
+        trie.insert_from("example.com", Some("first"));
+        trie.insert_from("ads.example.com", Some("second"));
+        trie.insert("pixel.test");
+        
+        let mut entries = trie.entries();
+        entries.sort_unstable();
+        assert_eq!(entries, [
+            ("ads.example.com".to_string(), Some("second")),
+            ("example.com".to_string(), Some("first")),
+            ("pixel.test".to_string(), None),
+        ]);
+    }
+    
+    #[test]
+    fn claim_only_retags_entries_from_the_given_source() {
+        let mut trie = DomainTrie::new();
+        trie.insert_from("example.com", Some("base"));
+        trie.insert_from("pixel.test", Some("extra"));
+        
+        assert!(trie.claim("example.com", "base", Some("extra")));
+        assert!(!trie.claim("pixel.test", "base", None));
+        assert!(!trie.claim("ads.example.com", "base", None));
+        assert_eq!(trie.matched_source("ads.example.com"), Some("extra"));
+        assert_eq!(trie.matched_source("pixel.test"), Some("extra"));
+    }
+    
+    #[test]
+    fn wildcards_match_only_subdomains() {
+        let mut trie = trie(&["*.doubleclick.net"]);
+        assert!(trie.matches("ads.doubleclick.net"));
+        assert!(trie.matches("stats.g.doubleclick.net"));
+        assert!(!trie.matches("doubleclick.net"));
+        assert_eq!(trie.domains(), ["*.doubleclick.net"]);
+        
+        assert!(trie.insert("doubleclick.net"));
+        assert!(trie.matches("doubleclick.net"));
+        assert_eq!(trie.len(), 2);
+        
+        assert!(trie.remove("doubleclick.net"));
+        assert!(!trie.matches("doubleclick.net"));
+        assert!(trie.matches("ads.doubleclick.net"));
+    }
+    
+    #[test]
+    fn wildcard_and_exact_entries_keep_separate_sources() {
+        let mut trie = DomainTrie::new();
+        trie.insert_from("*.example.com", Some("base"));
+        trie.insert_from("example.com", Some("extra"));
+        
+        assert_eq!(trie.matched_source("example.com"), Some("extra"));
+        assert_eq!(trie.matched_source("ads.example.com"), Some("extra"));
+        assert!(!trie.claim("example.com", "base", None));
+        assert!(trie.claim("*.example.com", "base", Some("extra")));
+        
+        let mut entries = trie.entries();
+        entries.sort_unstable();
+        assert_eq!(entries, [
+            ("*.example.com".to_string(), Some("extra")),
+            ("example.com".to_string(), Some("extra")),
+        ]);
+        
+        assert!(trie.remove("example.com"));
+        assert_eq!(trie.matched_source("ads.example.com"), Some("extra"));
+    }
+    
+    #[test]
+    fn remove_only_drops_the_exact_entry() {
+        let mut trie = trie(&["example.com", "ads.example.com"]);
+        assert!(!trie.insert("example.com"));
+        
+        assert!(trie.remove("example.com"));
+        assert!(!trie.remove("example.com"));
+        assert!(!trie.matches("www.example.com"));
+        assert!(trie.matches("ads.example.com"));
+    }
+    
+    #[test]
+    fn shared_suffixes_are_stored_once() {
+        let domains: Vec<String> = (0..1000).map(|i| format!("host{}.ads.example.com", i)).collect();
+        let mut trie = DomainTrie::new();
+        for domain in &domains {
+            trie.insert(domain);
+        }
+        
+        // One node per distinct label plus the root: com, example, ads and each host
+        assert_eq!(trie.nodes.len(), 1 + 3 + 1000);
+        assert!(domains.iter().all(|domain| trie.matches(domain)));
+    }
+    
+    #[test]
+    fn bloom_growth_keeps_every_entry_reachable() {
+        let domains: Vec<String> = (0..5000).map(|i| format!("tracker{}.example.net", i)).collect();
+        let mut trie = DomainTrie::new();
+        for domain in &domains {
+            trie.insert(domain);
+        }
+        assert!(domains.iter().all(|domain| trie.matches(&format!("cdn.{}", domain))));
+        assert!(!trie.matches("example.net"));
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+/// Errors returned by the ad blocker API
+#[derive(Debug)]
+pub enum AdBlockerError {
+    /// Downloading a filter list or hosts file failed
+    Network(reqwest::Error),
+    /// A filter rule or built-in pattern could not be parsed
+    FilterParse(String),
+    /// The URL being checked could not be parsed
+    InvalidUrl(String),
+    /// Reading or writing a local file or proxied connection failed
+    Io(std::io::Error),
+    /// A configuration file could not be parsed or written
+    Config(String),
+    /// A downloaded filter list was empty, an HTML page or binary data
+    InvalidFilterList(String),
+}
+
+/// Result type used throughout the library
+pub type Result<T> = std::result::Result<T, AdBlockerError>;
+
+impl fmt::Display for AdBlockerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdBlockerError::Network(e) => write!(f, "Network error: {}", e),
+            AdBlockerError::FilterParse(msg) => write!(f, "Failed to parse filter: {}", msg),
+            AdBlockerError::InvalidUrl(msg) => write!(f, "Invalid URL: {}", msg),
+            AdBlockerError::Io(e) => write!(f, "I/O error: {}", e),
+            AdBlockerError::Config(msg) => write!(f, "Invalid configuration: {}", msg),
+            AdBlockerError::InvalidFilterList(msg) => write!(f, "Invalid filter list: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AdBlockerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AdBlockerError::Network(e) => Some(e),
+            AdBlockerError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for AdBlockerError {
+    fn from(e: reqwest::Error) -> Self {
+        AdBlockerError::Network(e)
+    }
+}
+
+impl From<std::io::Error> for AdBlockerError {
+    fn from(e: std::io::Error) -> Self {
+        AdBlockerError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for AdBlockerError {
+    fn from(e: serde_json::Error) -> Self {
+        AdBlockerError::Config(e.to_string())
+    }
+}
+
+impl From<toml::de::Error> for AdBlockerError {
+    fn from(e: toml::de::Error) -> Self {
+        AdBlockerError::Config(e.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for AdBlockerError {
+    fn from(e: toml::ser::Error) -> Self {
+        AdBlockerError::Config(e.to_string())
+    }
+}
+
+impl From<regex::Error> for AdBlockerError {
+    fn from(e: regex::Error) -> Self {
+        AdBlockerError::FilterParse(e.to_string())
+    }
+}
+
+impl From<url::ParseError> for AdBlockerError {
+    fn from(e: url::ParseError) -> Self {
+        AdBlockerError::InvalidUrl(e.to_string())
+    }
+}
+
+impl From<adblock::request::RequestError> for AdBlockerError {
+    fn from(e: adblock::request::RequestError) -> Self {
+        AdBlockerError::InvalidUrl(format!("{:?}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::FilterManager;
+    use crate::test_util::closed_url;
+    use std::error::Error;
+    
+    #[tokio::test]
+    async fn unreachable_list_is_a_network_error() {
+        let err = FilterManager::new().load_filters(&closed_url().await, false).await.unwrap_err();
+        assert!(matches!(err, AdBlockerError::Network(_)), "{:?}", err);
+        assert!(err.source().is_some());
+    }
+    
+    #[test]
+    fn io_errors_keep_their_source() {
+        let err = AdBlockerError::from(std::io::Error::new(std::io::ErrorKind::NotFound, "missing.txt"));
+        assert_eq!(err.to_string(), "I/O error: missing.txt");
+        assert_eq!(err.source().unwrap().to_string(), "missing.txt");
+    }
+}
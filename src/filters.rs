@@ -1,6 +1,28 @@
-use anyhow::Result;
-use regex::Regex;
+use crate::config::AdBlockerConfig;
+use crate::error::{AdBlockerError, Result};
+use crate::types::BlockCategory;
+use adblock::lists::parse_filter;
+use async_trait::async_trait;
+use base64::Engine as _;
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use futures_util::future::join_all;
+use regex::{Regex, RegexSet};
 use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::{info, instrument, warn};
+
+/// Run `future`, also returning how long it took
+pub(crate) async fn timed<F: Future>(future: F) -> (F::Output, Duration) {
+    let started = Instant::now();
+    let output = future.await;
+    (output, started.elapsed())
+}
 
 /// Filter list sources
 pub struct FilterSources;
@@ -9,7 +31,73 @@ impl FilterSources {
     pub const EASYLIST: &'static str = "https://easylist.to/easylist/easylist.txt";
     pub const EASYPRIVACY: &'static str = "https://easylist.to/easylist/easyprivacy.txt";
     pub const MALWARE_DOMAINS: &'static str = "https://malware-filter.gitlab.io/malware-filter/urlhaus-filter-online.txt";
+    pub const PHISHING: &'static str = "https://malware-filter.gitlab.io/malware-filter/phishing-filter.txt";
     pub const SOCIAL_ANNOYANCES: &'static str = "https://easylist.to/easylist/fanboy-social.txt";
+    pub const CRYPTOMINING: &'static str = "https://raw.githubusercontent.com/hoshsadiq/adblock-nocoin-list/master/nocoin.txt";
+}
+
+/// A user-provided filter list, loaded alongside the built-in lists.
+///
+/// Implement this to pull rules from places the built-in sources can't reach,
+/// such as a private list behind authentication.
+#[async_trait]
+pub trait FilterSource: Send + Sync {
+    /// Name reported in `BlockResult::source_list` when one of these rules matches
+    fn name(&self) -> String {
+        "External".to_string()
+    }
+    
+    /// Category reported when one of these rules matches
+    fn category(&self) -> BlockCategory;
+    
+    /// Fetch the list's rules in Adblock Plus syntax
+    async fn fetch(&self) -> Result<Vec<String>>;
+}
+
+impl fmt::Debug for dyn FilterSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterSource")
+            .field("name", &self.name())
+            .field("category", &self.category())
+            .finish()
+    }
+}
+
+/// A filter list URL along with headers to send when downloading it, for
+/// lists behind authentication
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSourceSpec {
+    pub url: String,
+    /// Read from config files but never written back out, so credentials
+    /// don't end up in saved configs or blocker snapshots
+    #[serde(skip_serializing)]
+    pub headers: HashMap<String, String>,
+}
+
+impl FilterSourceSpec {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: HashMap::new(),
+        }
+    }
+    
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+    
+    /// Authenticate with HTTP basic auth
+    pub fn with_basic_auth(self, username: &str, password: &str) -> Self {
+        let credentials = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        self.with_header("Authorization", format!("Basic {}", credentials))
+    }
+    
+    /// Authenticate with a bearer token
+    pub fn with_bearer_token(self, token: &str) -> Self {
+        self.with_header("Authorization", format!("Bearer {}", token))
+    }
 }
 
 /// Built-in tracking patterns
@@ -17,6 +105,13 @@ pub struct TrackingPatterns;
 
 impl TrackingPatterns {
     pub fn get_patterns() -> Result<Vec<Regex>> {
+        Self::get_patterns_except(&[])
+    }
+    
+    /// The built-in patterns minus those in `disabled`, each given either by
+    /// its source (e.g. `segment\.com`) or its index in `get_patterns`.
+    /// Entries matching no built-in pattern are logged and ignored.
+    pub fn get_patterns_except(disabled: &[String]) -> Result<Vec<Regex>> {
         let patterns = vec![
             // Google Analytics & Ads
             r"google-analytics\.com",
@@ -49,10 +144,76 @@ impl TrackingPatterns {
             r"amplitude\.com",
         ];
         
+        let disables = |entry: &str, index: usize, pattern: &str| entry == pattern || entry.parse::<usize>() == Ok(index);
+        let is_disabled = |index: usize, pattern: &str| disabled.iter().any(|entry| disables(entry, index, pattern));
+        for entry in disabled {
+            if !patterns.iter().enumerate().any(|(index, pattern)| disables(entry, index, pattern)) {
+                warn!(pattern = %entry, "disabled pattern matches no built-in tracking pattern");
+            }
+        }
+        
         patterns.into_iter()
-            .map(|p| Regex::new(p))
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| anyhow::anyhow!("Failed to compile regex: {}", e))
+            .enumerate()
+            .filter(|&(index, pattern)| !is_disabled(index, pattern))
+            .map(|(_, pattern)| Regex::new(pattern))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+}
+
+/// Query parameters commonly used for click and campaign tracking
+pub struct TrackingParams;
+
+impl TrackingParams {
+    /// Built-in parameter names. A trailing `*` matches any parameter with that prefix.
+    pub fn default_params() -> Vec<String> {
+        vec![
+            "utm_*",
+            "fbclid",
+            "gclid",
+            "dclid",
+            "gbraid",
+            "wbraid",
+            "msclkid",
+            "mc_cid",
+            "mc_eid",
+            "igshid",
+            "yclid",
+            "_hsenc",
+            "_hsmi",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect()
+    }
+    
+    /// Check whether a query parameter name matches any of the given entries
+    pub fn matches(name: &str, params: &[String]) -> bool {
+        params.iter().any(|param| match param.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == param,
+        })
+    }
+}
+
+/// Text that gives away ad-injection code in HTML and JavaScript bodies
+pub struct BodySignatures;
+
+impl BodySignatures {
+    /// Built-in signatures, matched literally and case-sensitively
+    pub fn default_signatures() -> Vec<String> {
+        vec![
+            "adsbygoogle",
+            "googletag.pubads",
+            "pagead2.googlesyndication.com",
+            "apstag.fetchBids",
+            "pbjs.que",
+            "window._taboola",
+            "outbrain.com/outbrain.js",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect()
     }
 }
 
@@ -73,48 +234,730 @@ impl SocialPatterns {
         ];
         
         patterns.into_iter()
-            .map(|p| Regex::new(p))
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| anyhow::anyhow!("Failed to compile regex: {}", e))
+            .map(Regex::new)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+}
+
+/// Regexes tested against a URL in a single pass, reporting the first
+/// matching pattern in the order they were given
+#[derive(Debug, Clone)]
+pub(crate) struct PatternSet {
+    patterns: Vec<Regex>,
+    set: RegexSet,
+}
+
+impl PatternSet {
+    pub(crate) fn new(patterns: Vec<Regex>) -> Result<Self> {
+        let set = RegexSet::new(patterns.iter().map(Regex::as_str))?;
+        Ok(Self { patterns, set })
+    }
+    
+    /// The earliest pattern that matches `text`
+    pub(crate) fn first_match(&self, text: &str) -> Option<&Regex> {
+        let index = self.set.matches(text).iter().next()?;
+        Some(&self.patterns[index])
+    }
+    
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        self.set.is_match(text)
     }
+    
+    /// Append patterns, recompiling the combined set
+    pub(crate) fn extend(&mut self, patterns: Vec<Regex>) -> Result<()> {
+        self.patterns.extend(patterns);
+        self.set = RegexSet::new(self.patterns.iter().map(Regex::as_str))?;
+        Ok(())
+    }
+}
+
+/// A downloaded filter list along with the validators needed to revalidate it
+#[derive(Debug, Clone, Default)]
+struct CachedList {
+    filters: Vec<String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// First bytes of any gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Share of non-blank lines that may contain binary data before a download
+/// is rejected as not being a filter list
+const MAX_GARBAGE_LINE_RATIO: f64 = 0.1;
+
+/// Delay before the first retry; doubled after each further failure
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Downloads with fewer rules than this are rejected unless overridden
+const DEFAULT_MIN_RULES: usize = 1;
+
+/// How often `FilterManager` served lists from its cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Loads answered from the cache (the server said the list was
+    /// unchanged, or sent a broken list)
+    pub hits: u64,
+    /// Loads that downloaded the list
+    pub misses: u64,
+    /// Total length of the cached rules
+    pub bytes_cached: u64,
 }
 
 /// Filter list manager
 pub struct FilterManager {
-    cached_filters: HashMap<String, Vec<String>>,
+    client: reqwest::Client,
+    retries: u32,
+    /// Downloads with fewer parseable rules are rejected
+    min_rules: usize,
+    cached_filters: HashMap<String, CachedList>,
+    /// Extra headers sent when downloading each URL
+    source_headers: HashMap<String, HashMap<String, String>>,
+    /// URLs downloaded from another location, by the URL they stand in for
+    mirrors: HashMap<String, String>,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
 }
 
-impl FilterManager {
-    pub fn new() -> Self {
+impl Default for FilterManager {
+    fn default() -> Self {
         Self {
+            client: reqwest::Client::default(),
+            retries: 0,
+            min_rules: DEFAULT_MIN_RULES,
             cached_filters: HashMap::new(),
+            source_headers: HashMap::new(),
+            mirrors: HashMap::new(),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+        }
+    }
+}
+
+impl FilterManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Create a manager with a per-request timeout and a number of retries
+    /// (with exponential backoff) for failed downloads
+    pub fn with_fetch_settings(timeout: Duration, retries: u32) -> Result<Self> {
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        Ok(Self { retries, ..Self::default() }.with_client(client))
+    }
+    
+    /// Create a manager with the download settings from `config`: timeout,
+    /// retries, proxy and extra root certificates
+    pub fn from_config(config: &AdBlockerConfig) -> Result<Self> {
+        let mut builder = reqwest::Client::builder().timeout(config.fetch_timeout);
+        if let Some(proxy) = &config.fetch_proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| AdBlockerError::Config(format!("invalid fetch proxy {}: {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+        for path in &config.fetch_ca_certs {
+            let pem = std::fs::read(path)?;
+            let certificate = reqwest::Certificate::from_pem(&pem)
+                .map_err(|e| AdBlockerError::Config(format!("invalid CA certificate {}: {}", path.display(), e)))?;
+            builder = builder.add_root_certificate(certificate);
         }
+        let manager = Self { retries: config.fetch_retries, ..Self::default() }.with_client(builder.build()?);
+        Ok(config.filter_lists.iter().cloned().fold(manager, Self::with_source))
     }
     
-    /// Load filters from URL with caching
+    /// Download every list with `client`, reusing its connection pool, proxy
+    /// and TLS settings
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+    
+    /// The client lists are downloaded with, for other downloads that should
+    /// use the same settings
+    pub(crate) fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+    
+    /// Send the spec's headers whenever its URL is downloaded
+    pub fn with_source(mut self, spec: FilterSourceSpec) -> Self {
+        self.source_headers.insert(spec.url, spec.headers);
+        self
+    }
+    
+    /// Download `url` (e.g. `FilterSources::EASYLIST`) from `mirror` instead,
+    /// such as a copy on an internal server. The list is still cached and
+    /// reported under `url`.
+    #[cfg(test)]
+    pub(crate) fn with_mirror(mut self, url: impl Into<String>, mirror: impl Into<String>) -> Self {
+        self.mirrors.insert(url.into(), mirror.into());
+        self
+    }
+    
+    /// Reject downloaded lists with fewer than `min_rules` parseable rules
+    pub fn with_min_rules(mut self, min_rules: usize) -> Self {
+        self.min_rules = min_rules;
+        self
+    }
+    
+    /// Load filters from URL with caching. A cached list is revalidated
+    /// with a conditional GET and reused if the server answers 304 Not
+    /// Modified, or sends something that isn't a valid filter list (see
+    /// `AdBlockerError::InvalidFilterList`).
+    #[instrument(skip(self))]
     pub async fn load_filters(&mut self, url: &str, use_cache: bool) -> Result<Vec<String>> {
-        if use_cache && self.cached_filters.contains_key(url) {
-            return Ok(self.cached_filters[url].clone());
+        let cached = if use_cache { self.cached_filters.get(url) } else { None };
+        let list = self.revalidate(url, cached).await?;
+        let filters = list.filters.clone();
+        info!(rules = filters.len(), "loaded filter list");
+        
+        if use_cache {
+            self.cached_filters.insert(url.to_string(), list);
         }
         
-        let response = reqwest::get(url).await?;
-        let content = response.text().await?;
+        Ok(filters)
+    }
+    
+    /// Re-download a cached filter list using a conditional GET. If the server
+    /// answers 304 Not Modified, the cached filters are returned without re-parsing.
+    pub async fn refresh_filters(&mut self, url: &str) -> Result<Vec<String>> {
+        self.load_filters(url, true).await
+    }
+    
+    /// Load several filter lists concurrently, returning results in the same
+    /// order as `urls`. Cached lists are revalidated like in `load_filters`.
+    pub async fn load_many(&mut self, urls: &[&str], use_cache: bool) -> Vec<Result<Vec<String>>> {
+        self.load_many_timed(urls, use_cache)
+            .await
+            .into_iter()
+            .map(|(result, _)| result)
+            .collect()
+    }
+    
+    /// `load_many`, along with how long each list took to load
+    pub(crate) async fn load_many_timed(&mut self, urls: &[&str], use_cache: bool) -> Vec<(Result<Vec<String>>, Duration)> {
+        let manager = &*self;
+        let fetches = urls.iter().map(|url| {
+            let cached = if use_cache { manager.cached_filters.get(*url) } else { None };
+            timed(manager.revalidate(url, cached))
+        });
+        let results: Vec<(Result<CachedList>, Duration)> = join_all(fetches).await;
         
-        let filters: Vec<String> = content
-            .lines()
-            .filter(|line| !line.starts_with('!') && !line.trim().is_empty())
-            .map(|line| line.to_string())
-            .collect();
+        urls.iter()
+            .zip(results)
+            .map(|(url, (result, elapsed))| {
+                let result = result.map(|list| {
+                    let filters = list.filters.clone();
+                    if use_cache {
+                        self.cached_filters.insert(url.to_string(), list);
+                    }
+                    filters
+                });
+                (result, elapsed)
+            })
+            .collect()
+    }
+    
+    /// Download a list, or reuse `cached` if the server reports it unchanged
+    /// or sends a broken list
+    async fn revalidate(&self, url: &str, cached: Option<&CachedList>) -> Result<CachedList> {
+        let list = match (self.fetch_list(url, cached).await, cached) {
+            (Ok(Some(list)), _) => {
+                self.cache_misses.fetch_add(1, Ordering::Relaxed);
+                return Ok(list);
+            }
+            (Ok(None), Some(cached)) => cached.clone(),
+            (Ok(None), None) => CachedList::default(),
+            (Err(AdBlockerError::InvalidFilterList(msg)), Some(cached)) => {
+                warn!(url, reason = %msg, "invalid filter list; keeping the cached copy");
+                cached.clone()
+            }
+            (Err(e), _) => return Err(e),
+        };
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        Ok(list)
+    }
+    
+    /// Download a list, sending `If-None-Match`/`If-Modified-Since` when a cached
+    /// copy is given. Returns `None` if the server reports it unchanged.
+    async fn fetch_list(&self, url: &str, cached: Option<&CachedList>) -> Result<Option<CachedList>> {
+        let mut attempt = 0;
+        let response = loop {
+            let mut request = self.client.get(self.mirrors.get(url).map_or(url, String::as_str));
+            for (name, value) in self.source_headers.get(url).into_iter().flatten() {
+                request = request.header(name, value);
+            }
+            if let Some(cached) = cached {
+                if let Some(etag) = &cached.etag {
+                    request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = &cached.last_modified {
+                    request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+                }
+            }
+            
+            // Retry connection failures, timeouts and server errors
+            match request.send().await {
+                Ok(response) if !response.status().is_server_error() => break response,
+                Ok(response) if attempt >= self.retries => break response,
+                Err(e) if attempt >= self.retries => return Err(e.into()),
+                _ => {}
+            }
+            
+            tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt)).await;
+            attempt += 1;
+        };
         
-        if use_cache {
-            self.cached_filters.insert(url.to_string(), filters.clone());
+        if cached.is_some() && response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(None);
         }
         
-        Ok(filters)
+        let response = response.error_for_status()?;
+        let header = |name| {
+            response.headers()
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(reqwest::header::ETAG);
+        let last_modified = header(reqwest::header::LAST_MODIFIED);
+        let content = decode_list(url, &response.bytes().await?)?;
+        let filters = parse_filter_lines(&content);
+        self.validate_list(url, &filters)?;
+        
+        Ok(Some(CachedList {
+            filters,
+            etag,
+            last_modified,
+        }))
+    }
+    
+    /// Reject HTML pages (e.g. a mirror's error page) and lists with too few
+    /// rules, which would otherwise load as a blocker that matches nothing
+    fn validate_list(&self, url: &str, filters: &[String]) -> Result<()> {
+        let first_rule = filters.iter().map(|line| line.trim()).find(|line| !line.starts_with('['));
+        if let Some(line) = first_rule {
+            let line = line.to_ascii_lowercase();
+            if line.starts_with("<!doctype") || line.starts_with("<html") {
+                return Err(AdBlockerError::InvalidFilterList(format!("{} returned an HTML page", url)));
+            }
+        }
+        
+        let rules = filters
+            .iter()
+            .filter(|line| parse_filter(line, true, Default::default()).is_ok())
+            .count();
+        if rules < self.min_rules {
+            return Err(AdBlockerError::InvalidFilterList(format!(
+                "{} has {} rules, expected at least {}",
+                url, rules, self.min_rules
+            )));
+        }
+        Ok(())
     }
     
     /// Clear filter cache
     pub fn clear_cache(&mut self) {
         self.cached_filters.clear();
     }
-}
\ No newline at end of file
+    
+    /// Cache hits and misses of all loads so far, and the current cache size
+    pub fn cache_stats(&self) -> CacheStats {
+        let bytes_cached = self.cached_filters
+            .values()
+            .flat_map(|list| &list.filters)
+            .map(|rule| rule.len() as u64)
+            .sum();
+        CacheStats {
+            hits: self.cache_hits.load(Ordering::Relaxed),
+            misses: self.cache_misses.load(Ordering::Relaxed),
+            bytes_cached,
+        }
+    }
+}
+
+/// Read the rules from every `.txt` file in `dirs`, a directory at a time
+/// and in filename order within each. Lines starting with `!` or `#` are
+/// comments, except cosmetic rules like `##.ad`.
+pub async fn load_filter_dirs(dirs: &[PathBuf]) -> Result<Vec<String>> {
+    let mut rules = Vec::new();
+    
+    for dir in dirs {
+        let mut paths = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_file() && path.extension().is_some_and(|ext| ext == "txt") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        
+        for path in paths {
+            let content = tokio::fs::read_to_string(&path).await?;
+            rules.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !is_comment(line))
+                    .map(str::to_string),
+            );
+        }
+    }
+    Ok(rules)
+}
+
+fn is_comment(line: &str) -> bool {
+    line.starts_with('!')
+        || (line.starts_with('#') && !["##", "#@#", "#?#"].iter().any(|marker| line.starts_with(marker)))
+}
+
+/// Turn a downloaded list into text, decompressing it if it's gzipped. Lists
+/// are gunzipped when the body starts with the gzip magic bytes, whatever
+/// the URL or `Content-Encoding` say: a `.gz` file may already have been
+/// decompressed in transit, and mirrors don't always send the header.
+fn decode_list(url: &str, body: &[u8]) -> Result<String> {
+    let content = if body.starts_with(&GZIP_MAGIC) {
+        let mut content = Vec::new();
+        GzDecoder::new(body).read_to_end(&mut content).map_err(|e| {
+            AdBlockerError::InvalidFilterList(format!("could not decompress {}: {}", url, e))
+        })?;
+        String::from_utf8_lossy(&content).into_owned()
+    } else {
+        String::from_utf8_lossy(body).into_owned()
+    };
+    
+    let lines: Vec<&str> = content.lines().filter(|line| !line.trim().is_empty()).collect();
+    let garbage = lines
+        .iter()
+        .filter(|line| line.chars().any(|c| c == char::REPLACEMENT_CHARACTER || (c.is_control() && c != '\t' && c != '\r')))
+        .count();
+    if !lines.is_empty() && garbage as f64 / lines.len() as f64 > MAX_GARBAGE_LINE_RATIO {
+        return Err(AdBlockerError::InvalidFilterList(format!(
+            "{} does not look like a filter list ({} of {} lines are not text)",
+            url, garbage, lines.len()
+        )));
+    }
+    Ok(content)
+}
+
+/// Split a downloaded list into rules, dropping comments and blank lines
+fn parse_filter_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter(|line| !line.starts_with('!') && !line.trim().is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{MockResponse, MockServer, closed_url, filter_list, temp_path};
+    use std::time::Instant;
+    use tracing_test::traced_test;
+    
+    #[test]
+    fn pattern_sets_report_the_same_match_as_a_linear_scan() {
+        let urls = [
+            "https://www.google-analytics.com/collect?v=1",
+            "https://connect.facebook.net/en_US/fbevents.js",
+            "https://www.facebook.com/tr?id=1&ev=PageView",
+            "https://www.facebook.com/plugins/like.php?href=https://doubleclick.net/",
+            "https://platform.twitter.com/widgets.js",
+            "https://www.youtube.com/embed/abc",
+            "https://cdn.example/app.js",
+            "https://news.example/article?utm_source=feed",
+        ];
+        
+        for patterns in [TrackingPatterns::get_patterns().unwrap(), SocialPatterns::get_patterns().unwrap()] {
+            let set = PatternSet::new(patterns.clone()).unwrap();
+            for url in urls {
+                let linear = patterns.iter().find(|pattern| pattern.is_match(url)).map(Regex::as_str);
+                assert_eq!(set.first_match(url).map(Regex::as_str), linear, "{url}");
+                assert_eq!(set.is_match(url), linear.is_some(), "{url}");
+            }
+        }
+    }
+    
+    #[test]
+    fn extended_pattern_sets_keep_their_order() {
+        let mut set = PatternSet::new(vec![Regex::new("ads").unwrap()]).unwrap();
+        set.extend(vec![Regex::new("banner").unwrap(), Regex::new("ads/banner").unwrap()]).unwrap();
+        assert_eq!(set.first_match("https://x.example/ads/banner.png").unwrap().as_str(), "ads");
+        assert_eq!(set.first_match("https://x.example/banner.png").unwrap().as_str(), "banner");
+        assert!(set.first_match("https://x.example/").is_none());
+    }
+    
+    #[tokio::test]
+    async fn lists_download_concurrently() {
+        let delay = Duration::from_secs(1);
+        let paths = ["/easylist.txt", "/easyprivacy.txt", "/malware.txt", "/social.txt"];
+        let server = MockServer::start(paths.iter().map(|path| (*path, vec![MockResponse::ok("||ads.example^\n").delay(delay)])).collect()).await;
+        let urls: Vec<String> = paths.iter().map(|path| server.url(path)).collect();
+        let urls: Vec<&str> = urls.iter().map(String::as_str).collect();
+        
+        let started = Instant::now();
+        let results = FilterManager::new().load_many(&urls, false).await;
+        let elapsed = started.elapsed();
+        assert!(results.iter().all(Result::is_ok));
+        assert!(elapsed >= delay);
+        assert!(elapsed < delay * 2, "four lists took {:?}", elapsed);
+    }
+    
+    #[tokio::test]
+    async fn not_modified_lists_come_from_the_cache() {
+        let body = "[Adblock Plus 2.0]\n||ads.example.com^\n";
+        let server = MockServer::start(vec![(
+            "/list.txt",
+            vec![
+                MockResponse::ok(body).header("ETag", "\"v1\"").header("Last-Modified", "Tue, 01 Sep 2026 00:00:00 GMT"),
+                MockResponse::status(304),
+            ],
+        )])
+        .await;
+        let url = server.url("/list.txt");
+        let mut manager = FilterManager::new();
+        
+        let first = manager.load_filters(&url, true).await.unwrap();
+        let second = manager.refresh_filters(&url).await.unwrap();
+        
+        assert_eq!(first, second);
+        assert!(second.contains(&"||ads.example.com^".to_string()));
+        let requests = server.requests();
+        assert!(!requests[0].to_lowercase().contains("if-none-match"));
+        assert!(requests[1].to_lowercase().contains("if-none-match: \"v1\""));
+        assert!(requests[1].to_lowercase().contains("if-modified-since: tue, 01 sep 2026"));
+    }
+    
+    #[tokio::test]
+    async fn cache_stats_count_hits_and_misses() {
+        let body = "[Adblock Plus 2.0]\n||ads.example.com^\n";
+        let server = MockServer::start(vec![(
+            "/list.txt",
+            vec![MockResponse::ok(body).header("ETag", "\"v1\""), MockResponse::status(304)],
+        )])
+        .await;
+        let url = server.url("/list.txt");
+        let mut manager = FilterManager::new();
+        assert_eq!(manager.cache_stats(), CacheStats::default());
+        
+        manager.load_filters(&url, true).await.unwrap();
+        manager.load_filters(&url, true).await.unwrap();
+        
+        let stats = manager.cache_stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.bytes_cached, ("[Adblock Plus 2.0]".len() + "||ads.example.com^".len()) as u64);
+    }
+    
+    #[tokio::test]
+    async fn uncached_loads_always_download() {
+        let server = MockServer::start(vec![("/list.txt", vec![MockResponse::ok("||ads.example.com^\n").header("ETag", "\"v1\"")])]).await;
+        let url = server.url("/list.txt");
+        let mut manager = FilterManager::new();
+        
+        manager.load_filters(&url, false).await.unwrap();
+        manager.load_filters(&url, false).await.unwrap();
+        
+        assert_eq!(server.requests().len(), 2);
+        assert!(server.requests().iter().all(|head| !head.to_lowercase().contains("if-none-match")));
+    }
+    
+    fn flaky_server_routes(body: &str) -> Vec<(&'static str, Vec<MockResponse>)> {
+        vec![("/list.txt", vec![MockResponse::status(500), MockResponse::status(503), MockResponse::ok(body)])]
+    }
+    
+    #[tokio::test]
+    async fn server_errors_are_retried() {
+        let server = MockServer::start(flaky_server_routes(&filter_list(&["||ads.example.com^"]))).await;
+        let mut manager = FilterManager::with_fetch_settings(Duration::from_secs(5), 2).unwrap();
+        
+        let filters = manager.load_filters(&server.url("/list.txt"), false).await.unwrap();
+        
+        assert!(filters.contains(&"||ads.example.com^".to_string()));
+        assert_eq!(server.requests().len(), 3);
+    }
+    
+    #[tokio::test]
+    async fn running_out_of_retries_is_a_network_error() {
+        let server = MockServer::start(flaky_server_routes(&filter_list(&["||ads.example.com^"]))).await;
+        let mut manager = FilterManager::with_fetch_settings(Duration::from_secs(5), 1).unwrap();
+        
+        let err = manager.load_filters(&server.url("/list.txt"), false).await.unwrap_err();
+        
+        assert!(matches!(err, AdBlockerError::Network(_)), "{:?}", err);
+        assert_eq!(server.requests().len(), 2);
+    }
+    
+    #[tokio::test]
+    async fn downloads_go_through_the_given_client() {
+        let server = MockServer::start(vec![("/list.txt", vec![MockResponse::ok(filter_list(&["||ads.example.com^"]))])]).await;
+        let proxy = reqwest::Proxy::all(closed_url().await).unwrap();
+        let client = reqwest::Client::builder().proxy(proxy).build().unwrap();
+        let mut manager = FilterManager::new().with_client(client);
+        
+        let err = manager.load_filters(&server.url("/list.txt"), false).await.unwrap_err();
+        
+        assert!(matches!(&err, AdBlockerError::Network(e) if e.is_connect()), "{:?}", err);
+        assert!(server.requests().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn configured_proxy_and_certificates_are_applied() {
+        let server = MockServer::start(vec![("/list.txt", vec![MockResponse::ok(filter_list(&["||ads.example.com^"]))])]).await;
+        let config = AdBlockerConfig { fetch_proxy: Some(closed_url().await), fetch_retries: 0, ..AdBlockerConfig::default() };
+        let mut manager = FilterManager::from_config(&config).unwrap();
+        assert!(manager.load_filters(&server.url("/list.txt"), false).await.is_err());
+        assert!(server.requests().is_empty());
+        
+        let path = temp_path("not-a-cert.pem");
+        std::fs::write(&path, "-----BEGIN CERTIFICATE-----\nnot base64\n-----END CERTIFICATE-----\n").unwrap();
+        let config = AdBlockerConfig { fetch_ca_certs: vec![path.clone()], ..AdBlockerConfig::default() };
+        let result = FilterManager::from_config(&config);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+    
+    #[tokio::test]
+    async fn source_headers_are_sent_with_the_download() {
+        let server = MockServer::start(vec![
+            ("/private.txt", vec![MockResponse::ok(filter_list(&["||ads.example.com^"]))]),
+            ("/public.txt", vec![MockResponse::ok(filter_list(&["||pixel.example.com^"]))]),
+        ]).await;
+        let mut manager = FilterManager::new()
+            .with_source(FilterSourceSpec::new(server.url("/private.txt")).with_bearer_token("s3cret"));
+        
+        manager.load_filters(&server.url("/private.txt"), false).await.unwrap();
+        manager.load_filters(&server.url("/public.txt"), false).await.unwrap();
+        
+        let requests: Vec<String> = server.requests().iter().map(|head| head.to_ascii_lowercase()).collect();
+        assert!(requests[0].contains("authorization: bearer s3cret"), "{}", requests[0]);
+        assert!(!requests[1].contains("authorization"), "{}", requests[1]);
+    }
+    
+    #[tokio::test]
+    #[traced_test]
+    async fn loads_emit_an_info_event_with_the_rule_count() {
+        let server = MockServer::start(vec![("/list.txt", vec![MockResponse::ok(filter_list(&["||ads.example.com^", "||pixel.example.com^"]))])]).await;
+        let url = server.url("/list.txt");
+        
+        let filters = FilterManager::new().load_filters(&url, false).await.unwrap();
+        
+        assert!(logs_contain("loaded filter list"));
+        assert!(logs_contain(&format!("rules={}", filters.len())));
+        assert!(logs_contain(&url));
+    }
+    
+    #[tokio::test]
+    async fn mirrored_lists_are_downloaded_from_the_mirror() {
+        let server = MockServer::start(vec![("/easylist.txt", vec![MockResponse::ok(filter_list(&["||ads.example.com^"]))])]).await;
+        let mut manager = FilterManager::new().with_mirror(FilterSources::EASYLIST, server.url("/easylist.txt"));
+        
+        let filters = manager.load_filters(FilterSources::EASYLIST, true).await.unwrap();
+        assert!(filters.contains(&"||ads.example.com^".to_string()));
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(manager.cache_stats().misses, 1);
+    }
+    
+    #[test]
+    fn basic_auth_is_base64_encoded() {
+        let spec = FilterSourceSpec::new("https://mirror.example/easylist.txt").with_basic_auth("ci", "hunter2");
+        assert_eq!(spec.headers["Authorization"], "Basic Y2k6aHVudGVyMg==");
+    }
+    
+    #[test]
+    fn source_headers_are_read_but_not_serialized() {
+        let spec = FilterSourceSpec::new("https://mirror.example/easylist.txt").with_bearer_token("s3cret");
+        let json = serde_json::to_string(&spec).unwrap();
+        assert!(!json.contains("s3cret"));
+        assert_eq!(serde_json::from_str::<FilterSourceSpec>(&json).unwrap(), FilterSourceSpec::new("https://mirror.example/easylist.txt"));
+        
+        let spec: FilterSourceSpec = serde_json::from_str(r#"{"url": "https://mirror.example/easylist.txt", "headers": {"Authorization": "Bearer s3cret"}}"#).unwrap();
+        assert_eq!(spec.headers["Authorization"], "Bearer s3cret");
+    }
+    
+    #[tokio::test]
+    async fn slow_responses_time_out() {
+        let server = MockServer::start(vec![("/list.txt", vec![MockResponse::ok(filter_list(&["||ads.example.com^"])).delay(Duration::from_secs(5))])]).await;
+        let mut manager = FilterManager::with_fetch_settings(Duration::from_millis(200), 0).unwrap();
+        
+        let err = manager.load_filters(&server.url("/list.txt"), false).await.unwrap_err();
+        
+        assert!(matches!(&err, AdBlockerError::Network(e) if e.is_timeout()), "{:?}", err);
+    }
+    
+    const ERROR_PAGE: &str = "<!DOCTYPE html>\n<html><head><title>503 Service Unavailable</title></head>\n<body>Try again later</body></html>\n";
+    
+    #[tokio::test]
+    async fn html_pages_and_empty_lists_are_rejected() {
+        let server = MockServer::start(vec![
+            ("/html.txt", vec![MockResponse::ok(ERROR_PAGE)]),
+            ("/empty.txt", vec![MockResponse::ok("[Adblock Plus 2.0]\n! Nothing here\n")]),
+        ]).await;
+        let mut manager = FilterManager::new();
+        
+        for path in ["/html.txt", "/empty.txt"] {
+            let err = manager.load_filters(&server.url(path), true).await.unwrap_err();
+            assert!(matches!(err, AdBlockerError::InvalidFilterList(_)), "{}: {:?}", path, err);
+        }
+    }
+    
+    #[tokio::test]
+    async fn broken_downloads_fall_back_to_the_cached_list() {
+        let server = MockServer::start(vec![(
+            "/list.txt",
+            vec![MockResponse::ok(filter_list(&["||ads.example.com^"])), MockResponse::ok(ERROR_PAGE)],
+        )]).await;
+        let mut manager = FilterManager::new();
+        let url = server.url("/list.txt");
+        
+        manager.load_filters(&url, true).await.unwrap();
+        let filters = manager.refresh_filters(&url).await.unwrap();
+        
+        assert!(filters.contains(&"||ads.example.com^".to_string()));
+        assert_eq!(server.requests().len(), 2);
+    }
+    
+    fn gzip(data: &str) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, data.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+    
+    #[tokio::test]
+    async fn gzipped_lists_are_decompressed() {
+        let rules = ["||ads.example.com^", "||pixel.example.com^", "example.com##.banner"];
+        let body = gzip(&filter_list(&rules));
+        // One mirror names the file .gz, the other serves gzip under a .txt name
+        let server = MockServer::start(vec![
+            ("/easylist.txt.gz", vec![MockResponse::ok(body.clone())]),
+            ("/easylist.txt", vec![MockResponse::ok(body)]),
+        ]).await;
+        let mut manager = FilterManager::new();
+        
+        for path in ["/easylist.txt.gz", "/easylist.txt"] {
+            let filters = manager.load_filters(&server.url(path), false).await.unwrap();
+            assert!(rules.iter().all(|rule| filters.contains(&rule.to_string())), "{}: {:?}", path, filters);
+        }
+    }
+    
+    #[tokio::test]
+    async fn plain_lists_under_a_gz_name_are_not_gunzipped() {
+        let rules = ["||ads.example.com^", "||pixel.example.com^"];
+        let server = MockServer::start(vec![("/easylist.txt.gz", vec![MockResponse::ok(filter_list(&rules))])]).await;
+        
+        let filters = FilterManager::new().load_filters(&server.url("/easylist.txt.gz"), false).await.unwrap();
+        
+        assert!(rules.iter().all(|rule| filters.contains(&rule.to_string())), "{:?}", filters);
+    }
+    
+    #[tokio::test]
+    async fn binary_garbage_is_rejected() {
+        let garbage: Vec<u8> = (0..4096u32).map(|i| (i * 7919 % 256) as u8).collect();
+        let server = MockServer::start(vec![("/list.txt", vec![MockResponse::ok(garbage)])]).await;
+        
+        let err = FilterManager::new().load_filters(&server.url("/list.txt"), false).await.unwrap_err();
+        
+        assert!(matches!(&err, AdBlockerError::InvalidFilterList(message) if message.contains("does not look like a filter list")), "{:?}", err);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::error::AdBlockerError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An IPv4 or IPv6 network in CIDR notation, e.g. `203.0.113.0/24`.
+///
+/// A bare address is read as a single-host network (`/32` or `/128`). Host
+/// bits below the prefix are ignored, so `203.0.113.7/24` is the same
+/// network as `203.0.113.0/24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    /// The network of `addr` with a `prefix_len`-bit prefix. Returns `None`
+    /// if the prefix is longer than the address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Self> {
+        (prefix_len <= max_prefix_len(addr)).then_some(Self { addr, prefix_len })
+    }
+    
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+    
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+    
+    /// Whether `ip` is in this network. IPv4-mapped IPv6 addresses (e.g.
+    /// `::ffff:203.0.113.5`) match IPv4 networks as the address they map;
+    /// otherwise IPv4 addresses never match IPv6 networks and vice versa.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) if self.addr.is_ipv4() => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            _ => ip,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => prefix_matches(u32::from(net).into(), u32::from(ip).into(), 32, self.prefix_len),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => prefix_matches(net.into(), ip.into(), 128, self.prefix_len),
+            _ => false,
+        }
+    }
+}
+
+fn max_prefix_len(addr: IpAddr) -> u8 {
+    if addr.is_ipv4() { 32 } else { 128 }
+}
+
+/// Whether the top `prefix_len` of `bits` bits of `a` and `b` are equal
+fn prefix_matches(a: u128, b: u128, bits: u8, prefix_len: u8) -> bool {
+    let host_bits = u32::from(bits - prefix_len);
+    a.checked_shr(host_bits).unwrap_or(0) == b.checked_shr(host_bits).unwrap_or(0)
+}
+
+impl FromStr for IpNet {
+    type Err = AdBlockerError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AdBlockerError::Config(format!("invalid IP range `{}`", s));
+        let (addr, prefix_len) = match s.trim().split_once('/') {
+            Some((addr, prefix_len)) => {
+                let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+                (addr, prefix_len.parse().map_err(|_| invalid())?)
+            }
+            None => {
+                let addr: IpAddr = s.trim().parse().map_err(|_| invalid())?;
+                (addr, max_prefix_len(addr))
+            }
+        };
+        Self::new(addr, prefix_len).ok_or_else(invalid)
+    }
+}
+
+impl TryFrom<String> for IpNet {
+    type Error = AdBlockerError;
+    
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<IpNet> for String {
+    fn from(net: IpNet) -> Self {
+        net.to_string()
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+    
+    #[test]
+    fn ipv4_ranges_match_their_addresses() {
+        let net: IpNet = "203.0.113.0/24".parse().unwrap();
+        assert!(net.contains(ip("203.0.113.5")));
+        assert!(net.contains(ip("203.0.113.255")));
+        assert!(!net.contains(ip("203.0.114.5")));
+        assert!(net.contains(ip("::ffff:203.0.113.5")));
+        assert!(!net.contains(ip("::ffff:203.0.114.5")));
+        
+        let everything: IpNet = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.contains(ip("198.51.100.1")));
+    }
+    
+    #[test]
+    fn ipv6_ranges_match_their_addresses() {
+        let net: IpNet = "2001:db8::/32".parse().unwrap();
+        assert!(net.contains(ip("2001:db8:1::1")));
+        assert!(!net.contains(ip("2001:db9::1")));
+        assert!(!net.contains(ip("203.0.113.5")));
+    }
+    
+    #[test]
+    fn bare_addresses_are_single_hosts() {
+        let net: IpNet = "198.51.100.7".parse().unwrap();
+        assert_eq!(net.to_string(), "198.51.100.7/32");
+        assert!(net.contains(ip("198.51.100.7")));
+        assert!(!net.contains(ip("198.51.100.8")));
+    }
+    
+    #[test]
+    fn rejects_malformed_ranges() {
+        for range in ["203.0.113.0/33", "2001:db8::/129", "ads.example/24", "203.0.113.0/", ""] {
+            assert!(matches!(range.parse::<IpNet>(), Err(AdBlockerError::Config(_))), "{}", range);
+        }
+    }
+}
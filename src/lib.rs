@@ -5,7 +5,7 @@
 //! 
 //! # Quick Start
 //! 
-//! ```rust
+//! ```rust,no_run
 //! use ad_blocker_api::SimpleAdBlocker;
 //! 
 //! #[tokio::main]
@@ -20,18 +20,43 @@
 //! }
 //! ```
 
+pub mod block_page;
 pub mod blocker;
+mod bloom;
 pub mod config;
+pub mod dns;
+pub mod dns_cache;
+mod domain;
+pub mod domain_blocker;
+mod domain_trie;
+pub mod error;
 pub mod filters;
+pub mod ip_net;
+pub mod proxy;
+pub mod rate_limit;
+mod result_cache;
+pub mod schedule;
+pub mod snapshot;
 pub mod types;
 pub mod stevenblack;
+#[cfg(test)]
+mod test_util;
 
-pub use blocker::{AdBlockerAPI, SimpleAdBlocker};
-pub use config::AdBlockerConfig;
-pub use types::{BlockResult, BlockCategory};
-pub use stevenblack::StevenBlackBlocker;
+pub use block_page::{block_page_html, escape_html};
+pub use blocker::{AdBlockerAPI, AutoUpdateHandle, DecisionCallback, SimpleAdBlocker};
+pub use config::{AdBlockerConfig, Precedence};
+pub use dns_cache::DnsCache;
+pub use domain_blocker::DomainBlocker;
+pub use filters::{BodySignatures, CacheStats, FilterSource, FilterSourceSpec};
+pub use ip_net::IpNet;
+pub use rate_limit::RateLimiter;
+pub use schedule::{BlockSchedule, Clock, ScheduleEntry};
+pub use snapshot::{BlockerSnapshot, SnapshotList};
+pub use error::AdBlockerError;
+pub use types::{BlockReason, BlockResult, BlockResultFull, BlockCategory, BodyMatch, Check, CheckOutcome, CheckStatus, ClientStats, Explanation, FilterCounts, FilterKind, LoadSummary, LogEntry, MinuteBucket, PageReport, ResourceType, StatusReport, WindowedStats};
+pub use stevenblack::{DomainDiff, HostLoadResult, SizeInfo, StevenBlackBlocker, diff_domain_sets};
 
 /// Re-export commonly used types
 pub mod prelude {
-    pub use crate::{AdBlockerAPI, SimpleAdBlocker, AdBlockerConfig, BlockResult, BlockCategory, StevenBlackBlocker};
+    pub use crate::{AdBlockerAPI, SimpleAdBlocker, AdBlockerConfig, AdBlockerError, BlockResult, BlockReason, BlockCategory, DomainBlocker, StevenBlackBlocker};
 }
\ No newline at end of file
@@ -0,0 +1,109 @@
+use crate::error::{AdBlockerError, Result};
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, copy_bidirectional};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// How long `tunnel` and `connect_upstream` wait for the target to accept
+/// the connection
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Open a connection to the `host:port` target of a CONNECT request, giving
+/// up after `CONNECT_TIMEOUT` with a `TimedOut` I/O error
+pub async fn connect_upstream(host_port: &str) -> Result<TcpStream> {
+    match timeout(CONNECT_TIMEOUT, TcpStream::connect(host_port)).await {
+        Ok(connected) => Ok(connected?),
+        Err(_) => Err(Error::new(ErrorKind::TimedOut, format!("connecting to {} timed out", host_port)).into()),
+    }
+}
+
+/// Complete an allowed CONNECT request whose request line has already been
+/// read from `client`: connect to `host_port`, answer `200 Connection
+/// Established` and copy bytes both ways until both sides have closed.
+/// Returns the bytes sent from the client to the target and back.
+///
+/// If the target can't be reached the client is sent `502 Bad Gateway`
+/// (`504 Gateway Timeout` after `CONNECT_TIMEOUT`) and the error returned.
+pub async fn tunnel(mut client: TcpStream, host_port: &str) -> Result<(u64, u64)> {
+    let mut target = match connect_upstream(host_port).await {
+        Ok(target) => target,
+        Err(e) => {
+            let status = match &e {
+                AdBlockerError::Io(e) if e.kind() == ErrorKind::TimedOut => "504 Gateway Timeout",
+                _ => "502 Bad Gateway",
+            };
+            let _ = client.write_all(format!("HTTP/1.1 {}\r\n\r\n", status).as_bytes()).await;
+            return Err(e);
+        }
+    };
+    
+    client.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n").await?;
+    Ok(copy_bidirectional(&mut client, &mut target).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    
+    /// Address of a server echoing back whatever each connection sends
+    async fn echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut read, mut write) = stream.split();
+                    let _ = tokio::io::copy(&mut read, &mut write).await;
+                });
+            }
+        });
+        addr.to_string()
+    }
+    
+    /// A proxy accepting one connection and tunnelling it to `target`
+    async fn proxy_to(target: String) -> (String, tokio::task::JoinHandle<Result<(u64, u64)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = tokio::spawn(async move {
+            let (client, _) = listener.accept().await.unwrap();
+            tunnel(client, &target).await
+        });
+        (addr, handle)
+    }
+    
+    #[tokio::test]
+    async fn tunnels_bytes_both_ways() {
+        let (proxy, handle) = proxy_to(echo_server().await).await;
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+        
+        let established = b"HTTP/1.1 200 Connection Established\r\n\r\n";
+        let mut response = vec![0; established.len()];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(response, established);
+        
+        client.write_all(b"hello through the tunnel").await.unwrap();
+        client.shutdown().await.unwrap();
+        let mut echoed = String::new();
+        client.read_to_string(&mut echoed).await.unwrap();
+        assert_eq!(echoed, "hello through the tunnel");
+        
+        assert_eq!(handle.await.unwrap().unwrap(), (24, 24));
+    }
+    
+    #[tokio::test]
+    async fn unreachable_targets_get_a_bad_gateway() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        
+        let (proxy, handle) = proxy_to(closed).await;
+        let mut client = TcpStream::connect(proxy).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert_eq!(response, "HTTP/1.1 502 Bad Gateway\r\n\r\n");
+        assert!(matches!(handle.await.unwrap(), Err(AdBlockerError::Io(_))));
+    }
+}
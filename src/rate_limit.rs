@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Per-client token bucket limiter for DNS servers exposed to the network.
+///
+/// Each client IP gets a bucket holding up to `burst` tokens that refills at
+/// `rate_per_sec`; a query is allowed if it can take a token. Buckets left
+/// idle for longer than the idle timeout are dropped so memory stays bounded
+/// by the number of recently active clients.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate_per_sec: f64,
+    burst: f64,
+    idle_timeout: Duration,
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    buckets: HashMap<IpAddr, Bucket>,
+    last_sweep: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Idle buckets are dropped after this long unless overridden
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+impl RateLimiter {
+    /// Allow each client `rate_per_sec` queries per second on average, with
+    /// bursts of up to `burst` queries
+    pub fn new(rate_per_sec: f64, burst: u32) -> Self {
+        Self {
+            rate_per_sec: rate_per_sec.max(0.0),
+            burst: f64::from(burst.max(1)),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            state: Mutex::new(LimiterState {
+                buckets: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+    
+    /// Drop a client's bucket after it has been idle this long
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+    
+    /// Whether a query from `client_ip` should be answered. Consumes a token
+    /// when it returns `true`.
+    pub fn check(&self, client_ip: IpAddr) -> bool {
+        self.check_at(client_ip, Instant::now())
+    }
+    
+    /// Number of clients currently holding a bucket
+    pub fn tracked_clients(&self) -> usize {
+        self.state.lock().unwrap().buckets.len()
+    }
+    
+    fn check_at(&self, client_ip: IpAddr, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        
+        if now.saturating_duration_since(state.last_sweep) >= self.idle_timeout {
+            let idle_timeout = self.idle_timeout;
+            state.buckets.retain(|_, bucket| now.saturating_duration_since(bucket.last_refill) < idle_timeout);
+            state.last_sweep = now;
+        }
+        
+        let bucket = state.buckets.entry(client_ip).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+        
+        let elapsed = now.saturating_duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate_per_sec).min(self.burst);
+        bucket.last_refill = now;
+        
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 0, 2, last])
+    }
+    
+    #[test]
+    fn rejects_burst_then_allows_after_refill() {
+        let limiter = RateLimiter::new(2.0, 5);
+        let start = Instant::now();
+        
+        for _ in 0..5 {
+            assert!(limiter.check_at(ip(1), start));
+        }
+        assert!(!limiter.check_at(ip(1), start));
+        
+        // Other clients have their own bucket
+        assert!(limiter.check_at(ip(2), start));
+        
+        // Two tokens come back after one second
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.check_at(ip(1), later));
+        assert!(limiter.check_at(ip(1), later));
+        assert!(!limiter.check_at(ip(1), later));
+    }
+    
+    #[test]
+    fn refill_is_capped_at_burst() {
+        let limiter = RateLimiter::new(10.0, 3);
+        let start = Instant::now();
+        assert!(limiter.check_at(ip(1), start));
+        
+        let later = start + Duration::from_secs(60);
+        let allowed = (0..10).filter(|_| limiter.check_at(ip(1), later)).count();
+        assert_eq!(allowed, 3);
+    }
+    
+    #[test]
+    fn idle_buckets_expire() {
+        let limiter = RateLimiter::new(1.0, 1).with_idle_timeout(Duration::from_secs(10));
+        let start = Instant::now();
+        limiter.check_at(ip(1), start);
+        limiter.check_at(ip(2), start + Duration::from_secs(8));
+        assert_eq!(limiter.tracked_clients(), 2);
+        
+        limiter.check_at(ip(3), start + Duration::from_secs(12));
+        assert_eq!(limiter.tracked_clients(), 2);
+        assert!(limiter.check_at(ip(1), start + Duration::from_secs(12)));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::types::BlockResult;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A checked URL and the page that requested it
+type CacheKey = (String, Option<String>);
+
+/// Bounded LRU cache of `should_block` verdicts.
+///
+/// Entries are tagged with the filter generation they were computed under;
+/// looking up or storing with a newer generation drops everything cached
+/// before it, so rebuilding the engine invalidates the whole cache.
+#[derive(Debug)]
+pub(crate) struct ResultCache {
+    capacity: usize,
+    /// Lookups answered from the cache, only counted for tests
+    #[cfg(test)]
+    hits: AtomicU64,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, (BlockResult, u64)>,
+    /// Keys by when they were last used, least recently used first
+    recency: BTreeMap<u64, CacheKey>,
+    next_use: u64,
+    generation: u64,
+}
+
+impl CacheState {
+    /// Drop every entry if they were computed under an older generation.
+    /// Returns `false` if `generation` is the older one.
+    fn sync_generation(&mut self, generation: u64) -> bool {
+        if generation > self.generation {
+            self.entries.clear();
+            self.recency.clear();
+            self.generation = generation;
+        }
+        generation == self.generation
+    }
+    
+    fn touch(&mut self) -> u64 {
+        self.next_use += 1;
+        self.next_use
+    }
+}
+
+impl ResultCache {
+    /// A cache holding up to `capacity` verdicts; 0 disables it
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            #[cfg(test)]
+            hits: AtomicU64::new(0),
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+    
+    pub(crate) fn get(&self, url: &str, source_url: Option<&str>, generation: u64) -> Option<BlockResult> {
+        if self.capacity == 0 {
+            return None;
+        }
+        
+        let mut state = self.state.lock().unwrap();
+        if !state.sync_generation(generation) {
+            return None;
+        }
+        let key = (url.to_string(), source_url.map(str::to_string));
+        let last_used = state.entries.get(&key)?.1;
+        
+        let used = state.touch();
+        state.recency.remove(&last_used);
+        state.recency.insert(used, key.clone());
+        let entry = state.entries.get_mut(&key)?;
+        entry.1 = used;
+        
+        #[cfg(test)]
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(entry.0.clone())
+    }
+    
+    pub(crate) fn insert(&self, url: &str, source_url: Option<&str>, result: &BlockResult, generation: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        
+        let mut state = self.state.lock().unwrap();
+        if !state.sync_generation(generation) {
+            return;
+        }
+        let key = (url.to_string(), source_url.map(str::to_string));
+        let used = state.touch();
+        if let Some((_, last_used)) = state.entries.insert(key.clone(), (result.clone(), used)) {
+            state.recency.remove(&last_used);
+        }
+        state.recency.insert(used, key);
+        
+        while state.entries.len() > self.capacity {
+            let Some((_, oldest)) = state.recency.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+    }
+    
+    /// Number of lookups answered from the cache
+    #[cfg(test)]
+    pub(crate) fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{BlockCategory, BlockReason};
+    
+    fn verdict(should_block: bool) -> BlockResult {
+        BlockResult {
+            should_block,
+            would_block: should_block,
+            reason: BlockReason::Clean,
+            filter_matched: None,
+            source_list: None,
+            category: BlockCategory::Clean,
+            is_third_party: false,
+        }
+    }
+    
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        let cache = ResultCache::new(2);
+        cache.insert("https://a.example/", None, &verdict(true), 0);
+        cache.insert("https://b.example/", None, &verdict(false), 0);
+        assert!(cache.get("https://a.example/", None, 0).unwrap().should_block);
+        
+        cache.insert("https://c.example/", None, &verdict(false), 0);
+        assert!(cache.get("https://b.example/", None, 0).is_none());
+        assert!(cache.get("https://a.example/", None, 0).is_some());
+        assert!(cache.get("https://c.example/", None, 0).is_some());
+        assert_eq!(cache.hits(), 3);
+    }
+    
+    #[test]
+    fn source_url_is_part_of_the_key() {
+        let cache = ResultCache::new(4);
+        cache.insert("https://a.example/", Some("https://news.example/"), &verdict(true), 0);
+        assert!(cache.get("https://a.example/", None, 0).is_none());
+        assert!(cache.get("https://a.example/", Some("https://news.example/"), 0).is_some());
+    }
+    
+    #[test]
+    fn newer_generation_drops_everything() {
+        let cache = ResultCache::new(4);
+        cache.insert("https://a.example/", None, &verdict(true), 0);
+        assert!(cache.get("https://a.example/", None, 1).is_none());
+        
+        // Verdicts computed before the rebuild aren't stored
+        cache.insert("https://a.example/", None, &verdict(true), 0);
+        assert!(cache.get("https://a.example/", None, 1).is_none());
+    }
+    
+    #[test]
+    fn zero_capacity_disables_caching() {
+        let cache = ResultCache::new(0);
+        cache.insert("https://a.example/", None, &verdict(true), 0);
+        assert!(cache.get("https://a.example/", None, 0).is_none());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::types::BlockCategory;
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// Time-of-day restrictions on when categories are blocked.
+///
+/// A category with no entries is always blocked. A category with entries is
+/// only blocked while at least one of them is active, and is treated as
+/// disabled the rest of the time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockSchedule {
+    pub entries: Vec<ScheduleEntry>,
+}
+
+/// A window during which a category is blocked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    pub category: BlockCategory,
+    /// Days the window applies to; the day is the one the window starts on
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    /// End of the window, exclusive. An end before `start` runs past midnight.
+    pub end: NaiveTime,
+}
+
+impl BlockSchedule {
+    /// Whether `category` should be blocked at the given local time
+    pub fn is_active(&self, category: BlockCategory, now: NaiveDateTime) -> bool {
+        let mut entries = self.entries.iter().filter(|entry| entry.category == category).peekable();
+        if entries.peek().is_none() {
+            return true;
+        }
+        entries.any(|entry| entry.covers(now))
+    }
+}
+
+impl ScheduleEntry {
+    fn covers(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let today = now.weekday();
+        
+        if self.start <= self.end {
+            self.days.contains(&today) && self.start <= time && time < self.end
+        } else {
+            // Overnight window: the evening belongs to today's entry, the
+            // early morning to yesterday's
+            (self.days.contains(&today) && time >= self.start)
+                || (self.days.contains(&today.pred()) && time < self.end)
+        }
+    }
+}
+
+/// Source of the current local time, replaceable so schedules can be tested
+pub trait Clock: Send + Sync {
+    fn now(&self) -> NaiveDateTime;
+    
+    /// The current time on the system clock, for measuring how long ago
+    /// something happened without the jumps local time makes at DST changes
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// The system clock in the local time zone
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalClock;
+
+impl Clock for LocalClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::september;
+    
+    fn entry(category: BlockCategory, days: &[Weekday], start: (u32, u32), end: (u32, u32)) -> ScheduleEntry {
+        ScheduleEntry {
+            category,
+            days: days.to_vec(),
+            start: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+        }
+    }
+    
+    #[test]
+    fn daytime_windows_cover_their_days_and_hours() {
+        let schedule = BlockSchedule { entries: vec![entry(BlockCategory::Social, &[Weekday::Mon, Weekday::Tue], (9, 0), (17, 0))] };
+        
+        // September 1st 2026 is a Tuesday
+        assert!(schedule.is_active(BlockCategory::Social, september(1, 9, 0)));
+        assert!(!schedule.is_active(BlockCategory::Social, september(1, 17, 0)));
+        assert!(!schedule.is_active(BlockCategory::Social, september(2, 12, 0)));
+        assert!(schedule.is_active(BlockCategory::Advertisement, september(2, 12, 0)));
+    }
+    
+    #[test]
+    fn overnight_windows_belong_to_the_day_they_start() {
+        let schedule = BlockSchedule { entries: vec![entry(BlockCategory::Social, &[Weekday::Fri], (22, 0), (6, 0))] };
+        
+        // Friday the 4th at 23:00, then the early hours of Saturday the 5th
+        assert!(schedule.is_active(BlockCategory::Social, september(4, 23, 0)));
+        assert!(schedule.is_active(BlockCategory::Social, september(5, 5, 59)));
+        assert!(!schedule.is_active(BlockCategory::Social, september(5, 6, 0)));
+        // Friday morning belongs to Thursday's window, which doesn't exist
+        assert!(!schedule.is_active(BlockCategory::Social, september(4, 3, 0)));
+    }
+}
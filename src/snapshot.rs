@@ -0,0 +1,29 @@
+use crate::config::AdBlockerConfig;
+use crate::types::BlockCategory;
+use serde::{Deserialize, Serialize};
+
+/// The logical state of an `AdBlockerAPI`, from `AdBlockerAPI::snapshot`:
+/// the rules of every loaded list plus the handle's configuration.
+///
+/// Unlike the compiled engine this is plain data, so it can be saved as
+/// JSON, diffed, and turned back into an identical blocker with
+/// `AdBlockerAPI::from_snapshot` without network access.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockerSnapshot {
+    /// Configuration of the handle the snapshot was taken from, including
+    /// its whitelist and toggles. `custom_filters` holds every custom rule
+    /// in the engine, including ones read from `custom_filter_dirs` or added
+    /// at runtime, so `custom_filter_dirs` is left empty.
+    pub config: AdBlockerConfig,
+    /// Rules from the downloaded lists, sorted by list name
+    pub lists: Vec<SnapshotList>,
+}
+
+/// The rules one list contributed, sorted. A rule found in several lists
+/// only appears under the list it is attributed to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotList {
+    pub name: String,
+    pub category: BlockCategory,
+    pub rules: Vec<String>,
+}
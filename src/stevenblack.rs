@@ -1,13 +1,254 @@
-use anyhow::Result;
-use reqwest;
-use std::collections::HashSet;
+use crate::domain::normalize_domain;
+use crate::domain_trie::DomainTrie;
+use crate::error::Result;
+use crate::types::LoadSummary;
+use futures_util::StreamExt;
+use regex::{Regex, RegexSet};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::sync::RwLock;
+use tracing::{debug, info, instrument, warn};
+
+/// Default StevenBlack unified hosts file
+pub const STEVENBLACK_HOSTS_URL: &str = "https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts";
+
+/// First bytes of a file written by `save_binary`
+const BINARY_MAGIC: &[u8; 4] = b"SBHB";
+
+/// Layout version of `save_binary` files; bump when the layout changes so
+/// caches written by older versions are rejected
+const BINARY_VERSION: u8 = 2;
+
+/// Domains added and removed between two versions of a blocklist
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DomainDiff {
+    /// Domains in the new set but not the old, sorted
+    pub added: Vec<String>,
+    /// Domains in the old set but not the new, sorted
+    pub removed: Vec<String>,
+}
+
+/// Compute which domains were added and removed going from `old` to `new`
+pub fn diff_domain_sets(old: &HashSet<String>, new: &HashSet<String>) -> DomainDiff {
+    let mut added: Vec<String> = new.difference(old).cloned().collect();
+    let mut removed: Vec<String> = old.difference(new).cloned().collect();
+    added.sort_unstable();
+    removed.sort_unstable();
+    DomainDiff { added, removed }
+}
+
+/// Blocked domains in a hosts file, normalized with `normalize_domain`
+fn parse_hosts(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .filter_map(hosts_entry)
+        .map(|domain| normalize_domain(domain).into_owned())
+        .collect()
+}
+
+/// The domain blocked by one hosts file line, i.e. one pointed at 0.0.0.0 or
+/// 127.0.0.1, or an adblock-style `||domain^` rule as used by DNS blocklists.
+/// Inline `#` comments and any `:port` after the domain are dropped. A
+/// `*.domain` wildcard is kept as is, blocking only subdomains.
+fn hosts_entry(line: &str) -> Option<&str> {
+    let line = line.split_once('#').map_or(line, |(entry, _)| entry);
+    if let Some(rule) = line.trim().strip_prefix("||") {
+        // Rules with options or a path can't be expressed as a blocked domain
+        let domain = rule.strip_suffix('^')?;
+        return (!domain.is_empty() && !domain.contains(['/', '^', '$', '*'])).then_some(domain);
+    }
+    
+    let mut parts = line.split_whitespace();
+    let (ip, domain) = (parts.next()?, parts.next()?);
+    if ip != "0.0.0.0" && ip != "127.0.0.1" {
+        return None;
+    }
+    
+    let domain = domain.split_once(':').map_or(domain, |(host, _)| host);
+    (!domain.is_empty()).then_some(domain)
+}
+
+/// The regex of a `/regex/` line, matched against whole domains
+fn pattern_entry(line: &str) -> Option<&str> {
+    let pattern = line.trim().strip_prefix('/')?.strip_suffix('/')?;
+    (!pattern.is_empty()).then_some(pattern)
+}
+
+/// Running totals while one hosts file is read
+#[derive(Debug, Default)]
+struct HostsLoad {
+    count: usize,
+    duplicates: u64,
+    /// `/regex/` entries, added to the blocker once the file is read
+    patterns: Vec<Regex>,
+}
+
+impl HostsLoad {
+    /// Read one line of the list `source`. Domains already loaded from the
+    /// `base` list are retagged as coming from `source`, so reloading the base
+    /// list doesn't drop them.
+    fn add_line(&mut self, line: &str, blocked_domains: &mut DomainTrie, source: &str, base: &str) {
+        if let Some(domain) = hosts_entry(line) {
+            let domain = normalize_domain(domain);
+            if !blocked_domains.insert_from(&domain, Some(source)) {
+                blocked_domains.claim(&domain, base, Some(source));
+                self.duplicates += 1;
+            }
+            self.count += 1;
+        } else if let Some(pattern) = pattern_entry(line) {
+            match Regex::new(pattern) {
+                Ok(pattern) => {
+                    self.patterns.push(pattern);
+                    self.count += 1;
+                }
+                Err(e) => debug!(source, pattern, error = %e, "skipping invalid hosts regex"),
+            }
+        }
+    }
+}
+
+/// `/regex/` entries from loaded lists, compiled into one `RegexSet` so a
+/// lookup is a single pass however many patterns are loaded
+#[derive(Debug, Default)]
+struct HostPatterns {
+    /// Each pattern with the list it came from, in `set` order
+    patterns: Vec<(Regex, String)>,
+    set: RegexSet,
+}
+
+impl HostPatterns {
+    fn len(&self) -> usize {
+        self.patterns.len()
+    }
+    
+    fn is_match(&self, domain: &str) -> bool {
+        self.set.is_match(domain)
+    }
+    
+    /// The list of the earliest pattern matching `domain`
+    fn matched_source(&self, domain: &str) -> Option<&str> {
+        let index = self.set.matches(domain).iter().next()?;
+        Some(&self.patterns[index].1)
+    }
+    
+    /// Add patterns from `source`, recompiling the combined set
+    fn extend(&mut self, patterns: Vec<Regex>, source: &str) -> Result<()> {
+        let mut combined = self.patterns.clone();
+        combined.extend(patterns.into_iter().map(|pattern| (pattern, source.to_string())));
+        *self = Self::new(combined)?;
+        Ok(())
+    }
+    
+    /// Replace the patterns from `source` with `patterns`
+    fn replace_source(&mut self, patterns: Vec<Regex>, source: &str) -> Result<()> {
+        let mut combined: Vec<_> = self.patterns.iter().filter(|(_, from)| from != source).cloned().collect();
+        combined.extend(patterns.into_iter().map(|pattern| (pattern, source.to_string())));
+        *self = Self::new(combined)?;
+        Ok(())
+    }
+    
+    fn new(patterns: Vec<(Regex, String)>) -> Result<Self> {
+        let set = RegexSet::new(patterns.iter().map(|(pattern, _)| pattern.as_str()))?;
+        Ok(Self { patterns, set })
+    }
+}
+
+/// Splits a hosts file arriving in chunks into lines, keeping only the
+/// current partial line in memory rather than the whole body
+#[derive(Debug, Default)]
+struct LineSplitter {
+    partial: Vec<u8>,
+}
+
+impl LineSplitter {
+    /// Call `f` with every line completed by `chunk`
+    fn push(&mut self, chunk: &[u8], mut f: impl FnMut(&str)) {
+        let mut rest = chunk;
+        while let Some(newline) = rest.iter().position(|&byte| byte == b'\n') {
+            let (line, tail) = rest.split_at(newline);
+            if self.partial.is_empty() {
+                f(&String::from_utf8_lossy(line));
+            } else {
+                self.partial.extend_from_slice(line);
+                f(&String::from_utf8_lossy(&self.partial));
+                self.partial.clear();
+            }
+            rest = &tail[1..];
+        }
+        self.partial.extend_from_slice(rest);
+    }
+    
+    /// Call `f` with the last line, if the body didn't end with a newline
+    fn finish(self, mut f: impl FnMut(&str)) {
+        if !self.partial.is_empty() {
+            f(&String::from_utf8_lossy(&self.partial));
+        }
+    }
+}
+
+fn write_string(out: &mut impl Write, value: &str) -> std::io::Result<()> {
+    let len = u16::try_from(value.len()).map_err(|_| invalid_data("string too long"))?;
+    out.write_all(&len.to_le_bytes())?;
+    out.write_all(value.as_bytes())
+}
+
+fn read_string(input: &mut impl Read) -> std::io::Result<String> {
+    let mut len = [0; 2];
+    input.read_exact(&mut len)?;
+    let mut value = vec![0; u16::from_le_bytes(len) as usize];
+    input.read_exact(&mut value)?;
+    String::from_utf8(value).map_err(|_| invalid_data("string is not UTF-8"))
+}
+
+fn read_u32(input: &mut impl Read) -> std::io::Result<u32> {
+    let mut value = [0; 4];
+    input.read_exact(&mut value)?;
+    Ok(u32::from_le_bytes(value))
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
 
 /// StevenBlack hosts file integration
 pub struct StevenBlackBlocker {
-    blocked_domains: Arc<RwLock<HashSet<String>>>,
-    stats: Arc<RwLock<BlockStats>>,
+    blocked_domains: Arc<RwLock<DomainTrie>>,
+    /// Domains that are never blocked, kept separately so reloads don't clear them
+    allowed_domains: Arc<RwLock<DomainTrie>>,
+    /// `/regex/` entries from loaded lists, each with the list it came from
+    blocked_patterns: Arc<RwLock<HostPatterns>>,
+    stats: Arc<AtomicStats>,
+    /// Entries read from each hosts file on its latest load, and how many of
+    /// them were duplicates, for `load_summary`
+    load_counts: Arc<RwLock<HashMap<String, (usize, u64)>>>,
+    /// Where `load_stevenblack_hosts` and `reload_base` download the base list
+    base_url: String,
+    /// Client every hosts file is downloaded with
+    client: reqwest::Client,
+}
+
+/// How many domains a `StevenBlackBlocker` holds and roughly how much
+/// memory they take
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeInfo {
+    /// Blocked domains, not counting the allowlist
+    pub domain_count: usize,
+    /// Approximate heap usage of the blocklist and allowlist, in bytes
+    pub approx_bytes: usize,
+}
+
+/// How loading one hosts file went, from `load_additional_hosts`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostLoadResult {
+    pub url: String,
+    /// Entries read from the file, including any read before a failure
+    pub loaded: usize,
+    /// Why the file failed to load, if it did
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -15,16 +256,35 @@ pub struct BlockStats {
     pub total_checks: u64,
     pub blocked_domains: u64,
     pub allowed_domains: u64,
+    /// Number of domains and `/regex/` patterns currently on the blocklist
     pub hosts_loaded: u64,
 }
 
+/// Lock-free counters behind `BlockStats`, so lookups never contend on stats
+#[derive(Debug, Default)]
+struct AtomicStats {
+    total_checks: AtomicU64,
+    blocked_domains: AtomicU64,
+    allowed_domains: AtomicU64,
+    hosts_loaded: AtomicU64,
+    patterns_loaded: AtomicU64,
+}
+
+impl AtomicStats {
+    fn snapshot(&self) -> BlockStats {
+        BlockStats {
+            total_checks: self.total_checks.load(Ordering::Relaxed),
+            blocked_domains: self.blocked_domains.load(Ordering::Relaxed),
+            allowed_domains: self.allowed_domains.load(Ordering::Relaxed),
+            hosts_loaded: self.hosts_loaded.load(Ordering::Relaxed) + self.patterns_loaded.load(Ordering::Relaxed),
+        }
+    }
+}
+
 impl StevenBlackBlocker {
     /// Create new StevenBlack blocker
     pub async fn new() -> Result<Self> {
-        let blocker = Self {
-            blocked_domains: Arc::new(RwLock::new(HashSet::new())),
-            stats: Arc::new(RwLock::new(BlockStats::default())),
-        };
+        let blocker = Self::empty();
         
         // Load default hosts file
         blocker.load_stevenblack_hosts().await?;
@@ -32,142 +292,898 @@ impl StevenBlackBlocker {
         Ok(blocker)
     }
     
+    /// A blocker with nothing loaded yet, to fill with `load_binary`,
+    /// `load_additional_hosts` or `add_blocked_domain` without downloading
+    /// the StevenBlack list
+    pub fn empty() -> Self {
+        Self {
+            blocked_domains: Arc::new(RwLock::new(DomainTrie::new())),
+            allowed_domains: Arc::new(RwLock::new(DomainTrie::new())),
+            blocked_patterns: Arc::new(RwLock::new(HostPatterns::default())),
+            stats: Arc::new(AtomicStats::default()),
+            load_counts: Arc::new(RwLock::new(HashMap::new())),
+            base_url: STEVENBLACK_HOSTS_URL.to_string(),
+            client: reqwest::Client::default(),
+        }
+    }
+    
+    /// Download hosts files with `client`, e.g. one with the proxy, extra
+    /// root certificates and timeout `FilterManager::from_config` uses
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+    
     /// Load StevenBlack hosts file
+    #[instrument(skip(self))]
     pub async fn load_stevenblack_hosts(&self) -> Result<()> {
-        println!("📥 Loading StevenBlack hosts file...");
-        
-        let url = "https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts";
-        let response = reqwest::get(url).await?;
-        let content = response.text().await?;
+        let (count, result) = self.load_hosts_url(&self.base_url).await;
+        result?;
+        info!(domains = count, "loaded StevenBlack hosts");
+        Ok(())
+    }
+    
+    /// Download the StevenBlack hosts file again and swap it in: domains
+    /// dropped from it are removed and new ones added. Domains from
+    /// `load_additional_hosts` or `add_blocked_domain` stay blocked, even
+    /// ones the StevenBlack list also had. The new list is read into a
+    /// separate trie as it arrives, so a failed download leaves the old one
+    /// in place.
+    #[instrument(skip(self))]
+    pub async fn reload_base(&self) -> Result<()> {
+        let response = self.client.get(&self.base_url).send().await?.error_for_status()?;
+        let base = self.base_url.as_str();
+        let staged = RwLock::new(DomainTrie::new());
+        let (load, result) = self.read_hosts_stream(response, base, &staged).await;
+        result?;
+        self.record_load(base, &load).await;
+        let fresh: HashSet<String> = staged.into_inner().domains().into_iter().collect();
         
         let mut blocked_domains = self.blocked_domains.write().await;
-        let mut count = 0;
-        
-        for line in content.lines() {
-            let line = line.trim();
-            
-            // Skip comments and empty lines
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            
-            // Parse hosts file format: "0.0.0.0 domain.com"
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                let ip = parts[0];
-                let domain = parts[1];
-                
-                // Only block domains that point to 0.0.0.0 or 127.0.0.1
-                if ip == "0.0.0.0" || ip == "127.0.0.1" {
-                    blocked_domains.insert(domain.to_lowercase());
-                    count += 1;
-                }
-            }
+        let stale: Vec<String> = blocked_domains
+            .entries()
+            .into_iter()
+            .filter(|(domain, source)| *source == Some(base) && !fresh.contains(domain))
+            .map(|(domain, _)| domain)
+            .collect();
+        for domain in &stale {
+            blocked_domains.remove(domain);
         }
-        
-        // Update stats
-        {
-            let mut stats = self.stats.write().await;
-            stats.hosts_loaded = count;
+        for domain in &fresh {
+            blocked_domains.insert_from(domain, Some(base));
         }
+        self.update_hosts_loaded(&blocked_domains);
+        drop(blocked_domains);
         
-        println!("✅ Loaded {} blocked domains from StevenBlack hosts", count);
+        let mut blocked_patterns = self.blocked_patterns.write().await;
+        blocked_patterns.replace_source(load.patterns, base)?;
+        self.update_patterns_loaded(&blocked_patterns);
+        drop(blocked_patterns);
+        
+        info!(domains = fresh.len(), removed = stale.len(), "reloaded StevenBlack hosts");
         Ok(())
     }
     
     /// Check if domain should be blocked
     pub async fn is_blocked(&self, domain: &str) -> bool {
-        let mut stats = self.stats.write().await;
-        stats.total_checks += 1;
+        self.stats.total_checks.fetch_add(1, Ordering::Relaxed);
+        let domain = normalize_domain(domain);
         
-        let domain_lower = domain.to_lowercase();
-        let blocked_domains = self.blocked_domains.read().await;
+        // Allowlisted domains and their subdomains override the blocklist
+        if self.allowed_domains.read().await.matches(&domain) {
+            self.stats.allowed_domains.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
         
-        // Check exact match
-        if blocked_domains.contains(&domain_lower) {
-            stats.blocked_domains += 1;
+        // Matches the domain itself or any parent (e.g. if "ads.example.com" is
+        // blocked, "banner.ads.example.com" is blocked too)
+        if self.blocked_domains.read().await.matches(&domain) {
+            self.stats.blocked_domains.fetch_add(1, Ordering::Relaxed);
             return true;
         }
         
-        // Check subdomains (e.g., if "ads.example.com" is blocked, block "banner.ads.example.com")
-        let parts: Vec<&str> = domain_lower.split('.').collect();
-        for i in 1..parts.len() {
-            let parent_domain = parts[i..].join(".");
-            if blocked_domains.contains(&parent_domain) {
-                stats.blocked_domains += 1;
-                return true;
-            }
+        if self.blocked_patterns.read().await.is_match(&domain) {
+            self.stats.blocked_domains.fetch_add(1, Ordering::Relaxed);
+            return true;
         }
         
-        stats.allowed_domains += 1;
+        self.stats.allowed_domains.fetch_add(1, Ordering::Relaxed);
         false
     }
     
     /// Check if URL should be blocked
     pub async fn is_url_blocked(&self, url: &str) -> bool {
-        if let Ok(parsed_url) = url::Url::parse(url) {
-            if let Some(domain) = parsed_url.domain() {
-                return self.is_blocked(domain).await;
-            }
+        if let Ok(parsed_url) = url::Url::parse(url)
+            && let Some(domain) = parsed_url.domain()
+        {
+            return self.is_blocked(domain).await;
         }
         false
     }
     
+    /// URL of the hosts list responsible for blocking a domain, if it is
+    /// blocked by a loaded list. Domains added with `add_blocked_domain`
+    /// have no source.
+    pub async fn block_source(&self, domain: &str) -> Option<String> {
+        let domain = normalize_domain(domain);
+        if let Some(source) = self.blocked_domains.read().await.matched_source(&domain) {
+            return Some(source.to_string());
+        }
+        
+        self.blocked_patterns.read().await.matched_source(&domain).map(str::to_string)
+    }
+    
+    /// Sorted snapshot of the blocked domains, leaving out any covered by
+    /// the allowlist. `/regex/` patterns aren't domains, so they aren't
+    /// included; `export_hosts_file` writes them too.
+    pub async fn export_domains(&self) -> Vec<String> {
+        let allowed_domains = self.allowed_domains.read().await;
+        let mut domains = self.blocked_domains.read().await.domains();
+        domains.retain(|domain| !allowed_domains.matches(domain));
+        domains.sort_unstable();
+        domains
+    }
+    
+    /// Write the blocked domains to a hosts file as `0.0.0.0 domain` lines,
+    /// followed by the `/regex/` patterns in the same form they're loaded from
+    pub async fn export_hosts_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let domains = self.export_domains().await;
+        let mut file = BufWriter::new(File::create(path)?);
+        for domain in &domains {
+            writeln!(file, "0.0.0.0 {}", domain)?;
+        }
+        for (pattern, _) in &self.blocked_patterns.read().await.patterns {
+            writeln!(file, "/{}/", pattern.as_str())?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+    
+    /// Save the blocklist, with the source of each domain and pattern, in a
+    /// compact binary form that `load_binary` reads much faster than a hosts
+    /// file can be parsed. The allowlist isn't saved.
+    ///
+    /// The layout is the magic `SBHB` and a version byte, the source names,
+    /// the sorted domains each with the index of its source (0 for none),
+    /// then the `/regex/` patterns each with the index of its source.
+    /// Strings are length-prefixed and integers little-endian.
+    pub async fn save_binary(&self, path: impl AsRef<Path>) -> Result<()> {
+        let blocked_domains = self.blocked_domains.read().await;
+        let blocked_patterns = self.blocked_patterns.read().await;
+        let mut entries = blocked_domains.entries();
+        entries.sort_unstable();
+        
+        let mut sources: Vec<&str> = Vec::new();
+        let mut source_index = HashMap::new();
+        let pattern_sources = blocked_patterns.patterns.iter().map(|(_, source)| source.as_str());
+        for source in entries.iter().filter_map(|(_, source)| *source).chain(pattern_sources) {
+            source_index.entry(source).or_insert_with(|| {
+                sources.push(source);
+                sources.len() as u32
+            });
+        }
+        
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(BINARY_MAGIC)?;
+        file.write_all(&[BINARY_VERSION])?;
+        file.write_all(&(sources.len() as u32).to_le_bytes())?;
+        for source in &sources {
+            write_string(&mut file, source)?;
+        }
+        file.write_all(&(entries.len() as u32).to_le_bytes())?;
+        for (domain, source) in &entries {
+            write_string(&mut file, domain)?;
+            let index = source.map_or(0, |source| source_index[source]);
+            file.write_all(&index.to_le_bytes())?;
+        }
+        file.write_all(&(blocked_patterns.len() as u32).to_le_bytes())?;
+        for (pattern, source) in &blocked_patterns.patterns {
+            write_string(&mut file, pattern.as_str())?;
+            file.write_all(&source_index[source.as_str()].to_le_bytes())?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+    
+    /// Replace the blocklist and patterns with those saved by `save_binary`. Files that are
+    /// truncated, corrupt or from another format version are rejected with
+    /// an `Io` error, leaving the current blocklist in place.
+    pub async fn load_binary(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut header = [0; 5];
+        file.read_exact(&mut header)?;
+        if &header[..4] != BINARY_MAGIC {
+            return Err(invalid_data("not a binary blocklist").into());
+        }
+        if header[4] != BINARY_VERSION {
+            return Err(invalid_data(&format!("unsupported binary blocklist version {}", header[4])).into());
+        }
+        
+        let sources = (0..read_u32(&mut file)?)
+            .map(|_| read_string(&mut file))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let source = |index: u32| sources.get((index as usize).wrapping_sub(1)).ok_or_else(|| invalid_data("source index out of range"));
+        let mut trie = DomainTrie::new();
+        for _ in 0..read_u32(&mut file)? {
+            let domain = read_string(&mut file)?;
+            let source = match read_u32(&mut file)? {
+                0 => None,
+                index => Some(source(index)?),
+            };
+            trie.insert_from(&domain, source.map(String::as_str));
+        }
+        let mut patterns = Vec::new();
+        for _ in 0..read_u32(&mut file)? {
+            let pattern = read_string(&mut file)?;
+            let pattern = Regex::new(&pattern).map_err(|_| invalid_data("invalid pattern"))?;
+            patterns.push((pattern, source(read_u32(&mut file)?)?.clone()));
+        }
+        if file.read(&mut [0])? != 0 {
+            return Err(invalid_data("trailing data after the last pattern").into());
+        }
+        let patterns = HostPatterns::new(patterns)?;
+        
+        let mut blocked_domains = self.blocked_domains.write().await;
+        let mut blocked_patterns = self.blocked_patterns.write().await;
+        *blocked_domains = trie;
+        *blocked_patterns = patterns;
+        self.update_hosts_loaded(&blocked_domains);
+        self.update_patterns_loaded(&blocked_patterns);
+        Ok(())
+    }
+    
+    /// Compare the current blocklist with a freshly downloaded hosts file
+    /// (e.g. `STEVENBLACK_HOSTS_URL`) without loading it
+    pub async fn diff_with_remote(&self, url: &str) -> Result<DomainDiff> {
+        let content = self.client.get(url).send().await?.error_for_status()?.text().await?;
+        let fresh = parse_hosts(&content);
+        let current: HashSet<String> = self.blocked_domains.read().await.domains().into_iter().collect();
+        Ok(diff_domain_sets(&current, &fresh))
+    }
+    
     /// Get statistics
     pub async fn get_stats(&self) -> BlockStats {
-        self.stats.read().await.clone()
+        self.stats.snapshot()
+    }
+    
+    /// Number of blocked domains and the approximate memory used to store
+    /// them, e.g. to monitor growth as more hosts files are loaded
+    pub async fn size_info(&self) -> SizeInfo {
+        let blocked_domains = self.blocked_domains.read().await;
+        let allowed_domains = self.allowed_domains.read().await;
+        SizeInfo {
+            domain_count: blocked_domains.len(),
+            approx_bytes: blocked_domains.approx_bytes() + allowed_domains.approx_bytes(),
+        }
+    }
+    
+    /// How many hosts entries have been loaded and how many were duplicate
+    /// domains collapsed into one, e.g. across overlapping hosts files. Each
+    /// file counts as of its latest load, so reloading it (or `reload_base`)
+    /// replaces its counts rather than adding to them.
+    pub async fn load_summary(&self) -> LoadSummary {
+        let load_counts = self.load_counts.read().await;
+        let total_rules = load_counts.values().map(|(read, _)| read).sum::<usize>();
+        let duplicate_rules = load_counts.values().map(|(_, duplicates)| duplicates).sum::<u64>() as usize;
+        drop(load_counts);
+        LoadSummary {
+            total_rules,
+            unique_rules: total_rules - duplicate_rules,
+            duplicate_rules,
+            exceptions: self.allowed_domains.read().await.len(),
+            ..LoadSummary::default()
+        }
+    }
+    
+    /// Keep `hosts_loaded` in step with the blocklist; called with the write
+    /// lock held so concurrent changes can't store a stale count
+    fn update_hosts_loaded(&self, blocked_domains: &DomainTrie) {
+        self.stats.hosts_loaded.store(blocked_domains.len() as u64, Ordering::Relaxed);
+    }
+    
+    /// Keep the pattern part of `hosts_loaded` in step, like `update_hosts_loaded`
+    fn update_patterns_loaded(&self, blocked_patterns: &HostPatterns) {
+        self.stats.patterns_loaded.store(blocked_patterns.len() as u64, Ordering::Relaxed);
     }
     
     /// Add custom blocked domain
     pub async fn add_blocked_domain(&self, domain: &str) {
+        let domain = normalize_domain(domain);
         let mut blocked_domains = self.blocked_domains.write().await;
-        blocked_domains.insert(domain.to_lowercase());
+        if !blocked_domains.insert(&domain) {
+            blocked_domains.claim(&domain, &self.base_url, None);
+        }
+        self.update_hosts_loaded(&blocked_domains);
     }
     
     /// Remove domain from blocklist
     pub async fn remove_blocked_domain(&self, domain: &str) {
         let mut blocked_domains = self.blocked_domains.write().await;
-        blocked_domains.remove(&domain.to_lowercase());
+        blocked_domains.remove(&normalize_domain(domain));
+        self.update_hosts_loaded(&blocked_domains);
+    }
+    
+    /// Allow a domain and its subdomains even if they are on a blocklist
+    pub async fn add_allowed_domain(&self, domain: &str) {
+        let mut allowed_domains = self.allowed_domains.write().await;
+        allowed_domains.insert(&normalize_domain(domain));
+    }
+    
+    /// Remove domain from allowlist
+    pub async fn remove_allowed_domain(&self, domain: &str) {
+        let mut allowed_domains = self.allowed_domains.write().await;
+        allowed_domains.remove(&normalize_domain(domain));
     }
     
-    /// Load additional hosts files
-    pub async fn load_additional_hosts(&self, urls: Vec<&str>) -> Result<()> {
+    /// Load additional hosts files, one after another. A file that fails to
+    /// load doesn't stop the rest; check the returned results, one per URL
+    /// in order, to see which did.
+    #[instrument(skip(self))]
+    pub async fn load_additional_hosts(&self, urls: Vec<&str>) -> Vec<HostLoadResult> {
+        let mut results = Vec::with_capacity(urls.len());
         for url in urls {
-            println!("📥 Loading additional hosts from: {}", url);
+            debug!(url, "loading additional hosts");
             
-            match reqwest::get(url).await {
-                Ok(response) => {
-                    if let Ok(content) = response.text().await {
-                        let mut blocked_domains = self.blocked_domains.write().await;
-                        let mut count = 0;
-                        
-                        for line in content.lines() {
-                            let line = line.trim();
-                            if line.is_empty() || line.starts_with('#') {
-                                continue;
-                            }
-                            
-                            let parts: Vec<&str> = line.split_whitespace().collect();
-                            if parts.len() >= 2 {
-                                let ip = parts[0];
-                                let domain = parts[1];
-                                
-                                if ip == "0.0.0.0" || ip == "127.0.0.1" {
-                                    blocked_domains.insert(domain.to_lowercase());
-                                    count += 1;
-                                }
-                            }
-                        }
-                        
-                        println!("✅ Loaded {} additional domains from {}", count, url);
-                    }
-                }
+            let (loaded, result) = self.load_hosts_url(url).await;
+            match &result {
+                Ok(()) => info!(url, domains = loaded, "loaded additional hosts"),
+                Err(e) => warn!(url, domains = loaded, error = %e, "failed to load hosts"),
+            }
+            results.push(HostLoadResult {
+                url: url.to_string(),
+                loaded,
+                error: result.err().map(|e| e.to_string()),
+            });
+        }
+        results
+    }
+    
+    /// Download a hosts file and add its domains, returning how many entries
+    /// were read even if the download failed partway
+    async fn load_hosts_url(&self, url: &str) -> (usize, Result<()>) {
+        let response = match self.client.get(url).send().await.and_then(reqwest::Response::error_for_status) {
+            Ok(response) => response,
+            Err(e) => return (0, Err(e.into())),
+        };
+        self.load_hosts_stream(response, url).await
+    }
+    
+    /// Add the domains of a hosts file as its body arrives, tagged with
+    /// `source`. Returns the number of entries read, along with any error
+    /// reading the body; domains and patterns read before the error stay loaded.
+    async fn load_hosts_stream(&self, response: reqwest::Response, source: &str) -> (usize, Result<()>) {
+        let (load, mut result) = self.read_hosts_stream(response, source, &self.blocked_domains).await;
+        self.update_hosts_loaded(&*self.blocked_domains.write().await);
+        self.record_load(source, &load).await;
+        
+        if !load.patterns.is_empty() {
+            let mut blocked_patterns = self.blocked_patterns.write().await;
+            if let Err(e) = blocked_patterns.extend(load.patterns, source) {
+                result = result.and(Err(e));
+            }
+            self.update_patterns_loaded(&blocked_patterns);
+        }
+        (load.count, result)
+    }
+    
+    /// Keep the entry and duplicate counts of `source`'s latest load
+    async fn record_load(&self, source: &str, load: &HostsLoad) {
+        self.load_counts.write().await.insert(source.to_string(), (load.count, load.duplicates));
+    }
+    
+    /// Read the domains of a hosts file into `blocked_domains` as its body
+    /// arrives, tagged with `source`. The `/regex/` entries are returned in the `HostsLoad` for the caller to add.
+    async fn read_hosts_stream(&self, response: reqwest::Response, source: &str, blocked_domains: &RwLock<DomainTrie>) -> (HostsLoad, Result<()>) {
+        let mut body = response.bytes_stream();
+        let mut lines = LineSplitter::default();
+        let mut load = HostsLoad::default();
+        
+        let mut result = Ok(());
+        while let Some(chunk) = body.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
                 Err(e) => {
-                    eprintln!("⚠️  Failed to load hosts from {}: {}", url, e);
+                    result = Err(e.into());
+                    break;
+                }
+            };
+            // Locked per chunk so lookups aren't held up for the whole download
+            let mut blocked_domains = blocked_domains.write().await;
+            lines.push(&chunk, |line| load.add_line(line, &mut blocked_domains, source, &self.base_url));
+        }
+        
+        if result.is_ok() {
+            let mut blocked_domains = blocked_domains.write().await;
+            lines.finish(|line| load.add_line(line, &mut blocked_domains, source, &self.base_url));
+        }
+        (load, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AdBlockerError;
+    use crate::test_util::{MockResponse, MockServer, closed_url, temp_path};
+    use crate::{AdBlockerConfig, DomainBlocker, SimpleAdBlocker};
+    
+    fn blocker() -> StevenBlackBlocker {
+        StevenBlackBlocker::empty()
+    }
+    
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_checks_are_all_counted() {
+        let blocker = Arc::new(blocker());
+        blocker.add_blocked_domain("ads.example.com").await;
+        
+        let tasks: Vec<_> = (0..32)
+            .map(|task| {
+                let blocker = blocker.clone();
+                tokio::spawn(async move {
+                    for i in 0..500 {
+                        let domain = if i % 2 == 0 { "ads.example.com".to_string() } else { format!("site{}.example.org", task) };
+                        blocker.is_blocked(&domain).await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        
+        let stats = blocker.get_stats().await;
+        assert_eq!(stats.total_checks, 32 * 500);
+        assert_eq!(stats.blocked_domains, 32 * 250);
+        assert_eq!(stats.allowed_domains, 32 * 250);
+    }
+    
+    #[tokio::test]
+    async fn allowlist_covers_subdomains_and_survives_loads() {
+        let server = MockServer::start(vec![(
+            "/hosts",
+            vec![MockResponse::ok("0.0.0.0 example.com\n0.0.0.0 ads.example.com\n0.0.0.0 tracker.test\n")],
+        )])
+        .await;
+        let blocker = blocker();
+        blocker.add_allowed_domain("example.com").await;
+        blocker.load_additional_hosts(vec![&server.url("/hosts")]).await;
+        
+        assert!(!blocker.is_blocked("example.com").await);
+        assert!(!blocker.is_blocked("ads.example.com").await);
+        assert!(blocker.is_blocked("tracker.test").await);
+        
+        blocker.remove_allowed_domain("example.com").await;
+        assert!(blocker.is_blocked("ads.example.com").await);
+    }
+    
+    #[tokio::test]
+    async fn block_source_names_the_list_a_domain_came_from() {
+        let server = MockServer::start(vec![
+            ("/first", vec![MockResponse::ok("0.0.0.0 shared.test\n0.0.0.0 first-only.test\n")]),
+            ("/second", vec![MockResponse::ok("0.0.0.0 shared.test\n0.0.0.0 second-only.test\n")]),
+        ])
+        .await;
+        let (first, second) = (server.url("/first"), server.url("/second"));
+        let blocker = blocker();
+        blocker.load_additional_hosts(vec![&first, &second]).await;
+        blocker.add_blocked_domain("custom.test").await;
+        
+        assert_eq!(blocker.block_source("first-only.test").await, Some(first.clone()));
+        assert_eq!(blocker.block_source("cdn.second-only.test").await, Some(second));
+        assert_eq!(blocker.block_source("shared.test").await, Some(first));
+        assert_eq!(blocker.block_source("custom.test").await, None);
+        assert_eq!(blocker.block_source("unlisted.test").await, None);
+    }
+    
+    #[tokio::test]
+    async fn unicode_and_punycode_entries_match_each_other() {
+        let blocker = blocker();
+        blocker.add_blocked_domain("xn--bcher-kva.example").await;
+        blocker.add_blocked_domain("münchen.example").await;
+        
+        assert!(blocker.is_blocked("bücher.example").await);
+        assert!(blocker.is_blocked("shop.BÜCHER.example").await);
+        assert!(blocker.is_blocked("xn--mnchen-3ya.example").await);
+        assert!(!blocker.is_blocked("bucher.example").await);
+    }
+    
+    #[tokio::test]
+    async fn fully_qualified_and_mixed_case_names_match() {
+        let blocker = blocker();
+        blocker.add_blocked_domain("doubleclick.net").await;
+        blocker.add_allowed_domain("Safe.Example.").await;
+        blocker.add_blocked_domain("safe.example").await;
+        
+        assert!(blocker.is_blocked("doubleclick.net.").await);
+        assert!(blocker.is_blocked("Stats.DoubleClick.NET").await);
+        assert!(blocker.is_blocked("Doubleclick.NET.").await);
+        assert!(!blocker.is_blocked("safe.example").await);
+    }
+    
+    #[tokio::test]
+    async fn exported_hosts_files_load_back_identically() {
+        let (blocker, reloaded) = (blocker(), blocker());
+        for domain in ["tracker.test", "ads.example.com", "Pixel.Example.NET", "xn--bcher-kva.example"] {
+            blocker.add_blocked_domain(domain).await;
+        }
+        blocker.add_allowed_domain("example.com").await;
+        let patterns = MockServer::start(vec![("/patterns", vec![MockResponse::ok("/^ad[0-9]+\\.test$/\n")])]).await;
+        blocker.load_additional_hosts(vec![&patterns.url("/patterns")]).await;
+        let exported = blocker.export_domains().await;
+        assert_eq!(exported, ["pixel.example.net", "tracker.test", "xn--bcher-kva.example"]);
+        assert_eq!(blocker.get_stats().await.hosts_loaded, 5);
+        
+        let path = temp_path("export.hosts");
+        blocker.export_hosts_file(&path).await.unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(written.lines().all(|line| line.starts_with("0.0.0.0 ") || line.starts_with('/')));
+        
+        let server = MockServer::start(vec![("/hosts", vec![MockResponse::ok(written)])]).await;
+        reloaded.load_additional_hosts(vec![&server.url("/hosts")]).await;
+        assert_eq!(reloaded.export_domains().await, exported);
+        assert!(reloaded.is_blocked("ad1.test").await);
+    }
+    
+    const HOSTS_V1: &str = "# hosts v1\n0.0.0.0 ads.example.com\n0.0.0.0 tracker.test\n127.0.0.1 old-miner.test\n";
+    const HOSTS_V2: &str = "# hosts v2\n0.0.0.0 ads.example.com\n0.0.0.0 Tracker.Test # still listed\n0.0.0.0 new-pixel.test\n0.0.0.0 new-beacon.test\n";
+    
+    #[tokio::test]
+    async fn binary_blocklists_round_trip() {
+        let hosts = format!("{}\n/^track[0-9]+\\.test$/\n", large_hosts_file(3_000));
+        let server = MockServer::start(vec![
+            ("/hosts", vec![MockResponse::ok(hosts)]),
+            ("/stale", vec![MockResponse::ok("/^stale[0-9]+\\.test$/\n")]),
+        ])
+        .await;
+        let url = server.url("/hosts");
+        let blocker = blocker();
+        blocker.load_additional_hosts(vec![&url]).await;
+        blocker.add_blocked_domain("custom.test").await;
+        
+        let path = temp_path("blocklist.bin");
+        blocker.save_binary(&path).await.unwrap();
+        let loaded = StevenBlackBlocker::empty();
+        loaded.add_blocked_domain("stale.test").await;
+        loaded.load_additional_hosts(vec![&server.url("/stale")]).await;
+        let result = loaded.load_binary(&path).await;
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+        
+        assert_eq!(loaded.export_domains().await, blocker.export_domains().await);
+        assert_eq!(loaded.block_source("cdn.ads0.example.com").await, Some(url.clone()));
+        assert_eq!(loaded.block_source("custom.test").await, None);
+        assert!(!loaded.is_blocked("stale.test").await);
+        assert!(!loaded.is_blocked("stale1.test").await);
+        assert!(loaded.is_blocked("track7.test").await);
+        assert_eq!(loaded.block_source("track7.test").await, Some(url));
+        assert_eq!(loaded.get_stats().await.hosts_loaded, blocker.get_stats().await.hosts_loaded);
+    }
+    
+    #[tokio::test]
+    async fn corrupt_and_old_binary_blocklists_are_rejected() {
+        let blocker = blocker();
+        blocker.add_blocked_domain("ads.example").await;
+        let path = temp_path("saved.bin");
+        blocker.save_binary(&path).await.unwrap();
+        let saved = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        
+        let mut old_version = saved.clone();
+        old_version[4] = 0;
+        let mut bad_source = saved.clone();
+        let len = bad_source.len();
+        // The last domain's source index, just before the empty pattern count
+        bad_source[len - 8..len - 4].copy_from_slice(&7u32.to_le_bytes());
+        let corrupt = [
+            saved[..saved.len() - 3].to_vec(),
+            old_version,
+            bad_source,
+            b"0.0.0.0 ads.example\n".to_vec(),
+            [saved.as_slice(), b"x"].concat(),
+        ];
+        
+        let target = StevenBlackBlocker::empty();
+        target.add_blocked_domain("kept.example").await;
+        for (i, bytes) in corrupt.iter().enumerate() {
+            let path = temp_path(&format!("corrupt-{}.bin", i));
+            std::fs::write(&path, bytes).unwrap();
+            let result = target.load_binary(&path).await;
+            std::fs::remove_file(&path).unwrap();
+            assert!(matches!(result, Err(AdBlockerError::Io(_))), "case {}: {:?}", i, result);
+        }
+        assert_eq!(target.export_domains().await, ["kept.example"]);
+    }
+    
+    #[tokio::test]
+    async fn size_info_follows_additions_and_removals() {
+        let blocker = blocker();
+        let empty = blocker.size_info().await;
+        assert_eq!(empty.domain_count, 0);
+        
+        for domain in ["ads.example.com", "tracker.test", "ads.example.com"] {
+            blocker.add_blocked_domain(domain).await;
+        }
+        let loaded = blocker.size_info().await;
+        assert_eq!(loaded.domain_count, 2);
+        assert!(loaded.approx_bytes > empty.approx_bytes);
+        assert_eq!(blocker.get_stats().await.hosts_loaded, 2);
+        
+        blocker.remove_blocked_domain("tracker.test").await;
+        assert_eq!(blocker.size_info().await.domain_count, 1);
+        assert_eq!(blocker.get_stats().await.hosts_loaded, 1);
+    }
+    
+    /// A hosts file of `lines` entries mixing comments, ports, blank lines,
+    /// CRLF endings, duplicates and non-ASCII names
+    fn large_hosts_file(lines: usize) -> String {
+        let mut hosts = String::from("# Title: fixture\n\n");
+        for i in 0..lines {
+            match i % 6 {
+                0 => hosts.push_str(&format!("0.0.0.0 ads{}.example.com\n", i)),
+                1 => hosts.push_str(&format!("127.0.0.1 tracker{}.test # inline comment\r\n", i)),
+                2 => hosts.push_str(&format!("0.0.0.0 pixel{}.example.net:8080\n", i)),
+                3 => hosts.push_str(&format!("0.0.0.0 cdn{}.example.org\r\n# a comment line\n\n", i)),
+                4 => hosts.push_str(&format!("0.0.0.0 bücher{}.example\n", i)),
+                _ => hosts.push_str(&format!("0.0.0.0 ads{}.example.com\n", i - 5)),
+            }
+        }
+        hosts.push_str("0.0.0.0 no-trailing-newline.test");
+        hosts
+    }
+    
+    #[test]
+    fn chunked_lines_match_whole_body_lines() {
+        let hosts = large_hosts_file(600);
+        let expected = parse_hosts(&hosts);
+        
+        for chunk_size in [1, 7, 64, 4096] {
+            let mut splitter = LineSplitter::default();
+            let mut domains = HashSet::new();
+            let mut add = |line: &str| {
+                if let Some(domain) = hosts_entry(line) {
+                    domains.insert(normalize_domain(domain).into_owned());
                 }
+            };
+            for chunk in hosts.as_bytes().chunks(chunk_size) {
+                splitter.push(chunk, &mut add);
             }
+            splitter.finish(&mut add);
+            assert_eq!(domains, expected, "chunk size {chunk_size}");
         }
+    }
+    
+    #[tokio::test]
+    async fn streamed_hosts_files_match_the_whole_body_parser() {
+        let hosts = large_hosts_file(60_000);
+        let expected = parse_hosts(&hosts);
+        let server = MockServer::start(vec![("/hosts", vec![MockResponse::ok(hosts)])]).await;
         
-        Ok(())
+        let blocker = blocker();
+        blocker.load_additional_hosts(vec![&server.url("/hosts")]).await;
+        
+        let loaded: HashSet<String> = blocker.export_domains().await.into_iter().collect();
+        assert_eq!(loaded, expected);
+        assert_eq!(blocker.get_stats().await.hosts_loaded, expected.len() as u64);
+    }
+    
+    #[tokio::test]
+    async fn each_hosts_url_reports_its_own_result() {
+        let server = MockServer::start(vec![("/good", vec![MockResponse::ok("0.0.0.0 ads.example\n0.0.0.0 pixel.example\n")])]).await;
+        let unreachable = closed_url().await;
+        let (missing, good) = (server.url("/missing"), server.url("/good"));
+        let blocker = blocker();
+        
+        let results = blocker.load_additional_hosts(vec![&unreachable, &missing, &good]).await;
+        
+        let urls: Vec<&str> = results.iter().map(|result| result.url.as_str()).collect();
+        assert_eq!(urls, [unreachable.as_str(), missing.as_str(), good.as_str()]);
+        assert_eq!((results[0].loaded, results[0].error.is_some()), (0, true));
+        assert!(results[1].error.as_deref().is_some_and(|error| error.contains("404")), "{:?}", results[1]);
+        assert_eq!(results[2], HostLoadResult { url: good.clone(), loaded: 2, error: None });
+        assert!(blocker.is_blocked("pixel.example").await);
+    }
+    
+    #[tokio::test]
+    async fn overlapping_hosts_files_report_duplicates() {
+        let server = MockServer::start(vec![
+            ("/first", vec![MockResponse::ok("0.0.0.0 ads.example\n0.0.0.0 pixel.example\n0.0.0.0 ads.example\n")]),
+            ("/second", vec![MockResponse::ok("0.0.0.0 PIXEL.example\n0.0.0.0 tracker.example\n")]),
+        ]).await;
+        let blocker = blocker();
+        blocker.add_allowed_domain("cdn.example").await;
+        blocker.load_additional_hosts(vec![&server.url("/first"), &server.url("/second")]).await;
+        
+        let summary = blocker.load_summary().await;
+        assert_eq!(summary.total_rules, 5);
+        assert_eq!(summary.unique_rules, 3);
+        assert_eq!(summary.duplicate_rules, 2);
+        assert_eq!(summary.exceptions, 1);
+        assert_eq!(blocker.size_info().await.domain_count, 3);
+    }
+    
+    #[test]
+    fn hosts_entries_drop_comments_and_ports() {
+        for line in [
+            "0.0.0.0 ads.example.com",
+            "  0.0.0.0\tads.example.com  ",
+            "0.0.0.0 ads.example.com #ad network",
+            "0.0.0.0 ads.example.com#ad",
+            "0.0.0.0 ads.example.com:8080",
+            "127.0.0.1 ads.example.com:8080 # with port",
+            "||ads.example.com^",
+            "  ||ads.example.com^ # AdGuard-style",
+        ] {
+            assert_eq!(hosts_entry(line), Some("ads.example.com"), "{line:?}");
+        }
+        
+        for line in [
+            "# 0.0.0.0 ads.example.com",
+            "0.0.0.0 #ad",
+            "0.0.0.0",
+            "0.0.0.0 :8080",
+            "10.0.0.1 router.lan",
+            "",
+            "||^",
+            "||ads.example.com^$third-party",
+            "||ads.example.com/banner^",
+            "! ||ads.example.com^",
+        ] {
+            assert_eq!(hosts_entry(line), None, "{line:?}");
+        }
+    }
+    
+    #[tokio::test]
+    async fn reloading_the_base_list_keeps_other_domains() {
+        let server = MockServer::start(vec![
+            ("/base", vec![
+                MockResponse::ok("0.0.0.0 old-ad.example\n0.0.0.0 shared.example\n0.0.0.0 custom.example\n0.0.0.0 kept.example\n"),
+                MockResponse::ok("0.0.0.0 kept.example\n0.0.0.0 new-ad.example\n"),
+                MockResponse::status(500),
+            ]),
+            ("/extra", vec![MockResponse::ok("0.0.0.0 shared.example\n0.0.0.0 extra.example\n")]),
+        ])
+        .await;
+        let mut blocker = blocker();
+        blocker.base_url = server.url("/base");
+        blocker.load_stevenblack_hosts().await.unwrap();
+        blocker.load_additional_hosts(vec![&server.url("/extra")]).await;
+        blocker.add_blocked_domain("custom.example").await;
+        blocker.add_blocked_domain("mine.example").await;
+        
+        blocker.reload_base().await.unwrap();
+        assert!(!blocker.is_blocked("old-ad.example").await);
+        assert!(blocker.is_blocked("new-ad.example").await);
+        assert!(blocker.is_blocked("kept.example").await);
+        assert!(blocker.is_blocked("shared.example").await);
+        assert!(blocker.is_blocked("extra.example").await);
+        assert!(blocker.is_blocked("custom.example").await);
+        assert!(blocker.is_blocked("mine.example").await);
+        assert_eq!(blocker.get_stats().await.hosts_loaded, 6);
+        // The base list's counts are replaced, not added to
+        let summary = blocker.load_summary().await;
+        assert_eq!((summary.total_rules, summary.duplicate_rules), (4, 1));
+        
+        // A failed reload keeps the previous base list
+        assert!(blocker.reload_base().await.is_err());
+        assert!(blocker.is_blocked("new-ad.example").await);
+        assert_eq!(blocker.get_stats().await.hosts_loaded, 6);
+        assert_eq!(blocker.load_summary().await.total_rules, 4);
+    }
+    
+    #[tokio::test]
+    async fn wildcard_entries_block_only_subdomains() {
+        let server = MockServer::start(vec![
+            ("/wildcard", vec![MockResponse::ok("0.0.0.0 *.doubleclick.net\n")]),
+            ("/bare", vec![MockResponse::ok("0.0.0.0 doubleclick.net\n")]),
+        ])
+        .await;
+        let blocker = blocker();
+        blocker.load_additional_hosts(vec![&server.url("/wildcard")]).await;
+        
+        assert!(blocker.is_blocked("ads.doubleclick.net").await);
+        assert!(!blocker.is_blocked("doubleclick.net").await);
+        assert_eq!(blocker.export_domains().await, ["*.doubleclick.net"]);
+        
+        blocker.load_additional_hosts(vec![&server.url("/bare")]).await;
+        assert!(blocker.is_blocked("doubleclick.net").await);
+        assert_eq!(blocker.get_stats().await.hosts_loaded, 2);
+    }
+    
+    #[tokio::test]
+    async fn reloading_the_base_list_keeps_domains_added_over_its_wildcards() {
+        let server = MockServer::start(vec![
+            ("/base", vec![
+                MockResponse::ok("0.0.0.0 *.tracker.example\n0.0.0.0 ads.example\n"),
+                MockResponse::ok("0.0.0.0 ads.example\n"),
+            ]),
+            ("/extra", vec![MockResponse::ok("0.0.0.0 tracker.example\n")]),
+        ])
+        .await;
+        let extra = server.url("/extra");
+        let mut blocker = blocker();
+        blocker.base_url = server.url("/base");
+        blocker.load_stevenblack_hosts().await.unwrap();
+        blocker.load_additional_hosts(vec![&extra]).await;
+        
+        blocker.reload_base().await.unwrap();
+        assert!(blocker.is_blocked("tracker.example").await);
+        assert_eq!(blocker.block_source("tracker.example").await, Some(extra));
+        assert_eq!(blocker.export_domains().await, ["ads.example", "tracker.example"]);
+    }
+    
+    #[tokio::test]
+    async fn adblock_style_and_regex_entries_block() {
+        let list = "! Title: DNS filter\n||ads.example^\n/^track[0-9]+\\.example\\.net$/\n/[unclosed/\n0.0.0.0 pixel.test\n";
+        let server = MockServer::start(vec![("/dns", vec![MockResponse::ok(list)])]).await;
+        let url = server.url("/dns");
+        let blocker = blocker();
+        let results = blocker.load_additional_hosts(vec![&url]).await;
+        assert_eq!(results[0].loaded, 3);
+        
+        assert!(blocker.is_blocked("ads.example").await);
+        assert!(blocker.is_blocked("banner.ads.example").await);
+        assert!(blocker.is_blocked("track42.example.net").await);
+        assert!(!blocker.is_blocked("tracker.example.net").await);
+        assert!(blocker.is_blocked("pixel.test").await);
+        assert_eq!(blocker.block_source("track42.example.net").await, Some(url));
+        
+        blocker.add_allowed_domain("track42.example.net").await;
+        assert!(!blocker.is_blocked("track42.example.net").await);
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn loaded_hosts_files_store_clean_domains() {
+        let hosts = "0.0.0.0 ads.example.com #ad network\n0.0.0.0 pixel.test:8080\n0.0.0.0 #ad\n";
+        let server = MockServer::start(vec![("/hosts", vec![MockResponse::ok(hosts)])]).await;
+        let blocker = blocker();
+        blocker.load_additional_hosts(vec![&server.url("/hosts")]).await;
+        
+        assert_eq!(blocker.export_domains().await, ["ads.example.com", "pixel.test"]);
+        assert!(blocker.is_blocked("ads.example.com").await);
+    }
+    
+    #[test]
+    fn diff_lists_added_and_removed_domains() {
+        let diff = diff_domain_sets(&parse_hosts(HOSTS_V1), &parse_hosts(HOSTS_V2));
+        assert_eq!(diff.added, ["new-beacon.test", "new-pixel.test"]);
+        assert_eq!(diff.removed, ["old-miner.test"]);
+        
+        let same = diff_domain_sets(&parse_hosts(HOSTS_V2), &parse_hosts(HOSTS_V2));
+        assert!(same.added.is_empty() && same.removed.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn diff_with_remote_leaves_the_blocklist_alone() {
+        let server = MockServer::start(vec![("/v1", vec![MockResponse::ok(HOSTS_V1)]), ("/v2", vec![MockResponse::ok(HOSTS_V2)])]).await;
+        let blocker = blocker();
+        blocker.load_additional_hosts(vec![&server.url("/v1")]).await;
+        
+        let diff = blocker.diff_with_remote(&server.url("/v2")).await.unwrap();
+        
+        assert_eq!(diff.added, ["new-beacon.test", "new-pixel.test"]);
+        assert_eq!(diff.removed, ["old-miner.test"]);
+        assert!(blocker.is_blocked("old-miner.test").await);
+        assert!(!blocker.is_blocked("new-pixel.test").await);
+    }
+    
+    #[tokio::test]
+    async fn backends_are_interchangeable_as_domain_blockers() {
+        let hosts = blocker();
+        hosts.add_blocked_domain("ads.example.com").await;
+        let config = AdBlockerConfig {
+            enable_easylist: false,
+            enable_easyprivacy: false,
+            custom_filters: vec!["||ads.example.com^".to_string()],
+            ..AdBlockerConfig::default()
+        };
+        let filters = SimpleAdBlocker::with_config(config).await.unwrap();
+        
+        let backends: [&dyn DomainBlocker; 2] = [&hosts, &filters];
+        for backend in backends {
+            assert!(backend.is_domain_blocked("ads.example.com").await);
+            assert!(backend.is_domain_blocked("cdn.ads.example.com").await);
+            assert!(!backend.is_domain_blocked("news.example.com").await);
+        }
+    }
+}
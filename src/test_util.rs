@@ -0,0 +1,165 @@
+//! Helpers shared by the unit tests
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A canned HTTP response
+#[derive(Debug, Clone)]
+pub(crate) struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    delay: Duration,
+}
+
+impl MockResponse {
+    pub(crate) fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body: body.into(),
+            delay: Duration::ZERO,
+        }
+    }
+    
+    pub(crate) fn status(status: u16) -> Self {
+        Self { status, ..Self::ok("") }
+    }
+    
+    /// Wait this long before answering
+    pub(crate) fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+    
+    pub(crate) fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// An HTTP server on localhost. Each path answers with its responses in
+/// turn, repeating the last one; unknown paths get a 404.
+pub(crate) struct MockServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    pub(crate) async fn start(routes: Vec<(&str, Vec<MockResponse>)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let routes = routes.into_iter().map(|(path, responses)| (path.to_string(), (0, responses))).collect();
+        let routes = Arc::new(Mutex::new(routes));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        
+        let log = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(answer(stream, routes.clone(), log.clone()));
+            }
+        });
+        Self { addr, requests }
+    }
+    
+    pub(crate) fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+    
+    /// Heads of the requests received so far, in order
+    pub(crate) fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Responses by path, with how many of each have been served
+type Routes = Mutex<HashMap<String, (usize, Vec<MockResponse>)>>;
+
+async fn answer(mut stream: TcpStream, routes: Arc<Routes>, log: Arc<Mutex<Vec<String>>>) {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.ends_with(b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => head.extend_from_slice(&buf[..n]),
+        }
+    }
+    let head = String::from_utf8_lossy(&head).into_owned();
+    let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+    log.lock().unwrap().push(head);
+    
+    let response = match routes.lock().unwrap().get_mut(&path) {
+        Some((served, responses)) => {
+            let response = responses[(*served).min(responses.len() - 1)].clone();
+            *served += 1;
+            response
+        }
+        None => MockResponse::status(404),
+    };
+    tokio::time::sleep(response.delay).await;
+    
+    let mut out = format!("HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n", response.status, response.body.len());
+    for (name, value) in &response.headers {
+        out.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    out.push_str("\r\n");
+    let mut out = out.into_bytes();
+    out.extend_from_slice(&response.body);
+    let _ = stream.write_all(&out).await;
+    let _ = stream.shutdown().await;
+}
+
+/// A localhost URL nothing is listening on
+pub(crate) async fn closed_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    format!("http://{}/list.txt", addr)
+}
+
+/// A filter list body with `rules` under an Adblock Plus header
+pub(crate) fn filter_list(rules: &[&str]) -> String {
+    let mut list = String::from("[Adblock Plus 2.0]\n! Test list\n");
+    for rule in rules {
+        list.push_str(rule);
+        list.push('\n');
+    }
+    list
+}
+
+/// A path in the temp directory unique to this process, for tests that
+/// write files
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("ad-blocker-api-{}-{}", std::process::id(), name))
+}
+
+/// A clock stopped at a given local time
+pub(crate) struct FixedClock(pub(crate) chrono::NaiveDateTime);
+
+impl crate::schedule::Clock for FixedClock {
+    fn now(&self) -> chrono::NaiveDateTime {
+        self.0
+    }
+}
+
+/// A clock stopped at a given system time, for checks that measure elapsed time
+pub(crate) struct FixedSystemClock(pub(crate) std::time::SystemTime);
+
+impl crate::schedule::Clock for FixedSystemClock {
+    fn now(&self) -> chrono::NaiveDateTime {
+        chrono::DateTime::<chrono::Local>::from(self.0).naive_local()
+    }
+    
+    fn system_now(&self) -> std::time::SystemTime {
+        self.0
+    }
+}
+
+/// A local time on a given day of September 2026, which starts on a Tuesday
+pub(crate) fn september(day: u32, hour: u32, minute: u32) -> chrono::NaiveDateTime {
+    chrono::NaiveDate::from_ymd_opt(2026, 9, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+}
@@ -1,50 +1,444 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Result of checking if a URL should be blocked
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockResult {
     pub should_block: bool,
-    pub reason: String,
+    /// Whether the rules matched, even if `should_block` is `false` because
+    /// of `config.dry_run`
+    #[serde(default)]
+    pub would_block: bool,
+    pub reason: BlockReason,
     pub filter_matched: Option<String>,
+    /// Name of the filter list the matched rule came from, if known
+    pub source_list: Option<String>,
     pub category: BlockCategory,
+    /// Whether the request goes to a different site than `source_url`
+    /// (always `false` when no source URL was given)
+    pub is_third_party: bool,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// A verdict along with every category whose rules matched the URL, not
+/// just the first one `should_block` stopped at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockResultFull {
+    #[serde(flatten)]
+    pub result: BlockResult,
+    /// Categories of all matching rule families (engine, custom regex,
+    /// tracking, social), in that order and without duplicates. Empty for
+    /// clean and whitelisted URLs.
+    pub categories: Vec<BlockCategory>,
+}
+
+/// Every check `AdBlockerAPI::explain` ran on a URL and what each found,
+/// for debugging why a URL was or wasn't blocked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Explanation {
+    pub url: String,
+    /// Whether `should_block` would block the URL, ignoring `config.dry_run`
+    pub would_block: bool,
+    /// The check whose match settles the verdict: the first blocking check
+    /// that matched, or `Whitelist` when the whitelist lets the URL through.
+    /// `None` for clean URLs.
+    pub decided_by: Option<Check>,
+    /// Outcome of every check, in the order `should_block` runs them
+    pub checks: Vec<CheckOutcome>,
+}
+
+/// One of the checks `should_block` runs a URL through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Check {
+    /// Whitelisted domains and paths
+    Whitelist,
+    /// `config.blocked_ip_ranges`
+    IpRanges,
+    /// `config.enable_hosts_lists`
+    HostsLists,
+    /// The filter engine
+    Engine,
+    /// `config.regex_filters`
+    CustomRegex,
+    /// Tracking URL patterns
+    TrackingPatterns,
+    /// Social media URL patterns
+    SocialPatterns,
+}
+
+/// What a single check found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckOutcome {
+    pub check: Check,
+    pub status: CheckStatus,
+    /// What matched (a rule, pattern, range or list), why a check was
+    /// skipped, or an exception rule that stopped the engine matching
+    pub detail: Option<String>,
+}
+
+impl CheckOutcome {
+    pub(crate) fn matched(check: Check, detail: impl Into<String>) -> Self {
+        Self { check, status: CheckStatus::Matched, detail: Some(detail.into()) }
+    }
+    
+    pub(crate) fn no_match(check: Check) -> Self {
+        Self { check, status: CheckStatus::NoMatch, detail: None }
+    }
+    
+    pub(crate) fn skipped(check: Check, reason: impl Into<String>) -> Self {
+        Self { check, status: CheckStatus::Skipped, detail: Some(reason.into()) }
+    }
+    
+    /// `Matched` with what matched, or `NoMatch`
+    pub(crate) fn found(check: Check, found: Option<impl Into<String>>) -> Self {
+        found.map_or_else(|| Self::no_match(check), |detail| Self::matched(check, detail))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CheckStatus {
+    Matched,
+    NoMatch,
+    /// The check is disabled, not scheduled right now or doesn't apply to
+    /// the URL (e.g. IP ranges for a domain host)
+    Skipped,
+}
+
+/// Why a check reached its verdict. Displays as a short human-readable
+/// sentence, e.g. "Matched ad filter".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BlockReason {
+    /// A rule in the filter engine matched
+    AdFilter,
+    /// One of the tracking URL patterns matched
+    TrackingPattern,
+    /// One of the social media URL patterns matched
+    SocialPattern,
+    /// A `config.regex_filters` entry matched
+    CustomFilter,
+    /// The domain is in one of `config.enable_hosts_lists`
+    HostsList,
+    /// The host is an IP address in one of `config.blocked_ip_ranges`
+    IpRange,
+    /// The domain or path is whitelisted
+    Whitelisted,
+    /// Nothing matched
+    Clean,
+    /// The URL or domain couldn't be parsed
+    InvalidUrl,
+}
+
+impl fmt::Display for BlockReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            BlockReason::AdFilter => "Matched ad filter",
+            BlockReason::TrackingPattern => "Matched tracking pattern",
+            BlockReason::SocialPattern => "Matched social media pattern",
+            BlockReason::CustomFilter => "Matched custom regex filter",
+            BlockReason::HostsList => "Matched hosts list",
+            BlockReason::IpRange => "Matched blocked IP range",
+            BlockReason::Whitelisted => "Domain is whitelisted",
+            BlockReason::Clean => "No rule matched",
+            BlockReason::InvalidUrl => "Invalid URL format",
+        };
+        f.write_str(reason)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BlockCategory {
     Advertisement,
     Tracking,
     Malware,
+    Phishing,
+    Cryptomining,
     Social,
     Custom,
     Whitelisted,
     Clean,
 }
 
-/// Statistics about blocked content
+/// What kind of rule a filter line is, from `AdBlockerAPI::validate_filter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterKind {
+    /// A network blocking rule (e.g. `||ads.example.com^`)
+    Network,
+    /// A network exception rule (e.g. `@@||example.com^`)
+    Exception,
+    /// An element hiding rule (e.g. `example.com##.ad`)
+    Cosmetic,
+    /// A comment or list header (e.g. `! Title: EasyList`), which the engine ignores
+    Comment,
+}
+
+/// Type of resource a request loads, as guessed from its file extension.
+/// Rules scoped with options like `$image` or `$script` only match requests
+/// of that type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResourceType {
+    Script,
+    Stylesheet,
+    Image,
+    Font,
+    Media,
+    Other,
+}
+
+impl ResourceType {
+    /// Resource type for a URL path like `/static/app.js`, ignoring any
+    /// query string, fragment or `;param` suffix. Paths without a known
+    /// extension are `Other`.
+    pub fn from_path(path: &str) -> Self {
+        let path = path.split(['?', '#']).next().unwrap_or(path);
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        let file_name = file_name.split(';').next().unwrap_or(file_name);
+        let Some((_, extension)) = file_name.rsplit_once('.') else {
+            return ResourceType::Other;
+        };
+        match extension.to_ascii_lowercase().as_str() {
+            "js" | "mjs" => ResourceType::Script,
+            "css" => ResourceType::Stylesheet,
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" | "ico" => ResourceType::Image,
+            "woff" | "woff2" | "ttf" | "otf" => ResourceType::Font,
+            "mp4" | "webm" | "mp3" | "ogg" => ResourceType::Media,
+            _ => ResourceType::Other,
+        }
+    }
+    
+    /// The request type name the filter engine uses
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResourceType::Script => "script",
+            ResourceType::Stylesheet => "stylesheet",
+            ResourceType::Image => "image",
+            ResourceType::Font => "font",
+            ResourceType::Media => "media",
+            ResourceType::Other => "other",
+        }
+    }
+}
+
+/// Number of rules loaded into the engine, by kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterCounts {
+    /// Network blocking rules (e.g. `||ads.example.com^`)
+    pub network_rules: usize,
+    /// Network exception rules (e.g. `@@||example.com^`)
+    pub exception_rules: usize,
+    /// Element hiding rules (e.g. `example.com##.ad`)
+    pub cosmetic_rules: usize,
+    /// Network rules that block a whole domain (e.g. `||ads.example.com^`),
+    /// also counted in `network_rules`
+    pub domain_rules: usize,
+}
+
+/// How much the loaded lists overlap, from `AdBlockerAPI::load_summary`
+/// and `StevenBlackBlocker::load_summary`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadSummary {
+    /// Rules (or hosts entries) read across all lists
+    pub total_rules: usize,
+    /// Rules kept after collapsing duplicates
+    pub unique_rules: usize,
+    /// Rules already loaded from an earlier list, or earlier in the same one
+    pub duplicate_rules: usize,
+    /// Exception rules (e.g. `@@||example.com^`), which can cancel a block
+    /// from any list. For hosts lists, the domains on the allowlist.
+    pub exceptions: usize,
+    /// Names of the lists that failed to load and were skipped, either
+    /// because they are optional or because `config.fail_open` is set
+    pub failed_sources: Vec<String>,
+    /// Rules the engine couldn't parse and skipped, e.g. ones using modifiers
+    /// it doesn't support such as `$rewrite=` or `$sitekey=`
+    #[serde(default)]
+    pub unsupported_rules: Vec<String>,
+    /// Rules left out because `config.max_rules` was reached
+    #[serde(default)]
+    pub dropped_rules: usize,
+    /// How long downloading (or reading from the cache) took for each list,
+    /// including ones that failed. Lists load concurrently, so these can add
+    /// up to more than `load_duration`.
+    #[serde(default)]
+    pub source_load_durations: HashMap<String, Duration>,
+    /// Time spent loading all the lists
+    #[serde(default)]
+    pub load_duration: Duration,
+    /// Time spent compiling the rules into the engine, the last time it was built
+    #[serde(default)]
+    pub compile_duration: Duration,
+}
+
+/// An ad signature found by `AdBlockerAPI::scan_response_body`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BodyMatch {
+    /// The `config.body_signatures` entry that matched
+    pub signature: String,
+    /// Where it was found, as a byte range into the body
+    pub range: Range<usize>,
+}
+
+/// Summary of checking every resource a page loads, from `analyze_page`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageReport {
+    pub page_url: String,
+    pub total_resources: usize,
+    /// Resources on a different site than the page
+    pub third_party_resources: usize,
+    /// Resources by verdict category, including `Clean` and `Whitelisted`
+    pub category_counts: HashMap<BlockCategory, usize>,
+    /// Blocked resources and their verdicts, in the order given
+    pub blocked: Vec<(String, BlockResult)>,
+}
+
+/// Readiness of a blocker, e.g. for a `/healthz` endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusReport {
+    /// Whether filter lists have finished loading and checks are meaningful
+    pub ready: bool,
+    /// Network, exception and cosmetic rules in the engine
+    pub rules_loaded: usize,
+    /// Rules that block a whole domain (e.g. `||ads.example.com^`)
+    pub domains_loaded: usize,
+    /// When the engine was last built
+    pub last_reload: SystemTime,
+}
+
+/// A checked URL and its verdict, as recorded in the query log
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub url: String,
+    pub result: BlockResult,
+    pub timestamp: SystemTime,
+}
+
+/// Statistics about blocked content
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BlockStats {
     pub total_requests: u64,
     pub blocked_requests: u64,
     pub ads_blocked: u64,
     pub trackers_blocked: u64,
     pub malware_blocked: u64,
+    #[serde(default)]
+    pub phishing_blocked: u64,
+    #[serde(default)]
+    pub cryptomining_blocked: u64,
+    #[serde(default)]
+    pub social_blocked: u64,
+    #[serde(default)]
+    pub custom_blocked: u64,
     pub bytes_saved: u64,
+    /// `bytes_saved` broken down by the category of the blocked request
+    #[serde(default)]
+    pub bytes_saved_by_category: HashMap<BlockCategory, u64>,
 }
 
-impl Default for BlockStats {
-    fn default() -> Self {
-        Self {
-            total_requests: 0,
-            blocked_requests: 0,
-            ads_blocked: 0,
-            trackers_blocked: 0,
-            malware_blocked: 0,
-            bytes_saved: 0,
+impl BlockStats {
+    /// Add another instance's counters to this one
+    pub fn merge(&mut self, other: &BlockStats) {
+        // Destructure so adding a field to BlockStats fails to compile until it is merged here
+        let BlockStats {
+            total_requests,
+            blocked_requests,
+            ads_blocked,
+            trackers_blocked,
+            malware_blocked,
+            phishing_blocked,
+            cryptomining_blocked,
+            social_blocked,
+            custom_blocked,
+            bytes_saved,
+            bytes_saved_by_category,
+        } = other;
+        
+        self.total_requests += total_requests;
+        self.blocked_requests += blocked_requests;
+        self.ads_blocked += ads_blocked;
+        self.trackers_blocked += trackers_blocked;
+        self.malware_blocked += malware_blocked;
+        self.phishing_blocked += phishing_blocked;
+        self.cryptomining_blocked += cryptomining_blocked;
+        self.social_blocked += social_blocked;
+        self.custom_blocked += custom_blocked;
+        self.bytes_saved += bytes_saved;
+        for (category, bytes) in bytes_saved_by_category {
+            *self.bytes_saved_by_category.entry(*category).or_default() += bytes;
         }
     }
-}
-
-impl BlockStats {
+    
+    /// Sum the stats of several instances
+    pub fn aggregate<'a>(stats: impl IntoIterator<Item = &'a BlockStats>) -> BlockStats {
+        let mut total = BlockStats::default();
+        for s in stats {
+            total.merge(s);
+        }
+        total
+    }
+    
+    /// Blocked request counts keyed by category. Every blocking category is
+    /// present, including those with a count of zero.
+    pub fn by_category(&self) -> HashMap<BlockCategory, u64> {
+        HashMap::from([
+            (BlockCategory::Advertisement, self.ads_blocked),
+            (BlockCategory::Tracking, self.trackers_blocked),
+            (BlockCategory::Malware, self.malware_blocked),
+            (BlockCategory::Phishing, self.phishing_blocked),
+            (BlockCategory::Cryptomining, self.cryptomining_blocked),
+            (BlockCategory::Social, self.social_blocked),
+            (BlockCategory::Custom, self.custom_blocked),
+        ])
+    }
+    
+    /// Estimate the full request counts from ones where only a `rate`
+    /// fraction of checks was counted. `bytes_saved` is reported by callers
+    /// rather than sampled, so it is left alone.
+    pub(crate) fn scale_sampled(&mut self, rate: f64) {
+        if rate <= 0.0 || rate >= 1.0 {
+            return;
+        }
+        for count in [
+            &mut self.total_requests,
+            &mut self.blocked_requests,
+            &mut self.ads_blocked,
+            &mut self.trackers_blocked,
+            &mut self.malware_blocked,
+            &mut self.phishing_blocked,
+            &mut self.cryptomining_blocked,
+            &mut self.social_blocked,
+            &mut self.custom_blocked,
+        ] {
+            *count = (*count as f64 / rate).round() as u64;
+        }
+    }
+    
+    /// Render the counters in the Prometheus text exposition format, with
+    /// each metric name prefixed by `namespace` (e.g. `adblocker`)
+    pub fn to_prometheus(&self, namespace: &str) -> String {
+        let counters = [
+            ("total_requests", "Requests checked", self.total_requests),
+            ("blocked_requests", "Requests blocked", self.blocked_requests),
+            ("ads_blocked", "Requests blocked as advertisements", self.ads_blocked),
+            ("trackers_blocked", "Requests blocked as trackers", self.trackers_blocked),
+            ("malware_blocked", "Requests blocked as malware", self.malware_blocked),
+            ("phishing_blocked", "Requests blocked as phishing", self.phishing_blocked),
+            ("cryptomining_blocked", "Requests blocked as cryptomining", self.cryptomining_blocked),
+            ("social_blocked", "Requests blocked as social widgets", self.social_blocked),
+            ("custom_blocked", "Requests blocked by custom filters", self.custom_blocked),
+            ("bytes_saved", "Estimated bytes not downloaded", self.bytes_saved),
+        ];
+        
+        let mut out = String::new();
+        for (name, help, value) in counters {
+            let metric = format!("{}_{}_total", namespace, name);
+            out.push_str(&format!("# HELP {} {}\n", metric, help));
+            out.push_str(&format!("# TYPE {} counter\n", metric));
+            out.push_str(&format!("{} {}\n", metric, value));
+        }
+        out
+    }
+    
     pub fn block_percentage(&self) -> f64 {
         if self.total_requests == 0 {
             0.0
@@ -52,4 +446,301 @@ impl BlockStats {
             (self.blocked_requests as f64 / self.total_requests as f64) * 100.0
         }
     }
-}
\ No newline at end of file
+}
+
+/// Request and block counts for a single client, as recorded by `should_block_for`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientStats {
+    pub total_requests: u64,
+    pub blocked_requests: u64,
+    /// Block count per blocked domain
+    pub blocked_domains: HashMap<String, u64>,
+}
+
+impl ClientStats {
+    /// Record one check and, if it was blocked, the domain it was for
+    pub fn record(&mut self, domain: Option<&str>, blocked: bool) {
+        self.total_requests += 1;
+        if blocked {
+            self.blocked_requests += 1;
+            if let Some(domain) = domain {
+                *self.blocked_domains.entry(domain.to_string()).or_default() += 1;
+            }
+        }
+    }
+    
+    /// The `n` most frequently blocked domains, most blocked first
+    pub fn top_blocked(&self, n: usize) -> Vec<(String, u64)> {
+        let mut domains: Vec<(String, u64)> = self.blocked_domains
+            .iter()
+            .map(|(domain, count)| (domain.clone(), *count))
+            .collect();
+        domains.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        domains.truncate(n);
+        domains
+    }
+}
+
+/// Request and block counts for a single minute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MinuteBucket {
+    /// Minutes since the Unix epoch
+    pub minute: u64,
+    pub total_requests: u64,
+    pub blocked_requests: u64,
+}
+
+impl MinuteBucket {
+    /// Estimate the full counts, like `BlockStats::scale_sampled`
+    pub(crate) fn scale_sampled(&mut self, rate: f64) {
+        if rate <= 0.0 || rate >= 1.0 {
+            return;
+        }
+        for count in [&mut self.total_requests, &mut self.blocked_requests] {
+            *count = (*count as f64 / rate).round() as u64;
+        }
+    }
+}
+
+/// Rolling per-minute statistics over a fixed window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowedStats {
+    window_minutes: u64,
+    buckets: VecDeque<MinuteBucket>,
+}
+
+impl Default for WindowedStats {
+    fn default() -> Self {
+        Self::new(60)
+    }
+}
+
+impl WindowedStats {
+    /// Keep buckets for the last `window_minutes` minutes
+    pub fn new(window_minutes: u64) -> Self {
+        Self {
+            window_minutes: window_minutes.max(1),
+            buckets: VecDeque::new(),
+        }
+    }
+    
+    /// Current time in minutes since the Unix epoch
+    pub fn current_minute() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() / 60)
+            .unwrap_or(0)
+    }
+    
+    pub fn record_request(&mut self) {
+        self.record_request_at(Self::current_minute());
+    }
+    
+    pub fn record_block(&mut self) {
+        self.record_block_at(Self::current_minute());
+    }
+    
+    pub fn record_request_at(&mut self, minute: u64) {
+        self.bucket_mut(minute).total_requests += 1;
+    }
+    
+    pub fn record_block_at(&mut self, minute: u64) {
+        self.bucket_mut(minute).blocked_requests += 1;
+    }
+    
+    /// Buckets within the window ending now, oldest first
+    pub fn buckets(&self) -> Vec<MinuteBucket> {
+        self.buckets_at(Self::current_minute())
+    }
+    
+    /// Buckets within the window ending at `minute`, oldest first
+    pub fn buckets_at(&self, minute: u64) -> Vec<MinuteBucket> {
+        let oldest = minute.saturating_sub(self.window_minutes - 1);
+        self.buckets
+            .iter()
+            .filter(|bucket| bucket.minute >= oldest && bucket.minute <= minute)
+            .copied()
+            .collect()
+    }
+    
+    /// Bucket for `minute`, creating it and expiring buckets that fell out of the window
+    fn bucket_mut(&mut self, minute: u64) -> &mut MinuteBucket {
+        let newest = self.buckets.back().map_or(minute, |bucket| bucket.minute.max(minute));
+        let oldest = newest.saturating_sub(self.window_minutes - 1);
+        while self.buckets.front().is_some_and(|bucket| bucket.minute < oldest) {
+            self.buckets.pop_front();
+        }
+        
+        let index = match self.buckets.binary_search_by_key(&minute, |bucket| bucket.minute) {
+            Ok(index) => index,
+            Err(index) => {
+                self.buckets.insert(index, MinuteBucket {
+                    minute,
+                    total_requests: 0,
+                    blocked_requests: 0,
+                });
+                index
+            }
+        };
+        &mut self.buckets[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+    
+    #[test]
+    fn resource_type_follows_the_file_extension() {
+        let cases = [
+            ("/static/app.js", ResourceType::Script),
+            ("/theme/site.CSS", ResourceType::Stylesheet),
+            ("/img/logo.png", ResourceType::Image),
+            ("/img/photo.jpg", ResourceType::Image),
+            ("/img/spinner.gif", ResourceType::Image),
+            ("/img/hero.webp", ResourceType::Image),
+            ("/fonts/inter.woff", ResourceType::Font),
+            ("/fonts/inter.ttf", ResourceType::Font),
+            ("/video/intro.mp4", ResourceType::Media),
+            ("/video/intro.webm", ResourceType::Media),
+            ("/api/track", ResourceType::Other),
+            ("/", ResourceType::Other),
+            ("/page.html", ResourceType::Other),
+            // Query strings and parameters don't hide the extension
+            ("/ads/banner.png?v=2.css", ResourceType::Image),
+            ("/ads/loader.js;jsessionid=a1.b2", ResourceType::Script),
+            ("/ads.example/pixel?format=.gif", ResourceType::Other),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(ResourceType::from_path(path), expected, "{}", path);
+        }
+    }
+    
+    #[test]
+    fn windowed_stats_roll_over_each_minute() {
+        let mut stats = WindowedStats::new(3);
+        let start = 1_000_000;
+        for minute in start..start + 5 {
+            for _ in 0..minute - start + 1 {
+                stats.record_request_at(minute);
+            }
+            stats.record_block_at(minute);
+        }
+        
+        let buckets = stats.buckets_at(start + 4);
+        let counts: Vec<(u64, u64, u64)> = buckets.iter().map(|b| (b.minute - start, b.total_requests, b.blocked_requests)).collect();
+        assert_eq!(counts, [(2, 3, 1), (3, 4, 1), (4, 5, 1)]);
+        assert_eq!(stats.buckets_at(start + 6).len(), 1);
+        assert!(stats.buckets_at(start + 10).is_empty());
+    }
+    
+    #[test]
+    fn windowed_stats_skip_idle_minutes() {
+        let mut stats = WindowedStats::new(60);
+        stats.record_request_at(100);
+        stats.record_request_at(130);
+        stats.record_request_at(130);
+        
+        let minutes: Vec<(u64, u64)> = stats.buckets_at(130).iter().map(|b| (b.minute, b.total_requests)).collect();
+        assert_eq!(minutes, [(100, 1), (130, 2)]);
+        
+        // Recording far past the window expires the older buckets
+        stats.record_request_at(200);
+        assert_eq!(stats.buckets_at(200).len(), 1);
+    }
+    
+    fn sample_stats(base: u64) -> BlockStats {
+        BlockStats {
+            total_requests: base,
+            blocked_requests: base + 1,
+            ads_blocked: base + 2,
+            trackers_blocked: base + 3,
+            malware_blocked: base + 4,
+            phishing_blocked: base + 5,
+            cryptomining_blocked: base + 6,
+            social_blocked: base + 8,
+            custom_blocked: base + 9,
+            bytes_saved: base + 7,
+            bytes_saved_by_category: HashMap::from([(BlockCategory::Advertisement, base + 7)]),
+        }
+    }
+    
+    #[test]
+    fn merge_sums_every_counter() {
+        let (a, b) = (sample_stats(10), sample_stats(100));
+        let mut merged = a.clone();
+        merged.merge(&b);
+        
+        // Compare field by field through serde so a counter added later can't be skipped
+        let (a, b, merged) = (serde_json::to_value(&a).unwrap(), serde_json::to_value(&b).unwrap(), serde_json::to_value(&merged).unwrap());
+        for (field, value) in merged.as_object().unwrap() {
+            if let Some(value) = value.as_u64() {
+                assert_eq!(value, a[field].as_u64().unwrap() + b[field].as_u64().unwrap(), "{}", field);
+            }
+        }
+    }
+    
+    #[test]
+    fn aggregate_sums_all_instances() {
+        let total = BlockStats::aggregate([&sample_stats(0), &sample_stats(1), &BlockStats::default()]);
+        
+        assert_eq!(total.total_requests, 1);
+        assert_eq!(total.phishing_blocked, 11);
+        assert_eq!(total.bytes_saved, 15);
+        assert_eq!(total.bytes_saved_by_category[&BlockCategory::Advertisement], 15);
+        assert_eq!(BlockStats::aggregate([]).total_requests, 0);
+    }
+    
+    /// Samples in Prometheus text exposition output, checking each is a valid
+    /// metric name preceded by HELP and TYPE lines for it
+    fn parse_exposition(text: &str) -> HashMap<String, f64> {
+        let name_pattern = regex::Regex::new(r"^[a-zA-Z_:][a-zA-Z0-9_:]*$").unwrap();
+        let mut declared = HashSet::new();
+        let mut samples = HashMap::new();
+        for line in text.lines() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                let mut parts = comment.splitn(3, ' ');
+                let (keyword, name, rest) = (parts.next().unwrap(), parts.next().unwrap(), parts.next().unwrap());
+                match keyword {
+                    "HELP" => assert!(!rest.is_empty(), "{}", line),
+                    "TYPE" => {
+                        assert_eq!(rest, "counter", "{}", line);
+                        declared.insert(name.to_string());
+                    }
+                    _ => panic!("unexpected comment: {}", line),
+                }
+                continue;
+            }
+            let (name, value) = line.split_once(' ').unwrap();
+            assert!(name_pattern.is_match(name), "{}", name);
+            assert!(declared.contains(name), "{} has no TYPE line", name);
+            samples.insert(name.to_string(), value.parse().unwrap());
+        }
+        samples
+    }
+    
+    #[test]
+    fn prometheus_output_is_valid_exposition_text() {
+        let stats = BlockStats {
+            total_requests: 120,
+            blocked_requests: 30,
+            ads_blocked: 25,
+            social_blocked: 3,
+            custom_blocked: 2,
+            bytes_saved: 4096,
+            ..BlockStats::default()
+        };
+        
+        let samples = parse_exposition(&stats.to_prometheus("adblocker"));
+        
+        assert_eq!(samples.len(), 10);
+        assert_eq!(samples["adblocker_total_requests_total"], 120.0);
+        assert_eq!(samples["adblocker_blocked_requests_total"], 30.0);
+        assert_eq!(samples["adblocker_ads_blocked_total"], 25.0);
+        assert_eq!(samples["adblocker_social_blocked_total"], 3.0);
+        assert_eq!(samples["adblocker_custom_blocked_total"], 2.0);
+        assert_eq!(samples["adblocker_bytes_saved_total"], 4096.0);
+        assert_eq!(samples["adblocker_malware_blocked_total"], 0.0);
+    }
+}